@@ -0,0 +1,24 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ubus::*;
+
+// Run with `cargo fuzz run parse_message`. Any panic is a bug: malformed input must only
+// ever produce errors.
+fuzz_target!(|bytes: &[u8]| {
+    let Ok(message) = UbusMsg::from_bytes(bytes) else {
+        return;
+    };
+    let _ = format!("{:?} {:?}", message, message.blob);
+    let _ = format!("{:?}", message.hex_dump());
+    let _ = message.status();
+    let _ = UbusMonitorMsg::from_msg(&message);
+    for blob in message.blob.iter() {
+        let _ = UbusMsgAttr::from(blob).to_string();
+        let _ = parse_signature(&blob).count();
+        let _ = blobmsg_to_json(&blob);
+        for (_, value) in blob.msgs() {
+            let _ = value.to_string();
+        }
+    }
+});
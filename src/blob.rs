@@ -1,13 +1,22 @@
-use crate::{BlobMsg, BlobMsgPayload, BlobMsgType, UbusError};
+use crate::{BlobAttrId, BlobMsgType, UbusError, UbusStatus};
+#[cfg(feature = "std")]
+use crate::{BlobMsg, BlobMsgPayload};
 
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
 use core::convert::{TryFrom, TryInto};
 use core::marker::PhantomData;
-use core::mem::{align_of, size_of, transmute};
+use core::mem::{align_of, size_of};
 use core::str;
+#[cfg(feature = "std")]
 use std::collections::HashMap;
-use std::vec::Vec;
 use storage_endian::BEu32;
 
+/// Header in front of every blob attribute: a big-endian u32 laid out as
+///
+/// * bit 31: extended (blobmsg) flag
+/// * bits 24..=30: attribute id
+/// * bits 0..=23: length in bytes, including this tag but not trailing padding
 #[repr(transparent)]
 #[derive(Copy, Clone)]
 pub struct BlobTag(BEu32);
@@ -35,11 +44,11 @@ impl BlobTag {
 
     /// Create BlobTag from a byte array
     pub fn from_bytes(bytes: [u8; Self::SIZE]) -> Self {
-        unsafe { transmute(bytes) }
+        Self(u32::from_be_bytes(bytes).into())
     }
-    // Dump out bytes of MessageHeader
+    // Dump out bytes of BlobTag
     pub fn to_bytes(self) -> [u8; Self::SIZE] {
-        unsafe { core::mem::transmute(self) }
+        u32::from(self.0).to_be_bytes()
     }
     /// ID code of this blob
     pub fn id(&self) -> u32 {
@@ -54,12 +63,17 @@ impl BlobTag {
         let tag = Self::new(self.id(), size, self.is_extended()).unwrap();
         self.0 = tag.0
     }
+    /// Round `len` up to the 4-byte blob alignment, as libubox's `BLOB_ATTR_ALIGN` does.
+    /// A tag records the unpadded length; readers skip to the aligned one to find the next tag.
+    pub const fn aligned_len(len: usize) -> usize {
+        (len + Self::ALIGNMENT - 1) & !(Self::ALIGNMENT - 1)
+    }
     /// Number of padding bytes between this blob and the next blob
     fn padding(&self) -> usize {
-        Self::ALIGNMENT.wrapping_sub(self.size()) & (Self::ALIGNMENT - 1)
+        Self::aligned_len(self.size()) - self.size()
     }
-    /// Number of bytes to the next tag
-    fn next_tag(&self) -> usize {
+    /// Number of bytes to the next tag (size rounded up to the alignment)
+    pub fn padded_len(&self) -> usize {
         self.size() + self.padding()
     }
     /// Total number of bytes following the tag (extended header + data)
@@ -70,12 +84,24 @@ impl BlobTag {
     pub fn is_extended(&self) -> bool {
         (self.0 & Self::EXTENDED_BIT) != 0
     }
+    /// The blobmsg type of an extended blob, whose ID holds the type
+    pub fn msg_type(&self) -> Option<BlobMsgType> {
+        self.is_extended().then(|| BlobMsgType::from(self.id()))
+    }
     /// Does this blob look valid
     pub fn is_valid(&self) -> Result<(), UbusError> {
-        valid_data!(self.size() >= Self::SIZE, "Tag size smaller than tag");
+        if self.size() < Self::SIZE {
+            return Err(UbusError::InvalidBlobTag(*self));
+        }
         Ok(())
     }
 }
+impl PartialEq for BlobTag {
+    fn eq(&self, other: &Self) -> bool {
+        u32::from(self.0) == u32::from(other.0)
+    }
+}
+impl Eq for BlobTag {}
 impl core::fmt::Debug for BlobTag {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         let (id, len) = (self.id(), self.size());
@@ -98,6 +124,10 @@ impl<'a> BlobBuilder<'a> {
         self.push_bytes(id, &data.to_be_bytes())
     }
 
+    pub fn push_u64(&mut self, id: u32, data: u64) -> Result<(), UbusError> {
+        self.push_bytes(id, &data.to_be_bytes())
+    }
+
     pub fn push_bool(&mut self, id: u32, data: bool) -> Result<(), UbusError> {
         self.push_bytes(id, if data { &[1] } else { &[0] })
     }
@@ -110,22 +140,63 @@ impl<'a> BlobBuilder<'a> {
         &mut self,
         id: u32,
         data: impl IntoIterator<Item = &'b u8>,
+    ) -> Result<(), UbusError> {
+        self.push(id, false, data.into_iter().copied())
+    }
+
+    /// Push a blobmsg attribute: an extended tag, the padded name header, then `data`
+    pub fn push_msg<'b>(
+        &mut self,
+        id: u32,
+        name: &str,
+        data: impl IntoIterator<Item = &'b u8>,
+    ) -> Result<(), UbusError> {
+        let name_len: u16 = name
+            .len()
+            .try_into()
+            .map_err(|_| UbusError::InvalidData("blobmsg name too long"))?;
+        let len_bytes = name_len.to_be_bytes();
+        // Nul terminator plus enough padding to align the payload
+        let name_total_len = size_of::<u16>() + name.len() + 1;
+        let name_padding = BlobTag::aligned_len(name_total_len) - name_total_len;
+        let zeros = [0u8; BlobTag::ALIGNMENT];
+        let header = len_bytes
+            .iter()
+            .chain(name.as_bytes())
+            .chain(&zeros[..1 + name_padding])
+            .copied();
+        self.push(id, true, header.chain(data.into_iter().copied()))
+    }
+
+    fn push(
+        &mut self,
+        id: u32,
+        extended: bool,
+        data: impl IntoIterator<Item = u8>,
     ) -> Result<(), UbusError> {
         let iter = data.into_iter();
         let buffer = &mut self.buffer[self.offset..];
+        if buffer.len() < BlobTag::SIZE {
+            return Err(UbusError::InvalidData("BlobBuilder overflow!"));
+        }
 
         let mut offset = BlobTag::SIZE;
         for b in iter {
             if offset >= buffer.len() {
                 return Err(UbusError::InvalidData("BlobBuilder overflow!"));
             }
-            buffer[offset] = *b;
+            buffer[offset] = b;
             offset += 1;
         }
 
-        let tag = BlobTag::new(id, offset, false)?;
+        let tag = BlobTag::new(id, offset, extended)?;
         let pad = tag.padding();
+        if offset + pad > buffer.len() {
+            return Err(UbusError::InvalidData("BlobBuilder overflow!"));
+        }
         buffer[..4].copy_from_slice(&tag.to_bytes());
+        // Zero the padding so the next tag starts aligned on clean bytes
+        buffer[offset..offset + pad].fill(0);
 
         self.offset += offset + pad;
         Ok(())
@@ -140,12 +211,142 @@ impl<'a> BlobBuilder<'a> {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone)]
 pub struct Blob<'a> {
     pub tag: BlobTag,
     pub data: &'a [u8],
 }
 
+/// Byte-exact: the same tag and payload bytes. Padding after the payload is never part of
+/// a `Blob`, but padding between nested attributes is; see [`Blob::structural_eq`].
+impl PartialEq for Blob<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.tag == other.tag && self.data == other.data
+    }
+}
+impl Eq for Blob<'_> {}
+
+/// How many levels of nested attributes `Debug` descends into
+const DEBUG_DEPTH: usize = 4;
+
+/// The outer blob of a message and the attributes holding others are listed attribute by
+/// attribute; anything else is shown as one attribute with its value
+impl core::fmt::Debug for Blob<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "Blob(len={}) ", self.data.len())?;
+        let nested = !self.tag.is_extended()
+            && matches!(
+                BlobAttrId::from(self.tag.id()),
+                BlobAttrId::UNSPEC
+                    | BlobAttrId::DATA
+                    | BlobAttrId::SIGNATURE
+                    | BlobAttrId::SUBSCRIBERS
+            );
+        if nested {
+            DebugAttrs(self.data, 0).fmt(f)
+        } else {
+            DebugAttr(*self, 0).fmt(f)
+        }
+    }
+}
+
+/// Lists the attributes packed in a payload, `depth` levels down
+struct DebugAttrs<'a>(&'a [u8], usize);
+
+impl core::fmt::Debug for DebugAttrs<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let DebugAttrs(data, depth) = *self;
+        if depth >= DEBUG_DEPTH {
+            return f.write_str("[..]");
+        }
+        f.debug_list()
+            .entries(BlobIter::<Blob>::new(data).map(|blob| DebugAttr(blob, depth)))
+            .finish()
+    }
+}
+
+/// One attribute as `id -> len bytes`, followed by its value where the id has a known type
+/// and its bytes otherwise
+struct DebugAttr<'a>(Blob<'a>, usize);
+
+impl core::fmt::Debug for DebugAttr<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let DebugAttr(blob, depth) = *self;
+        let len = blob.data.len();
+        if blob.tag.is_extended() {
+            let ty = BlobMsgType::from(blob.tag.id());
+            let Ok((name, data)) = blob.msg_parts() else {
+                return write!(f, "{:?} -> {} bytes", ty, len);
+            };
+            write!(f, "{:?} ({:?}) -> {} bytes", name, ty, len)?;
+            let payload = Payload::from(data);
+            return match ty {
+                BlobMsgType::TABLE | BlobMsgType::ARRAY => {
+                    f.write_str(": ")?;
+                    DebugAttrs(data, depth + 1).fmt(f)
+                }
+                BlobMsgType::STRING => match TryInto::<&str>::try_into(payload) {
+                    Ok(value) => write!(f, ": {:?}", value),
+                    Err(_) => write!(f, ": {:02x?}", data),
+                },
+                BlobMsgType::INT64 => match TryInto::<i64>::try_into(payload) {
+                    Ok(value) => write!(f, ": {}", value),
+                    Err(_) => write!(f, ": {:02x?}", data),
+                },
+                BlobMsgType::INT32 => match TryInto::<i32>::try_into(payload) {
+                    Ok(value) => write!(f, ": {}", value),
+                    Err(_) => write!(f, ": {:02x?}", data),
+                },
+                BlobMsgType::INT16 => match TryInto::<i16>::try_into(payload) {
+                    Ok(value) => write!(f, ": {}", value),
+                    Err(_) => write!(f, ": {:02x?}", data),
+                },
+                BlobMsgType::INT8 => match TryInto::<i8>::try_into(payload) {
+                    Ok(value) => write!(f, ": {}", value),
+                    Err(_) => write!(f, ": {:02x?}", data),
+                },
+                BlobMsgType::DOUBLE => match TryInto::<f64>::try_into(payload) {
+                    Ok(value) => write!(f, ": {}", value),
+                    Err(_) => write!(f, ": {:02x?}", data),
+                },
+                _ => write!(f, ": {:02x?}", data),
+            };
+        }
+        let id = BlobAttrId::from(blob.tag.id());
+        write!(f, "{:?} -> {} bytes", id, len)?;
+        let payload = Payload::from(blob.data);
+        match id {
+            BlobAttrId::DATA | BlobAttrId::SIGNATURE | BlobAttrId::SUBSCRIBERS => {
+                f.write_str(": ")?;
+                DebugAttrs(blob.data, depth + 1).fmt(f)
+            }
+            BlobAttrId::STATUS if len == 4 => match TryInto::<u32>::try_into(payload) {
+                Ok(status) => write!(f, ": {:?}", UbusStatus::from(status)),
+                Err(_) => write!(f, ": {:02x?}", blob.data),
+            },
+            BlobAttrId::OBJID | BlobAttrId::OBJTYPE | BlobAttrId::TARGET if len == 4 => {
+                match TryInto::<u32>::try_into(payload) {
+                    Ok(value) => write!(f, ": {:#010x}", value),
+                    Err(_) => write!(f, ": {:02x?}", blob.data),
+                }
+            }
+            BlobAttrId::ACTIVE | BlobAttrId::NO_REPLY if len == 1 => {
+                match TryInto::<bool>::try_into(payload) {
+                    Ok(value) => write!(f, ": {}", value),
+                    Err(_) => write!(f, ": {:02x?}", blob.data),
+                }
+            }
+            BlobAttrId::OBJPATH | BlobAttrId::METHOD | BlobAttrId::USER | BlobAttrId::GROUP => {
+                match TryInto::<&str>::try_into(payload) {
+                    Ok(value) => write!(f, ": {:?}", value),
+                    Err(_) => write!(f, ": {:02x?}", blob.data),
+                }
+            }
+            _ => write!(f, ": {:02x?}", blob.data),
+        }
+    }
+}
+
 impl<'a> Blob<'a> {
     pub fn from_bytes(data: &'a [u8]) -> Result<Self, UbusError> {
         valid_data!(data.len() >= BlobTag::SIZE, "Blob too short");
@@ -163,14 +364,110 @@ impl<'a> Blob<'a> {
 
         Ok(Blob { tag, data })
     }
-}
 
-impl<'a> TryInto<BlobMsg<'a>> for Blob<'a> {
-    type Error = UbusError;
-    fn try_into(self) -> Result<BlobMsg<'a>, Self::Error> {
+    /// Iterate over the attributes contained in this blob
+    pub fn iter(&self) -> BlobIter<'a, Blob<'a>> {
+        BlobIter::new(self.data)
+    }
+
+    /// Find the first attribute inside this blob with the given id
+    pub fn get(&self, attr: BlobAttrId) -> Option<Blob<'a>> {
+        self.iter().find(|blob| blob.tag.id() == attr.value())
+    }
+
+    /// Read a string attribute, without the trailing NUL
+    pub fn get_str(&self, attr: BlobAttrId) -> Option<&'a str> {
+        Payload::from(self.get(attr)?.data).try_into().ok()
+    }
+
+    /// Read a single-byte boolean attribute
+    pub fn get_bool(&self, attr: BlobAttrId) -> Option<bool> {
+        match self.get(attr)?.data {
+            [value] => Some(*value != 0),
+            _ => None,
+        }
+    }
+
+    /// Read a big-endian u32 attribute, which must be exactly 4 bytes
+    pub fn get_u32(&self, attr: BlobAttrId) -> Option<u32> {
+        let bytes = self.get(attr)?.data.try_into().ok()?;
+        Some(u32::from_be_bytes(bytes))
+    }
+
+    /// Read a big-endian i32 attribute
+    pub fn get_i32(&self, attr: BlobAttrId) -> Option<i32> {
+        self.get_u32(attr).map(|value| value as i32)
+    }
+
+    /// Read a big-endian i64 attribute
+    pub fn get_i64(&self, attr: BlobAttrId) -> Option<i64> {
+        let bytes = self.get(attr)?.data.try_into().ok()?;
+        Some(i64::from_be_bytes(bytes))
+    }
+
+    /// Iterate over the payload as named blobmsg entries (e.g. the contents of a DATA
+    /// attribute), in wire order. Stops at the first malformed entry.
+    #[cfg(feature = "std")]
+    pub fn msgs(&self) -> impl Iterator<Item = (&'a str, BlobMsgPayload<'a>)> {
+        self.iter()
+            .map_while(|blob| TryInto::<BlobMsg>::try_into(blob).ok())
+            .map(|msg| (msg.name, msg.data))
+    }
+
+    /// Find the blobmsg entry called `name` in the payload
+    #[cfg(feature = "std")]
+    pub fn get_msg(&self, name: &str) -> Option<BlobMsgPayload<'a>> {
+        self.msgs()
+            .find(|(entry, _)| *entry == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Read the blobmsg DOUBLE entry called `name`
+    #[cfg(feature = "std")]
+    pub fn get_double(&self, name: &str) -> Option<f64> {
+        match self.get_msg(name)? {
+            BlobMsgPayload::Double(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Compare attribute by attribute rather than byte for byte: nested attributes (a
+    /// message's top-level blob, DATA, SIGNATURE and SUBSCRIBERS, blobmsg tables and arrays)
+    /// are walked, so the padding between them and after blobmsg names is ignored.
+    /// Everything else compares exactly.
+    pub fn structural_eq(&self, other: &Blob) -> bool {
+        if self.tag.id() != other.tag.id() || self.tag.is_extended() != other.tag.is_extended() {
+            return false;
+        }
+        if !self.tag.is_extended() {
+            return match BlobAttrId::from(self.tag.id()) {
+                BlobAttrId::UNSPEC
+                | BlobAttrId::DATA
+                | BlobAttrId::SIGNATURE
+                | BlobAttrId::SUBSCRIBERS => attrs_eq(self.data, other.data),
+                _ => self.data == other.data,
+            };
+        }
+        match (self.msg_parts(), other.msg_parts()) {
+            (Ok((name, payload)), Ok((other_name, other_payload))) => {
+                name == other_name
+                    && match BlobMsgType::from(self.tag.id()) {
+                        BlobMsgType::TABLE | BlobMsgType::ARRAY => attrs_eq(payload, other_payload),
+                        _ => payload == other_payload,
+                    }
+            }
+            _ => self.data == other.data,
+        }
+    }
+
+    /// Split an extended blob into its blobmsg name and the payload after the name padding
+    fn msg_parts(&self) -> Result<(&'a str, &'a [u8]), UbusError> {
         if !self.tag.is_extended() {
             return Err(UbusError::InvalidData("Not a extended blob"));
         }
+        if self.data.len() < size_of::<u16>() {
+            return Err(UbusError::InvalidData("No room for extended name length"));
+        }
         let (len_bytes, data) = self.data.split_at(size_of::<u16>());
         let name_len = u16::from_be_bytes(len_bytes.try_into().unwrap()) as usize;
         // Get the string
@@ -179,16 +476,41 @@ impl<'a> TryInto<BlobMsg<'a>> for Blob<'a> {
             return Err(UbusError::InvalidData("name lenth > data lenth"));
         }
         let (name_bytes, data) = data.split_at(name_len);
-        let name = str::from_utf8(name_bytes).unwrap();
+        let name = str::from_utf8(name_bytes)?;
         // Get the nul terminator (implicit)
         let name_len = name_len + 1;
-        let (terminator, data) = data.split_at(1);
-        valid_data!(terminator[0] == b'\0', "No extended name nul terminator");
+        let Some((b'\0', data)) = data.split_first() else {
+            return Err(UbusError::InvalidData("No extended name nul terminator"));
+        };
         // Ensure the rest of the payload is aligned
         let name_total_len = size_of::<u16>() + name_len;
-        let name_padding =
-            BlobTag::ALIGNMENT.wrapping_sub(name_total_len) & (BlobTag::ALIGNMENT - 1);
-        let payload = Payload::from(&data[name_padding..]);
+        let name_padding = BlobTag::aligned_len(name_total_len) - name_total_len;
+        let data = data
+            .get(name_padding..)
+            .ok_or(UbusError::InvalidData("Extended name padding truncated"))?;
+        Ok((name, data))
+    }
+}
+
+/// Pairwise [`Blob::structural_eq`] over the attributes packed in two payloads
+fn attrs_eq(data: &[u8], other: &[u8]) -> bool {
+    let mut attrs = BlobIter::<Blob>::new(data);
+    let mut others = BlobIter::<Blob>::new(other);
+    loop {
+        match (attrs.next(), others.next()) {
+            (Some(attr), Some(other)) if attr.structural_eq(&other) => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> TryInto<BlobMsg<'a>> for Blob<'a> {
+    type Error = UbusError;
+    fn try_into(self) -> Result<BlobMsg<'a>, Self::Error> {
+        let (name, data) = self.msg_parts()?;
+        let payload = Payload::from(data);
         let data = match self.tag.id().into() {
             BlobMsgType::ARRAY => BlobMsgPayload::Array(payload.try_into()?),
             BlobMsgType::TABLE => BlobMsgPayload::Table(payload.try_into()?),
@@ -218,7 +540,7 @@ macro_rules! payload_try_into_number {
             type Error = UbusError;
             fn try_into(self) -> Result<$ty, Self::Error> {
                 let size = size_of::<$ty>();
-                if let Ok(bytes) = self.0[..size].try_into() {
+                if let Some(Ok(bytes)) = self.0.get(..size).map(TryInto::try_into) {
                     Ok(<$ty>::from_be_bytes(bytes))
                 } else {
                     Err(UbusError::InvalidData(stringify!("Blob wrong size for " $ty)))
@@ -231,23 +553,26 @@ payload_try_into_number!(u8, i8, u16, i16, u32, i32, u64, i64, f64,);
 impl<'a> TryInto<bool> for Payload<'a> {
     type Error = UbusError;
     fn try_into(self) -> Result<bool, Self::Error> {
-        let value: u8 = self.0[0];
-        Ok(value != 0)
+        match self.0.first() {
+            Some(value) => Ok(*value != 0),
+            None => Err(UbusError::InvalidData("Blob wrong size for bool")),
+        }
     }
 }
 
 impl<'a> TryInto<&'a str> for Payload<'a> {
     type Error = UbusError;
+    /// Strings must end in the NUL ubus writes, which is left off. As in libubox, only the
+    /// last byte is checked.
     fn try_into(self) -> Result<&'a str, UbusError> {
-        let data = if self.0.last() == Some(&b'\0') {
-            &self.0[..self.0.len() - 1]
-        } else {
-            self.into()
+        let Some((b'\0', data)) = self.0.split_last() else {
+            return Err(UbusError::InvalidData("String missing nul terminator"));
         };
         str::from_utf8(data).map_err(UbusError::from)
     }
 }
 
+#[cfg(feature = "std")]
 impl<'a> TryInto<Vec<BlobMsg<'a>>> for Payload<'a> {
     type Error = UbusError;
     fn try_into(self) -> Result<Vec<BlobMsg<'a>>, UbusError> {
@@ -260,6 +585,7 @@ impl<'a> TryInto<Vec<BlobMsg<'a>>> for Payload<'a> {
     }
 }
 
+#[cfg(feature = "std")]
 impl<'a> TryInto<HashMap<&'a str, BlobMsgPayload<'a>>> for Payload<'a> {
     type Error = UbusError;
     fn try_into(self) -> Result<HashMap<&'a str, BlobMsgPayload<'a>>, UbusError> {
@@ -307,18 +633,31 @@ impl<'a, T> BlobIter<'a, T> {
 impl<'a, T: TryFrom<Blob<'a>>> Iterator for BlobIter<'a, T> {
     type Item = T;
     fn next(&mut self) -> Option<Self::Item> {
-        if self.data.is_empty() {
+        // Not even room for a tag: treat trailing bytes as the end
+        if self.data.len() < BlobTag::SIZE {
+            self.data = &[];
             return None;
         }
-        if let Ok(blob) = Blob::from_bytes(self.data) {
-            // Advance the internal pointer to the next tag
-            self.data = &self.data[blob.tag.next_tag()..];
-            if let Ok(blob) = blob.try_into() {
-                //println!("{:?}", self.data);
-                return Some(blob);
-            }
+        let (tag, data) = self.data.split_at(BlobTag::SIZE);
+        let tag = BlobTag::from_bytes(tag.try_into().unwrap());
+        // A tag claiming more than is left is truncated or corrupt: stop here
+        if tag.size() < BlobTag::SIZE || tag.inner_len() > data.len() {
+            self.data = &[];
+            return None;
         }
-        None
+        let blob = Blob {
+            tag,
+            data: &data[..tag.inner_len()],
+        };
+        // Advance the internal pointer to the next tag (the last one may be unpadded)
+        self.data = self.data.get(tag.padded_len()..).unwrap_or(&[]);
+        blob.try_into().ok()
+    }
+}
+
+impl<T> Clone for BlobIter<'_, T> {
+    fn clone(&self) -> Self {
+        Self::new(self.data)
     }
 }
 
@@ -328,11 +667,13 @@ impl<T> core::fmt::Debug for BlobIter<'_, T> {
     }
 }
 
+#[cfg(feature = "alloc")]
 pub struct BlobMsgBuilder<'a> {
     buffer: Vec<u8>,
     _phantom: PhantomData<&'a mut [u8]>,
 }
 
+#[cfg(feature = "std")]
 impl<'a> TryFrom<BlobMsg<'a>> for BlobMsgBuilder<'a> {
     type Error = UbusError;
 
@@ -401,6 +742,7 @@ impl<'a> TryFrom<BlobMsg<'a>> for BlobMsgBuilder<'a> {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl<'a> BlobMsgBuilder<'a> {
     pub fn from_bytes(bytes: &'a [u8]) -> Self {
         Self {
@@ -420,8 +762,7 @@ impl<'a> BlobMsgBuilder<'a> {
         blob.buffer.extend_from_slice(name.as_bytes());
         blob.buffer.push(b'\0');
         let name_total_len = size_of::<u16>() + name.len() + 1;
-        let name_padding =
-            BlobTag::ALIGNMENT.wrapping_sub(name_total_len) & (BlobTag::ALIGNMENT - 1);
+        let name_padding = BlobTag::aligned_len(name_total_len) - name_total_len;
         blob.buffer.resize(blob.buffer.len() + name_padding, 0u8);
         let tag = BlobTag::new(id, blob.buffer.len(), true).unwrap();
         blob.buffer[..4].copy_from_slice(&tag.to_bytes());
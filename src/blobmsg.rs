@@ -1,8 +1,12 @@
-use std::collections::HashMap;
-use std::fmt;
-use std::vec::Vec;
+#[cfg(feature = "json")]
+use std::string::{String, ToString};
+#[cfg(feature = "std")]
+use std::{collections::HashMap, fmt, vec::Vec};
 
-use serde::{Deserialize, Serialize};
+#[cfg(feature = "json")]
+use crate::{Blob, BlobAttrId, TableBuilder, UbusError, UbusMsgBuilder};
+#[cfg(feature = "json")]
+use serde_json::{Number, Value};
 
 values!(pub BlobMsgType(u32) {
     UNSPEC = 0,
@@ -13,11 +17,17 @@ values!(pub BlobMsgType(u32) {
     INT32  = 5,
     INT16  = 6,
     BOOL   = 7,
-    INT8   = 7,
     DOUBLE = 8,
 });
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl BlobMsgType {
+    /// libubox has no separate boolean encoding: BOOL is an alias of INT8
+    pub const INT8: Self = Self::BOOL;
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BlobMsgPayload<'a> {
     Array(Vec<BlobMsg<'a>>),
     Table(HashMap<&'a str, BlobMsgPayload<'a>>),
@@ -31,6 +41,26 @@ pub enum BlobMsgPayload<'a> {
     Unknown(u32, &'a [u8]),
 }
 
+#[cfg(feature = "std")]
+impl BlobMsgPayload<'_> {
+    /// Type id this payload is encoded with
+    pub fn ty(&self) -> BlobMsgType {
+        match self {
+            BlobMsgPayload::Array(_) => BlobMsgType::ARRAY,
+            BlobMsgPayload::Table(_) => BlobMsgType::TABLE,
+            BlobMsgPayload::String(_) => BlobMsgType::STRING,
+            BlobMsgPayload::Int64(_) => BlobMsgType::INT64,
+            BlobMsgPayload::Int32(_) => BlobMsgType::INT32,
+            BlobMsgPayload::Int16(_) => BlobMsgType::INT16,
+            BlobMsgPayload::Int8(_) => BlobMsgType::INT8,
+            BlobMsgPayload::Bool(_) => BlobMsgType::BOOL,
+            BlobMsgPayload::Double(_) => BlobMsgType::DOUBLE,
+            BlobMsgPayload::Unknown(ty, _) => BlobMsgType::from(*ty),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
 impl fmt::Display for BlobMsgPayload<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -50,7 +80,108 @@ impl fmt::Display for BlobMsgPayload<'_> {
     }
 }
 
+/// Converts the way `ubus call` prints replies: int8 (the boolean encoding) becomes a JSON
+/// bool, and unknown types become null. 64-bit ints stay exact in a `Value`, but like any
+/// JSON they may lose precision past 2^53 in consumers that parse numbers as doubles.
+/// Non-finite doubles have no JSON form and become null.
+#[cfg(feature = "json")]
+impl From<&BlobMsgPayload<'_>> for Value {
+    fn from(payload: &BlobMsgPayload) -> Self {
+        match payload {
+            BlobMsgPayload::Array(list) => list.iter().map(|msg| Value::from(&msg.data)).collect(),
+            BlobMsgPayload::Table(table) => Value::Object(
+                table
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.into()))
+                    .collect(),
+            ),
+            BlobMsgPayload::String(s) => Value::from(*s),
+            BlobMsgPayload::Int64(num) => Value::from(*num),
+            BlobMsgPayload::Int32(num) => Value::from(*num),
+            BlobMsgPayload::Int16(num) => Value::from(*num),
+            BlobMsgPayload::Int8(num) | BlobMsgPayload::Bool(num) => Value::Bool(*num != 0),
+            BlobMsgPayload::Double(num) => {
+                Number::from_f64(*num).map_or(Value::Null, Value::Number)
+            }
+            BlobMsgPayload::Unknown(..) => Value::Null,
+        }
+    }
+}
+
+/// Convert the blobmsg entries in `blob`'s payload (e.g. a DATA attribute) to a JSON object
+#[cfg(feature = "json")]
+pub fn blobmsg_to_json(blob: &Blob) -> Value {
+    Value::Object(
+        blob.msgs()
+            .map(|(name, value)| (name.to_string(), Value::from(&value)))
+            .collect(),
+    )
+}
+
+/// [`blobmsg_to_json`] rendered as a string, on one line or, if `pretty`, indented by two
+/// spaces per level. Keys come out sorted rather than in wire order, as with any `Value`,
+/// and `ubus call` indents with tabs, so match on the JSON rather than the text.
+#[cfg(feature = "json")]
+pub fn blobmsg_to_json_string(blob: &Blob, pretty: bool) -> String {
+    let json = blobmsg_to_json(blob);
+    let text = if pretty {
+        serde_json::to_string_pretty(&json)
+    } else {
+        serde_json::to_string(&json)
+    };
+    // Serializing a `Value` only fails for non-string map keys, which it can't hold
+    text.unwrap()
+}
+
+/// Write a JSON object into `builder` as a DATA table, the inverse of [`blobmsg_to_json`]:
+/// what `ubus call obj method '{...}'` sends. Integers are INT32 when they fit and INT64
+/// otherwise, like libubox's `blobmsg_add_json_element`; integers past `i64::MAX` and other
+/// numbers are DOUBLE, and null is an empty UNSPEC.
+#[cfg(feature = "json")]
+pub fn json_to_blobmsg(value: &Value, builder: &mut UbusMsgBuilder) -> Result<(), UbusError> {
+    let Value::Object(object) = value else {
+        return Err(UbusError::InvalidData("JSON arguments must be an object"));
+    };
+    let mut table = builder.open_table(BlobAttrId::DATA)?;
+    for (name, value) in object {
+        put_json(&mut table, name, value)?;
+    }
+    table.close()
+}
+
+#[cfg(feature = "json")]
+fn put_json(table: &mut TableBuilder, name: &str, value: &Value) -> Result<(), UbusError> {
+    match value {
+        Value::Null => table.put_msg(BlobMsgType::UNSPEC, name, &[]),
+        Value::Bool(value) => table.put_bool(name, *value),
+        Value::Number(num) => match num.as_i64() {
+            Some(num) => match i32::try_from(num) {
+                Ok(num) => table.put_i32(name, num),
+                Err(_) => table.put_i64(name, num),
+            },
+            None => table.put_double(name, num.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(value) => table.put_str(name, value),
+        Value::Array(list) => {
+            let mut array = table.open_array(name)?;
+            for value in list {
+                put_json(&mut array.0, "", value)?;
+            }
+            array.close()
+        }
+        Value::Object(object) => {
+            let mut nested = table.open_table(name)?;
+            for (name, value) in object {
+                put_json(&mut nested, name, value)?;
+            }
+            nested.close()
+        }
+    }
+}
+
+#[cfg(feature = "std")]
 struct List<'a>(&'a Vec<BlobMsg<'a>>);
+#[cfg(feature = "std")]
 impl<'a> fmt::Display for List<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "[")?;
@@ -68,7 +199,9 @@ impl<'a> fmt::Display for List<'a> {
     }
 }
 
+#[cfg(feature = "std")]
 struct Dict<'a>(&'a HashMap<&'a str, BlobMsgPayload<'a>>);
+#[cfg(feature = "std")]
 impl<'a> fmt::Display for Dict<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{{")?;
@@ -86,12 +219,43 @@ impl<'a> fmt::Display for Dict<'a> {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BlobMsg<'a> {
     pub name: &'a str,
     pub data: BlobMsgPayload<'a>,
 }
 
+#[cfg(feature = "std")]
+impl<'a> BlobMsg<'a> {
+    /// Named entries of a table value, or `None` if this isn't a table. Duplicate names
+    /// were already resolved while parsing: the last one wins.
+    pub fn as_table(&self) -> Option<impl Iterator<Item = (&'a str, &BlobMsgPayload<'a>)>> {
+        match &self.data {
+            BlobMsgPayload::Table(table) => Some(table.iter().map(|(name, value)| (*name, value))),
+            _ => None,
+        }
+    }
+
+    /// Elements of an array value in order, or `None` if this isn't an array
+    pub fn as_array(&self) -> Option<impl Iterator<Item = &BlobMsgPayload<'a>>> {
+        match &self.data {
+            BlobMsgPayload::Array(array) => Some(array.iter().map(|element| &element.data)),
+            _ => None,
+        }
+    }
+
+    /// Look up the entry called `name` in a table value
+    pub fn get(&self, name: &str) -> Option<&BlobMsgPayload<'a>> {
+        match &self.data {
+            BlobMsgPayload::Table(table) => table.get(name),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
 impl fmt::Display for BlobMsg<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.name.len() > 0 {
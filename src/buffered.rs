@@ -0,0 +1,70 @@
+use crate::{UbusError, IO};
+use alloc::vec::Vec;
+
+/// Batches outgoing messages into fewer writes on the underlying transport, for services
+/// sending many notifications. Each `put` is kept whole: the buffer is flushed before it
+/// would overflow `capacity`, and a message that doesn't fit at all is written straight
+/// through. Buffered bytes are flushed before every read, so a request always goes out
+/// before its reply is waited for; anything still buffered when this is dropped is lost,
+/// so call [`BufferedWriter::flush`] when done sending.
+pub struct BufferedWriter<T: IO> {
+    io: T,
+    buffer: Vec<u8>,
+    capacity: usize,
+}
+
+impl<T: IO> BufferedWriter<T> {
+    pub fn new(io: T, capacity: usize) -> Self {
+        Self {
+            io,
+            buffer: Vec::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Write out everything buffered with a single `put`
+    pub fn flush(&mut self) -> Result<(), UbusError> {
+        if !self.buffer.is_empty() {
+            self.io.put(&self.buffer)?;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+
+    /// Number of bytes waiting for the next flush
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.io
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.io
+    }
+
+    /// Flush and return the underlying transport
+    pub fn into_inner(mut self) -> Result<T, UbusError> {
+        self.flush()?;
+        Ok(self.io)
+    }
+}
+
+impl<T: IO> IO for BufferedWriter<T> {
+    type Error = T::Error;
+    fn put(&mut self, data: &[u8]) -> Result<(), UbusError> {
+        if self.buffer.len() + data.len() > self.capacity {
+            self.flush()?;
+        }
+        if data.len() > self.capacity {
+            return self.io.put(data);
+        }
+        self.buffer.extend_from_slice(data);
+        Ok(())
+    }
+    fn get(&mut self, data: &mut [u8]) -> Result<(), UbusError> {
+        self.flush()?;
+        self.io.get(data)
+    }
+}
@@ -1,15 +1,20 @@
 use crate::*;
 
-use core::panic;
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
 use std::collections::HashMap;
-extern crate alloc;
-use alloc::string::String;
+#[cfg(feature = "json")]
 use std::format;
 use ubuserror::*;
 
 #[derive(Copy, Clone)]
 pub struct ObjectResult<'a> {
     pub path: &'a str,
+    /// Allocated at random by ubusd, not derived from `path`
     pub id: u32,
     pub ty: u32,
 }
@@ -25,12 +30,44 @@ pub struct SignatureResult<'a> {
     pub args: HashMap<&'a str, BlobMsgType>,
 }
 
-#[derive(Clone, Copy)]
+/// A DATA attribute with nothing in it, for messages that leave DATA out
+pub(crate) fn empty_data() -> Result<Blob<'static>, UbusError> {
+    let tag = BlobTag::new(BlobAttrId::DATA.value(), BlobTag::SIZE, false)?;
+    Blob::from_tag_and_data(tag, &[])
+}
+
+#[derive(Clone)]
 pub struct Connection<T: IO> {
     io: T,
-    peer: u32,
+    peer: PeerId,
     sequence: u16,
-    buffer: [u8; 64 * 1024],
+    /// Room for the largest message ubusd sends, `MAX_MSG_SIZE` plus the header
+    buffer: Vec<u8>,
+    /// Sequence number and peer of requests still waiting for their STATUS
+    outstanding: Vec<(u16, u32)>,
+    /// Wire bytes of replies that arrived while waiting for a different request
+    stashed: Vec<Vec<u8>>,
+    /// Wire bytes of INVOKEs that arrived while waiting for a reply, handed out first by
+    /// [`Connection::next_message`]
+    invokes: Vec<Vec<u8>>,
+    /// Opens a fresh transport for [`Connection::reconnect`]
+    connector: Option<Arc<Connector<T>>>,
+    /// Objects added through this connection, to register again after reconnecting
+    objects: Vec<RegisteredObject>,
+    /// (our object id, target id) of each subscription, to renew after reconnecting
+    subscriptions: Vec<(u32, u32)>,
+}
+
+type Connector<T> = dyn Fn() -> Result<T, UbusError> + Send + Sync;
+
+#[derive(Clone)]
+struct RegisteredObject {
+    path: String,
+    id: u32,
+    ty: u32,
+    methods: Vec<(String, Vec<(String, BlobMsgType)>)>,
+    /// Whether anyone is subscribed, as last reported by ubusd
+    active: bool,
 }
 
 impl<T: IO> Connection<T> {
@@ -38,96 +75,617 @@ impl<T: IO> Connection<T> {
     pub fn new(io: T) -> Result<Self, UbusError> {
         let mut conn = Self {
             io,
-            peer: 0,
+            peer: PeerId(0),
             sequence: 0,
-            buffer: [0u8; 64 * 1024],
+            buffer: vec![0u8; UbusMsgHeader::SIZE + MAX_MSG_SIZE],
+            outstanding: Vec::new(),
+            stashed: Vec::new(),
+            invokes: Vec::new(),
+            connector: None,
+            objects: Vec::new(),
+            subscriptions: Vec::new(),
         };
 
-        // ubus server should say hello on connect
-        let message = conn.next_message()?;
+        conn.peer = conn.hello()?;
+
+        Ok(conn)
+    }
+
+    /// Connect with an IO opened by `connect`, which is kept so that
+    /// [`Connection::reconnect`] can open a new one
+    pub fn with_connector(
+        connect: impl Fn() -> Result<T, UbusError> + Send + Sync + 'static,
+    ) -> Result<Self, UbusError> {
+        let mut conn = Self::new(connect()?)?;
+        conn.connector = Some(Arc::new(connect));
+        Ok(conn)
+    }
+
+    /// Open a new transport with the connector given to [`Connection::with_connector`],
+    /// e.g. after ubusd restarted, and restore this connection's state on it: objects
+    /// added through it are registered again and its subscriptions renewed. Requests in
+    /// flight are forgotten.
+    ///
+    /// ubusd assigns new ids to the objects (see [`Connection::objects`]), and
+    /// subscriptions move to the new ids. A subscription is renewed with the same target
+    /// id; if ubusd restarted that target most likely has a new id too, so the renewal
+    /// fails. Those subscriptions are dropped and returned, to be looked up again.
+    pub fn reconnect(&mut self) -> Result<Vec<(u32, u32)>, UbusError> {
+        let connect = self
+            .connector
+            .clone()
+            .ok_or(UbusError::InvalidData("Connection has no connector"))?;
+        self.io = connect()?;
+        self.outstanding.clear();
+        self.stashed.clear();
+        self.invokes.clear();
+        self.peer = self.hello()?;
+
+        let mut renumbered = Vec::new();
+        let mut pending = core::mem::take(&mut self.objects).into_iter();
+        while let Some(object) = pending.next() {
+            let methods: Vec<(&str, Vec<(&str, BlobMsgType)>)> = object
+                .methods
+                .iter()
+                .map(|(name, args)| {
+                    let args = args.iter().map(|(arg, ty)| (arg.as_str(), *ty)).collect();
+                    (name.as_str(), args)
+                })
+                .collect();
+            let methods: Vec<(&str, &[(&str, BlobMsgType)])> = methods
+                .iter()
+                .map(|(name, args)| (*name, args.as_slice()))
+                .collect();
+            match self.add_object(&object.path, &methods) {
+                Ok(added) => renumbered.push((object.id, added.id)),
+                Err(error) => {
+                    // Keep the objects not registered yet, so another reconnect retries them
+                    self.objects.push(object);
+                    self.objects.extend(pending);
+                    self.renumber_subscriptions(&renumbered);
+                    return Err(error);
+                }
+            }
+        }
+        self.renumber_subscriptions(&renumbered);
+
+        let mut lost = Vec::new();
+        let mut pending = core::mem::take(&mut self.subscriptions).into_iter();
+        while let Some((obj_id, target)) = pending.next() {
+            match self.subscribe(obj_id, target) {
+                Ok(()) => {}
+                Err(UbusError::Status(_)) => lost.push((obj_id, target)),
+                Err(error) => {
+                    // As with objects, keep the rest so another reconnect renews them
+                    self.subscriptions.extend(lost);
+                    self.subscriptions.push((obj_id, target));
+                    self.subscriptions.extend(pending);
+                    return Err(error);
+                }
+            }
+        }
+        Ok(lost)
+    }
+
+    /// Move subscriptions made for our objects' old ids to their `(old, new)` ids
+    fn renumber_subscriptions(&mut self, renumbered: &[(u32, u32)]) {
+        for (obj_id, _) in self.subscriptions.iter_mut() {
+            if let Some((_, new)) = renumbered.iter().find(|(old, _)| old == obj_id) {
+                *obj_id = *new;
+            }
+        }
+    }
+
+    /// Whether our object `obj_id` has subscribers, as last reported by ubusd. ubusd sends a
+    /// NOTIFY with ACTIVE whenever the first subscriber arrives or the last one leaves, and
+    /// [`Connection::next_message`] records it, so a publisher can skip building events
+    /// nobody would receive. False for objects not added through this connection.
+    pub fn has_subscribers(&self, obj_id: u32) -> bool {
+        self.objects
+            .iter()
+            .any(|object| object.id == obj_id && object.active)
+    }
+
+    fn set_active(&mut self, obj_id: u32, active: bool) {
+        for object in self.objects.iter_mut().filter(|object| object.id == obj_id) {
+            object.active = active;
+        }
+    }
+
+    /// Objects currently registered through this connection
+    pub fn objects(&self) -> impl Iterator<Item = ObjectResult<'_>> {
+        self.objects.iter().map(|object| ObjectResult {
+            path: &object.path,
+            id: object.id,
+            ty: object.ty,
+        })
+    }
+
+    /// Wait for the HELLO ubusd sends on connect, returning the peer id it assigned us
+    fn hello(&mut self) -> Result<PeerId, UbusError> {
+        let message = self.next_message()?;
 
         // Verify the header is what we expect
-        valid_data!(
-            message.header.cmd_type == UbusCmdType::HELLO,
-            "Expected hello"
-        );
+        if message.header.cmd_type != UbusCmdType::HELLO {
+            return Err(UbusError::InvalidData("Expected hello"));
+        }
 
-        // Record our peer id
-        conn.peer = message.header.peer.into();
+        Ok(message.header.peer_id())
+    }
 
-        Ok(conn)
+    /// Peer id ubusd assigned to this connection
+    pub fn peer(&self) -> PeerId {
+        self.peer
+    }
+
+    pub(crate) fn io(&self) -> &T {
+        &self.io
     }
 
+    pub(crate) fn io_mut(&mut self) -> &mut T {
+        &mut self.io
+    }
+
+    // Sequence numbers wrap around after 65535. 0 is skipped, being what ubusd uses for
+    // its HELLO and unsolicited messages, and so are numbers of requests still in flight,
+    // so a reply can never be matched to the wrong request.
     fn header_by_obj_cmd(&mut self, obj_id: u32, cmd: UbusCmdType) -> UbusMsgHeader {
-        self.sequence += 1;
-        UbusMsgHeader {
-            version: UbusMsgVersion::CURRENT,
-            cmd_type: cmd,
-            sequence: self.sequence.into(),
-            peer: obj_id.into(),
+        loop {
+            self.sequence = self.sequence.wrapping_add(1);
+            let sequence = self.sequence;
+            if sequence != 0 && !self.outstanding.iter().any(|(other, _)| *other == sequence) {
+                break;
+            }
         }
+        UbusMsgHeader::new(cmd, self.sequence, obj_id)
     }
 
     // Get next message from ubus channel (blocking!)
-    pub fn next_message(&mut self) -> Result<UbusMsg, UbusError> {
+    // PINGs from ubusd are answered here and never returned.
+    // Subscription changes for our objects are recorded on the way through.
+    // INVOKEs held back by next_reply come first.
+    pub fn next_message(&mut self) -> Result<UbusMsg<'_>, UbusError> {
+        if self.invokes.is_empty() {
+            return self.read_message();
+        }
+        let bytes = self.invokes.remove(0);
+        self.buffer[..bytes.len()].copy_from_slice(&bytes);
+        UbusMsg::from_bytes(&self.buffer)
+    }
+
+    /// Read the next message from the transport, answering PINGs and recording
+    /// subscription changes as [`Connection::next_message`] does
+    fn read_message(&mut self) -> Result<UbusMsg<'_>, UbusError> {
+        loop {
+            let message = self.next_raw_message()?;
+            let header = message.header;
+            if let (Some(obj_id), Some(active)) =
+                (message.blob.get_u32(BlobAttrId::OBJID), message.active())
+            {
+                self.set_active(obj_id, active);
+            }
+            if header.cmd_type != UbusCmdType::PING {
+                break;
+            }
+            self.pong(&header)?;
+        }
+        // The message is still in the buffer; parse it again so the borrow starts here
+        UbusMsg::from_bytes(&self.buffer)
+    }
+
+    /// Get the next message from the ubus channel as-is, including PINGs
+    pub fn next_raw_message(&mut self) -> Result<UbusMsg<'_>, UbusError> {
         UbusMsg::from_io(&mut self.io, &mut self.buffer)
     }
 
+    /// Answer a PING with an empty DATA message carrying the same sequence number
+    fn pong(&mut self, ping: &UbusMsgHeader) -> Result<(), UbusError> {
+        let mut buffer = [0u8; UbusMsgHeader::SIZE + BlobTag::SIZE];
+        let header = UbusMsgHeader {
+            cmd_type: UbusCmdType::DATA,
+            ..*ping
+        };
+        self.send(UbusMsgBuilder::new(&mut buffer, &header)?)
+    }
+
     pub fn send(&mut self, message: UbusMsgBuilder) -> Result<(), UbusError> {
         self.io.put(message.into())
     }
 
+    /// Build a message with the next sequence number and send it. `build` adds the
+    /// attributes; the returned header is what replies should be matched against.
+    /// Replies are held for [`Connection::next_reply`] until the request's STATUS has been
+    /// read, or until [`Connection::cancel`].
+    pub fn request(
+        &mut self,
+        cmd: UbusCmdType,
+        obj_id: u32,
+        build: impl FnOnce(&mut UbusMsgBuilder) -> Result<(), UbusError>,
+    ) -> Result<UbusMsgHeader, UbusError> {
+        let mut buffer = [0u8; 1024];
+        let header = self.header_by_obj_cmd(obj_id, cmd);
+        let mut message = UbusMsgBuilder::new(&mut buffer, &header)?;
+        build(&mut message)?;
+        self.send(message)?;
+        self.outstanding
+            .push((header.sequence.into(), header.peer.into()));
+        Ok(header)
+    }
+
+    /// Get the next reply to `request`, as returned by [`Connection::request`]. A reply is
+    /// a DATA or STATUS with the request's sequence number and peer, which ubusd copies
+    /// from the request. Replies to other requests still in flight are kept for their own
+    /// `next_reply`, so several requests can be answered in any order, and INVOKEs for our
+    /// objects are kept for [`Connection::next_message`]; other messages are dropped.
+    /// `request` stops being tracked once its STATUS is returned.
+    pub fn next_reply(&mut self, request: &UbusMsgHeader) -> Result<UbusMsg<'_>, UbusError> {
+        let key = (u16::from(request.sequence), u32::from(request.peer));
+        let stashed = self
+            .stashed
+            .iter()
+            .position(|bytes| Self::reply_key(bytes) == key);
+        if let Some(index) = stashed {
+            let bytes = self.stashed.remove(index);
+            self.buffer[..bytes.len()].copy_from_slice(&bytes);
+        } else {
+            loop {
+                let message = self.read_message()?;
+                let (header, len) = (message.header, message.blob.tag.size());
+                let other = (u16::from(header.sequence), u32::from(header.peer));
+                let is_reply = matches!(header.cmd_type, UbusCmdType::DATA | UbusCmdType::STATUS);
+                if is_reply && other == key {
+                    break;
+                }
+                let bytes = self.buffer[..UbusMsgHeader::SIZE + len].to_vec();
+                if is_reply && self.outstanding.contains(&other) {
+                    self.stashed.push(bytes);
+                } else if header.cmd_type == UbusCmdType::INVOKE {
+                    self.invokes.push(bytes);
+                }
+            }
+        }
+
+        let message = UbusMsg::from_bytes(&self.buffer)?;
+        if message.header.cmd_type == UbusCmdType::STATUS {
+            self.outstanding.retain(|pending| *pending != key);
+        }
+        Ok(message)
+    }
+
+    /// Stop tracking `request` and drop any of its replies held so far
+    pub fn cancel(&mut self, request: &UbusMsgHeader) {
+        let key = (u16::from(request.sequence), u32::from(request.peer));
+        self.outstanding.retain(|pending| *pending != key);
+        self.stashed.retain(|bytes| Self::reply_key(bytes) != key);
+    }
+
+    /// Call `on_data` with each DATA reply to `request` until its STATUS arrives, which ends
+    /// the exchange and is turned into an error unless OK. ubusd answers a LOOKUP, say, with
+    /// one DATA per object before the STATUS. PINGs in between are answered as usual.
+    pub fn for_each_reply(
+        &mut self,
+        request: &UbusMsgHeader,
+        mut on_data: impl FnMut(UbusMsg<'_>) -> Result<(), UbusError>,
+    ) -> Result<(), UbusError> {
+        loop {
+            let message = self.next_reply(request)?;
+            match message.header.cmd_type {
+                UbusCmdType::STATUS => return Ok(message.status()?.into_result()?),
+                UbusCmdType::DATA => on_data(message)?,
+                _ => {}
+            }
+        }
+    }
+
+    /// Sequence number and peer of a message's header, which a reply shares with its request
+    fn reply_key(bytes: &[u8]) -> (u16, u32) {
+        let header = UbusMsgHeader::from_bytes(bytes[..UbusMsgHeader::SIZE].try_into().unwrap());
+        (header.sequence.into(), header.peer.into())
+    }
+
+    /// Send an INVOKE of `method` on `obj` without waiting; read its replies with
+    /// [`Connection::next_reply`]. ubusd finds attributes by id, so their order doesn't matter
+    /// to it, but they go out in libubus' order (OBJID, METHOD, DATA) so the frame matches
+    /// what `ubus call` sends byte for byte.
+    pub fn invoke_request(
+        &mut self,
+        obj: u32,
+        method: &str,
+        args: &[u8],
+    ) -> Result<UbusMsgHeader, UbusError> {
+        self.request(UbusCmdType::INVOKE, obj, |message| {
+            message.put(UbusMsgAttr::ObjId(obj))?;
+            message.put(UbusMsgAttr::Method(method))?;
+            message.put(UbusMsgAttr::Data(args))
+        })
+    }
+
     pub fn invoke(
         &mut self,
         obj: u32,
         method: &str,
         args: &[u8],
-        mut on_result: impl FnMut(BlobIter<Blob>),
+        on_result: impl FnMut(BlobIter<Blob>),
     ) -> Result<(), UbusError> {
-        let mut buffer = [0u8; 1024];
-        let header = self.header_by_obj_cmd(obj, UbusCmdType::INVOKE);
-        let mut message = UbusMsgBuilder::new(&mut buffer, &header).unwrap();
-        message.put(UbusMsgAttr::ObjId(obj))?;
-        message.put(UbusMsgAttr::Method(method))?;
+        let header = self.invoke_request(obj, method, args)?;
+        self.wait_invoke(&header, on_result)
+    }
 
-        message.put(UbusMsgAttr::Data(&args))?;
+    /// Invoke `method` on `obj` for a method that streams several DATA replies, calling
+    /// `on_data` with the DATA attribute of each in turn. Unlike [`Connection::invoke`], a
+    /// non-OK final status is returned rather than turned into an error.
+    pub fn invoke_stream(
+        &mut self,
+        obj: u32,
+        method: &str,
+        args: &[u8],
+        mut on_data: impl FnMut(Blob),
+    ) -> Result<UbusStatus, UbusError> {
+        let header = self.invoke_request(obj, method, args)?;
+        loop {
+            let message = self.next_reply(&header)?;
+            match message.header.cmd_type {
+                UbusCmdType::STATUS => return message.status(),
+                UbusCmdType::DATA => {
+                    let data = message.blob.get(BlobAttrId::DATA);
+                    on_data(data.ok_or(UbusError::InvalidData("Invalid data message"))?);
+                }
+                _ => {}
+            }
+        }
+    }
 
-        self.send(message)?;
-        'message: loop {
-            let message = self.next_message()?;
-            if message.header.sequence != header.sequence {
-                continue;
+    /// Invoke `method` on `obj` without waiting for an answer: the INVOKE carries NO_REPLY,
+    /// so the object sends neither DATA nor a STATUS, and this returns as soon as the
+    /// message is sent. No status is available, so don't wait for one with
+    /// [`Connection::next_reply`]; anything ubusd sends back for it (e.g. a NOT_FOUND
+    /// STATUS for an unknown object) is dropped.
+    pub fn invoke_no_reply(
+        &mut self,
+        obj: u32,
+        method: &str,
+        args: &[u8],
+    ) -> Result<(), UbusError> {
+        let header = self.request(UbusCmdType::INVOKE, obj, |message| {
+            message.put(UbusMsgAttr::ObjId(obj))?;
+            message.put(UbusMsgAttr::Method(method))?;
+            message.put(UbusMsgAttr::Data(args))?;
+            message.put(UbusMsgAttr::NoReply(true))
+        })?;
+        self.cancel(&header);
+        Ok(())
+    }
+
+    /// Call `on_result` with the attributes of each DATA reply to `request`, until its STATUS
+    pub fn wait_invoke(
+        &mut self,
+        request: &UbusMsgHeader,
+        mut on_result: impl FnMut(BlobIter<Blob>),
+    ) -> Result<(), UbusError> {
+        // Anything but DATA and the final STATUS is skipped
+        self.for_each_reply(request, |message| {
+            let data = message.blob.get(BlobAttrId::DATA);
+            let data = data.ok_or(UbusError::InvalidData("Invalid data message"))?;
+            on_result(BlobIter::new(data.data));
+            Ok(())
+        })
+    }
+
+    /// Invoke `method` on `obj` and collect the attributes of every DATA reply into one owned
+    /// buffer, which can be walked with `BlobIter` once the connection is used again
+    pub fn invoke_reply(
+        &mut self,
+        obj: u32,
+        method: &str,
+        args: &[u8],
+    ) -> Result<Vec<u8>, UbusError> {
+        let mut reply = Vec::new();
+        self.invoke(obj, method, args, |attrs| {
+            for blob in attrs {
+                reply.extend_from_slice(&blob.tag.to_bytes());
+                reply.extend_from_slice(blob.data);
+                reply.resize(reply.len() + blob.tag.padded_len() - blob.tag.size(), 0);
             }
+        })?;
+        Ok(reply)
+    }
 
-            let attrs = BlobIter::<UbusMsgAttr>::new(message.blob.data);
+    /// Invoke `method` on `obj` and return every DATA reply as an owned message, so they
+    /// can be kept while the connection is used again
+    pub fn invoke_owned(
+        &mut self,
+        obj: u32,
+        method: &str,
+        args: &[u8],
+    ) -> Result<Vec<UbusOwnedMsg>, UbusError> {
+        let header = self.invoke_request(obj, method, args)?;
+        self.wait_owned(&header)
+    }
+
+    /// Collect every DATA reply to `request` as an owned message, until its STATUS
+    pub fn wait_owned(&mut self, request: &UbusMsgHeader) -> Result<Vec<UbusOwnedMsg>, UbusError> {
+        let mut replies = Vec::new();
+        self.for_each_reply(request, |message| {
+            replies.push(UbusOwnedMsg::from(&message));
+            Ok(())
+        })?;
+        Ok(replies)
+    }
+
+    /// Register an object at `obj_path` exposing `methods`, each given as a name and its
+    /// argument policy. Returns the object with the id and type id ubusd assigned.
+    pub fn add_object<'a>(
+        &mut self,
+        obj_path: &'a str,
+        methods: &[(&str, &[(&str, BlobMsgType)])],
+    ) -> Result<ObjectResult<'a>, UbusError> {
+        let header = self.request(UbusCmdType::ADD_OBJECT, 0, |message| {
+            message.put_str(BlobAttrId::OBJPATH, obj_path)?;
+            message.put_signature(methods)
+        })?;
+
+        let mut object = None;
+        loop {
+            let message = self.next_reply(&header)?;
 
             match message.header.cmd_type {
-                UbusCmdType::STATUS => {
-                    for attr in attrs {
-                        if let UbusMsgAttr::Status(0) = attr {
-                            return Ok(());
-                        } else if let UbusMsgAttr::Status(status) = attr {
-                            return Err(UbusError::Status(status));
-                        }
-                    }
-                    return Err(UbusError::InvalidData("Invalid status message"));
-                }
                 UbusCmdType::DATA => {
-                    for attr in attrs {
-                        if let UbusMsgAttr::Data(data) = attr {
-                            on_result(BlobIter::new(data));
-                            continue 'message;
-                        }
-                    }
-                    return Err(UbusError::InvalidData("Invalid data message"));
+                    object = message
+                        .blob
+                        .get_u32(BlobAttrId::OBJID)
+                        .map(|id| ObjectResult {
+                            path: obj_path,
+                            id,
+                            ty: message.blob.get_u32(BlobAttrId::OBJTYPE).unwrap_or(0),
+                        });
                 }
-                unknown => {
-                    std::dbg!(unknown);
+                UbusCmdType::STATUS => {
+                    message.status()?.into_result()?;
+                    let object =
+                        object.ok_or(UbusError::InvalidData("Missing object id in reply"))?;
+                    self.objects.push(RegisteredObject {
+                        path: obj_path.to_string(),
+                        id: object.id,
+                        ty: object.ty,
+                        active: false,
+                        methods: methods
+                            .iter()
+                            .map(|(name, args)| {
+                                let args = args.iter().map(|(arg, ty)| (arg.to_string(), *ty));
+                                (name.to_string(), args.collect())
+                            })
+                            .collect(),
+                    });
+                    return Ok(object);
                 }
+                _ => continue,
+            }
+        }
+    }
+
+    /// Deregister an object previously added with [`Connection::add_object`]
+    pub fn remove_object(&mut self, obj_id: u32) -> Result<(), UbusError> {
+        let header = self.request(UbusCmdType::REMOVE_OBJECT, 0, |message| {
+            message.put_u32(BlobAttrId::OBJID, obj_id)
+        })?;
+        self.wait_status(&header)?;
+        self.objects.retain(|object| object.id != obj_id);
+        self.subscriptions.retain(|(obj, _)| *obj != obj_id);
+        Ok(())
+    }
+
+    /// Subscribe our object `obj_id` (registered with [`Connection::add_object`]) to the
+    /// notifications of `target`. ubusd acknowledges with a STATUS, and from then on delivers
+    /// the target's NOTIFY messages to `obj_id` as INVOKEs.
+    pub fn subscribe(&mut self, obj_id: u32, target: u32) -> Result<(), UbusError> {
+        self.subscription(UbusCmdType::SUBSCRIBE, obj_id, target)?;
+        self.subscriptions.push((obj_id, target));
+        Ok(())
+    }
+
+    /// Undo [`Connection::subscribe`], acknowledged by a STATUS like subscribing
+    pub fn unsubscribe(&mut self, obj_id: u32, target: u32) -> Result<(), UbusError> {
+        self.subscription(UbusCmdType::UNSUBSCRIBE, obj_id, target)?;
+        self.subscriptions
+            .retain(|subscription| *subscription != (obj_id, target));
+        Ok(())
+    }
+
+    fn subscription(
+        &mut self,
+        cmd: UbusCmdType,
+        obj_id: u32,
+        target: u32,
+    ) -> Result<(), UbusError> {
+        let header = self.request(cmd, 0, |message| {
+            message.put_u32(BlobAttrId::OBJID, obj_id)?;
+            message.put_target(target)
+        })?;
+        self.wait_status(&header)
+    }
+
+    /// Send the event `event` with blobmsg payload `data` to the subscribers of our object
+    /// `obj_id`. Returns how many subscribers ubusd forwarded it to. Check
+    /// [`Connection::has_subscribers`] first to skip events nobody listens to.
+    pub fn notify(&mut self, obj_id: u32, event: &str, data: &[u8]) -> Result<usize, UbusError> {
+        let header = self.request(UbusCmdType::NOTIFY, obj_id, |message| {
+            message.put_u32(BlobAttrId::OBJID, obj_id)?;
+            message.put_str(BlobAttrId::METHOD, event)?;
+            message.put_bytes(BlobAttrId::DATA, data)
+        })?;
+
+        loop {
+            let message = self.next_reply(&header)?;
+            if message.header.cmd_type != UbusCmdType::STATUS {
+                continue;
+            }
+            message.status()?.into_result()?;
+            return Ok(message.subscribers());
+        }
+    }
+
+    /// Run the event loop of a subscriber: read messages, answering PINGs, and call
+    /// `on_event` with the name and data of each notification. ubusd forwards a NOTIFY to
+    /// subscribed objects as an INVOKE, which is acknowledged with a STATUS here unless it
+    /// carries NO_REPLY. Everything else is dropped. Only returns on error, e.g. when the
+    /// connection to ubusd fails.
+    pub fn run_subscriptions(
+        &mut self,
+        mut on_event: impl FnMut(&str, Blob),
+    ) -> Result<(), UbusError> {
+        self.run_subscriptions_from(|_source, event, data| on_event(event, data))
+    }
+
+    /// Like [`Connection::run_subscriptions`], but also passes the object each event came
+    /// from, for subscribers watching several: the TARGET of the notification, or the OBJID
+    /// it was delivered to if it has none.
+    pub fn run_subscriptions_from(
+        &mut self,
+        mut on_event: impl FnMut(u32, &str, Blob),
+    ) -> Result<(), UbusError> {
+        loop {
+            let message = self.next_message()?;
+            if message.header.cmd_type != UbusCmdType::INVOKE {
+                continue;
+            }
+            let blob = message.blob;
+            let (Some(obj_id), Some(event)) = (
+                blob.get_u32(BlobAttrId::OBJID),
+                blob.get_str(BlobAttrId::METHOD),
+            ) else {
+                continue;
+            };
+            let data = match blob.get(BlobAttrId::DATA) {
+                Some(data) => data,
+                None => empty_data()?,
+            };
+            on_event(message.target().unwrap_or(obj_id), event, data);
+
+            if blob.get_bool(BlobAttrId::NO_REPLY) != Some(true) {
+                let header = UbusMsgHeader {
+                    cmd_type: UbusCmdType::STATUS,
+                    ..message.header
+                };
+                let mut buffer = [0u8; 64];
+                let mut reply = UbusMsgBuilder::new(&mut buffer, &header)?;
+                reply.put_i32(BlobAttrId::STATUS, UbusStatus::OK.value() as i32)?;
+                reply.put_u32(BlobAttrId::OBJID, obj_id)?;
+                self.send(reply)?;
             }
         }
     }
 
+    /// Skip replies to `header` until its STATUS arrives, and return that status
+    fn wait_status(&mut self, header: &UbusMsgHeader) -> Result<(), UbusError> {
+        loop {
+            let message = self.next_reply(header)?;
+            if message.header.cmd_type == UbusCmdType::STATUS {
+                return Ok(message.status()?.into_result()?);
+            }
+        }
+    }
+
+    #[cfg(feature = "json")]
     pub fn call<'a>(
         &'a mut self,
         obj_path: &'a str,
@@ -136,8 +694,9 @@ impl<T: IO> Connection<T> {
     ) -> Result<String, UbusError> {
         let obj_json = self.lookup_object_json(obj_path)?;
         let obj: UbusObject = serde_json::from_str(&obj_json)?;
-        let args = obj.args_from_json(method, args).unwrap();
+        let args = obj.args_from_json(method, args)?;
         let mut json = String::new();
+        let mut parsed = Ok(());
         self.invoke(obj.id, method, &args, |bi| {
             json += "{\n";
             let mut first = true;
@@ -146,15 +705,23 @@ impl<T: IO> Connection<T> {
                     json += ",\n";
                 }
                 //json_str += &format!("{:?}", x);
-                let msg: BlobMsg = x.try_into().unwrap();
+                let msg: BlobMsg = match x.try_into() {
+                    Ok(msg) => msg,
+                    Err(error) => {
+                        parsed = Err(error);
+                        return;
+                    }
+                };
                 json += &format!("\t{}", msg);
                 first = false;
             }
             json += "\n}";
         })?;
+        parsed?;
         Ok(json)
     }
 
+    #[cfg(feature = "json")]
     pub fn lookup_object_json<'a>(&'a mut self, obj_path: &'a str) -> Result<String, UbusError> {
         let mut obj_json = String::new();
         self.lookup(obj_path, |obj| {
@@ -169,216 +736,77 @@ impl<T: IO> Connection<T> {
         mut on_object: impl FnMut(ObjectResult),
         mut on_signature: impl FnMut(SignatureResult),
     ) -> Result<(), UbusError> {
-        let mut buffer = [0u8; 1024];
-        let header = self.header_by_obj_cmd(0, UbusCmdType::LOOKUP);
-        let mut request = UbusMsgBuilder::new(&mut buffer, &header).unwrap();
-        if obj_path.len() != 0 {
-            request.put(UbusMsgAttr::ObjPath(obj_path)).unwrap();
-        }
-        self.send(request)?;
-
-        loop {
-            let message = self.next_message()?;
-            if message.header.sequence != header.sequence {
-                continue;
-            }
-
-            let attrs = BlobIter::<UbusMsgAttr>::new(message.blob.data);
-
-            if message.header.cmd_type == UbusCmdType::STATUS {
-                for attr in attrs {
-                    if let UbusMsgAttr::Status(0) = attr {
-                        return Ok(());
-                    } else if let UbusMsgAttr::Status(status) = attr {
-                        return Err(UbusError::Status(status));
-                    }
-                }
-                return Err(UbusError::InvalidData("Invalid status message"));
-            }
-
-            if message.header.cmd_type != UbusCmdType::DATA {
-                continue;
-            }
+        let header = self.lookup_request(obj_path)?;
 
+        self.for_each_reply(&header, |message| {
             let mut obj_path: Option<&str> = None;
             let mut obj_id: Option<u32> = None;
             let mut obj_type: Option<u32> = None;
-            for attr in attrs {
+            for attr in BlobIter::<UbusMsgAttr>::new(message.blob.data) {
                 match attr {
                     UbusMsgAttr::ObjPath(path) => obj_path = Some(path),
                     UbusMsgAttr::ObjId(id) => obj_id = Some(id),
                     UbusMsgAttr::ObjType(ty) => obj_type = Some(ty),
                     UbusMsgAttr::Signature(nested) => {
-                        let object = ObjectResult {
-                            path: obj_path.unwrap(),
-                            id: obj_id.unwrap(),
-                            ty: obj_type.unwrap(),
+                        let (Some(path), Some(id), Some(ty)) = (obj_path, obj_id, obj_type) else {
+                            return Err(UbusError::InvalidData("Incomplete lookup reply"));
                         };
+                        let object = ObjectResult { path, id, ty };
                         on_object(object);
 
-                        for signature in nested {
-                            if let BlobMsgPayload::Table(table) = signature.1 {
-                                on_signature(SignatureResult {
-                                    object,
-                                    name: signature.0,
-                                    args: table
-                                        .iter()
-                                        .map(|(k, v)| {
-                                            if let BlobMsgPayload::Int32(typeid) = *v {
-                                                (*k, BlobMsgType::from(typeid as u32))
-                                            } else {
-                                                panic!()
-                                            }
-                                        })
-                                        .collect(),
-                                })
+                        for (name, policy) in nested {
+                            if let Ok(args) = method_policy(&policy) {
+                                on_signature(SignatureResult { object, name, args })
                             }
                         }
                     }
                     _ => continue,
                 }
             }
-        }
+            Ok(())
+        })
+    }
+
+    /// Send a LOOKUP for `obj_path`, or for every object if it is empty, laid out as
+    /// libubus' `ubus_lookup` does
+    fn lookup_request(&mut self, obj_path: &str) -> Result<UbusMsgHeader, UbusError> {
+        self.request(UbusCmdType::LOOKUP, 0, |request| {
+            if obj_path.is_empty() {
+                return Ok(());
+            }
+            request.put(UbusMsgAttr::ObjPath(obj_path))
+        })
     }
 
+    /// Ask ubusd for the id of the object at `obj_path`. There is no way to compute it
+    /// locally: ubusd picks object ids at random (`ubus_alloc_id`) when objects register,
+    /// so the same path gets a different id on every registration.
     pub fn lookup_id(&mut self, obj_path: &str) -> Result<u32, UbusError> {
         let mut obj_id = 0u32;
         self.lookup(obj_path, |obj| obj_id = obj.id)?;
         Ok(obj_id)
     }
 
+    /// Look up objects matching `obj_path` (every object if empty), calling `on_object` for
+    /// each DATA reply until the terminating STATUS
     pub fn lookup(
         &mut self,
         obj_path: &str,
         mut on_object: impl FnMut(UbusObject),
     ) -> Result<(), UbusError> {
-        let mut buffer = [0u8; 1024];
-        let header = self.header_by_obj_cmd(0, UbusCmdType::LOOKUP);
-        let mut request = UbusMsgBuilder::new(&mut buffer, &header).unwrap();
-        if obj_path.len() != 0 {
-            request.put(UbusMsgAttr::ObjPath(obj_path)).unwrap();
-        }
-        self.send(request)?;
-
-        loop {
-            let message = self.next_message()?;
-            if message.header.sequence != header.sequence {
-                continue;
-            }
+        let header = self.lookup_request(obj_path)?;
 
+        self.for_each_reply(&header, |message| {
             let attrs = BlobIter::<UbusMsgAttr>::new(message.blob.data);
-
-            if message.header.cmd_type == UbusCmdType::STATUS {
-                for attr in attrs {
-                    if let UbusMsgAttr::Status(0) = attr {
-                        return Ok(());
-                    } else if let UbusMsgAttr::Status(status) = attr {
-                        return Err(UbusError::Status(status));
-                    }
-                }
-                return Err(UbusError::InvalidData("Invalid status message"));
-            }
-
-            if message.header.cmd_type != UbusCmdType::DATA {
-                continue;
-            }
-            let mut obj = UbusObject::default();
-            for attr in attrs {
-                match attr {
-                    UbusMsgAttr::ObjPath(path) => obj.path = path,
-                    UbusMsgAttr::ObjId(id) => obj.id = id,
-                    UbusMsgAttr::ObjType(ty) => obj.ty = ty,
-                    UbusMsgAttr::Signature(nested) => {
-                        for item in nested {
-                            let signature = Method {
-                                name: item.0,
-                                policy: if let BlobMsgPayload::Table(table) = item.1 {
-                                    table
-                                        .iter()
-                                        .map(|(k, v)| {
-                                            if let BlobMsgPayload::Int32(typeid) = *v {
-                                                (*k, BlobMsgType::from(typeid as u32))
-                                            } else {
-                                                panic!()
-                                            }
-                                        })
-                                        .collect()
-                                } else {
-                                    panic!()
-                                },
-                            };
-                            obj.methods.insert(item.0, signature);
-                        }
-                    }
-                    _ => continue,
-                }
-            }
-            on_object(obj);
-        }
+            on_object(UbusObject::from_attrs(attrs)?);
+            Ok(())
+        })
     }
 
-    //  pub fn lookup_object<'a>(&'a mut self, obj_path: &'a str) -> Result<Vec<UbusObject>, UbusError> {
-    //     let mut buffer = [0u8; 1024];
-    //     let header = self.header_by_obj_cmd(0, UbusCmdType::LOOKUP);
-    //     let mut request = UbusMsgBuilder::new(&mut buffer, &header).unwrap();
-    //     if obj_path.len() != 0 {
-    //         request.put(UbusMsgAttr::ObjPath(obj_path)).unwrap();
-    //     }
-    //     self.send(request)?;
-    //     let mut objs = Vec::new();
-    //     loop {
-    //         let message = self.next_message()?;
-    //         if message.header.sequence != header.sequence {
-    //             continue;
-    //         }
-
-    //         let attrs = BlobIter::<UbusMsgAttr>::new(message.blob.data);
-
-    //         if message.header.cmd_type == UbusCmdType::STATUS {
-    //             for attr in attrs {
-    //                 if let UbusMsgAttr::Status(0) = attr {
-    //                     return Ok(Vec::new());
-    //                 } else if let UbusMsgAttr::Status(status) = attr {
-    //                     return Err(UbusError::Status(status));
-    //                 }
-    //             }
-    //             return Err(UbusError::InvalidData("Invalid status message"));
-    //         }
-
-    //         if message.header.cmd_type != UbusCmdType::DATA {
-    //             continue;
-    //         }
-    //         let mut obj = UbusObject::default();
-    //         for attr in attrs {
-    //             match attr {
-    //                 UbusMsgAttr::ObjPath(path) => obj.path = path,
-    //                 UbusMsgAttr::ObjId(id) => obj.id = id,
-    //                 UbusMsgAttr::ObjType(ty) => obj.ty = ty,
-    //                 UbusMsgAttr::Signature(nested) => {
-    //                     for item in nested {
-    //                         let signature = Method {
-    //                             name: item.0,
-    //                             policy: if let BlobMsgPayload::Table(table) = item.1 {
-    //                                 table
-    //                                     .iter()
-    //                                     .map(|(k, v)| {
-    //                                         if let BlobMsgPayload::Int32(typeid) = *v {
-    //                                             (*k, BlobMsgType::from(typeid as u32))
-    //                                         } else {
-    //                                             panic!()
-    //                                         }
-    //                                     })
-    //                                     .collect()
-    //                             } else {
-    //                                 panic!()
-    //                             },
-    //                         };
-    //                         obj.methods.insert(item.0, signature);
-    //                     }
-    //                 }
-    //                 _ => continue,
-    //             }
-    //         }
-    //         objs.push(obj);
-    // }
+    /// Look up every object on the bus, with its method signatures, as owned copies
+    pub fn lookup_all(&mut self) -> Result<Vec<ObjectInfo>, UbusError> {
+        let mut objects = Vec::new();
+        self.lookup("", |obj| objects.push(ObjectInfo::from(&obj)))?;
+        Ok(objects)
+    }
 }
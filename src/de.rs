@@ -0,0 +1,410 @@
+use crate::{Blob, BlobMsg, BlobMsgPayload, BlobMsgType, UbusError};
+use core::slice;
+use serde::de::value::BorrowedStrDeserializer;
+use serde::de::{self, Deserialize, Error as _, Unexpected, Visitor};
+use std::collections::hash_map;
+
+/// Read the blobmsg entries of `blob`'s payload (e.g. the DATA attribute of a reply) into
+/// `T`, treating them as one table. See [`from_blobmsg`] for the type mapping.
+pub fn from_blob<'de, T: Deserialize<'de>>(blob: &Blob<'de>) -> Result<T, UbusError> {
+    from_blobmsg(&BlobMsgPayload::Table(blob.msgs().collect()))
+}
+
+/// Read a parsed blobmsg value into `T`, the reverse of [`crate::to_blobmsg`].
+///
+/// Tables fill structs and maps, arrays fill sequences, and strings borrow from the
+/// message. Integers may be read into any Rust integer they fit in; a value read at its own
+/// width is taken bit-for-bit, so an INT32 of -1 reads as `u32::MAX`. Fields missing from a
+/// table are `None` for `Option`s, or use `#[serde(default)]`. Any other mismatch, such as
+/// a string where an integer was expected, is a [`UbusError::Deserialize`].
+pub fn from_blobmsg<'de, T: Deserialize<'de>>(
+    payload: &BlobMsgPayload<'de>,
+) -> Result<T, UbusError> {
+    T::deserialize(PayloadDeserializer(payload))
+}
+
+struct PayloadDeserializer<'p, 'de>(&'p BlobMsgPayload<'de>);
+
+impl<'p, 'de> PayloadDeserializer<'p, 'de> {
+    fn unexpected(&self) -> Unexpected<'p> {
+        match self.0 {
+            BlobMsgPayload::Array(_) => Unexpected::Seq,
+            BlobMsgPayload::Table(_) => Unexpected::Map,
+            BlobMsgPayload::String(s) => Unexpected::Str(s),
+            BlobMsgPayload::Double(v) => Unexpected::Float(*v),
+            BlobMsgPayload::Bool(v) => Unexpected::Bool(*v != 0),
+            BlobMsgPayload::Unknown(..) if self.is_unit() => Unexpected::Unit,
+            BlobMsgPayload::Unknown(_, data) => Unexpected::Bytes(data),
+            _ => Unexpected::Signed(self.integer().unwrap_or_default()),
+        }
+    }
+
+    fn invalid_type(&self, exp: &dyn de::Expected) -> UbusError {
+        UbusError::invalid_type(self.unexpected(), exp)
+    }
+
+    fn integer(&self) -> Option<i64> {
+        match *self.0 {
+            BlobMsgPayload::Int64(v) => Some(v),
+            BlobMsgPayload::Int32(v) => Some(v.into()),
+            BlobMsgPayload::Int16(v) => Some(v.into()),
+            BlobMsgPayload::Int8(v) | BlobMsgPayload::Bool(v) => Some(v.into()),
+            _ => None,
+        }
+    }
+
+    /// An UNSPEC entry without data, which is how `()` and JSON null are written
+    fn is_unit(&self) -> bool {
+        matches!(self.0, BlobMsgPayload::Unknown(ty, []) if *ty == BlobMsgType::UNSPEC.value())
+    }
+
+    fn visit_integer<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        match self.integer() {
+            Some(v) => visitor.visit_i64(v),
+            None => Err(self.invalid_type(&visitor)),
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for PayloadDeserializer<'_, 'de> {
+    type Error = UbusError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        match self.0 {
+            BlobMsgPayload::Array(elements) => visitor.visit_seq(Elements(elements.iter())),
+            BlobMsgPayload::Table(entries) => visitor.visit_map(Entries {
+                iter: entries.iter(),
+                value: None,
+            }),
+            BlobMsgPayload::String(s) => visitor.visit_borrowed_str(s),
+            BlobMsgPayload::Int64(v) => visitor.visit_i64(*v),
+            BlobMsgPayload::Int32(v) => visitor.visit_i32(*v),
+            BlobMsgPayload::Int16(v) => visitor.visit_i16(*v),
+            BlobMsgPayload::Int8(v) => visitor.visit_i8(*v),
+            BlobMsgPayload::Bool(v) => visitor.visit_bool(*v != 0),
+            BlobMsgPayload::Double(v) => visitor.visit_f64(*v),
+            BlobMsgPayload::Unknown(..) if self.is_unit() => visitor.visit_unit(),
+            BlobMsgPayload::Unknown(_, data) => visitor.visit_borrowed_bytes(data),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        match self.0 {
+            BlobMsgPayload::Int8(v) | BlobMsgPayload::Bool(v) => visitor.visit_bool(*v != 0),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        self.visit_integer(visitor)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        self.visit_integer(visitor)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        self.visit_integer(visitor)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        self.visit_integer(visitor)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        match *self.0 {
+            BlobMsgPayload::Int8(v) => visitor.visit_u8(v as u8),
+            _ => self.visit_integer(visitor),
+        }
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        match *self.0 {
+            BlobMsgPayload::Int16(v) => visitor.visit_u16(v as u16),
+            _ => self.visit_integer(visitor),
+        }
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        match *self.0 {
+            BlobMsgPayload::Int32(v) => visitor.visit_u32(v as u32),
+            _ => self.visit_integer(visitor),
+        }
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        match *self.0 {
+            BlobMsgPayload::Int64(v) => visitor.visit_u64(v as u64),
+            _ => self.visit_integer(visitor),
+        }
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        match *self.0 {
+            BlobMsgPayload::Double(v) => visitor.visit_f64(v),
+            _ => self.visit_integer(visitor),
+        }
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        match self.0 {
+            BlobMsgPayload::String(s) => visitor.visit_borrowed_str(s),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        match self.0 {
+            BlobMsgPayload::Unknown(_, data) => visitor.visit_borrowed_bytes(data),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        if self.is_unit() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        if self.is_unit() {
+            visitor.visit_unit()
+        } else {
+            Err(self.invalid_type(&visitor))
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, UbusError> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, UbusError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        match self.0 {
+            BlobMsgPayload::Array(elements) => visitor.visit_seq(Elements(elements.iter())),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, UbusError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, UbusError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        match self.0 {
+            BlobMsgPayload::Table(entries) => visitor.visit_map(Entries {
+                iter: entries.iter(),
+                value: None,
+            }),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, UbusError> {
+        self.deserialize_map(visitor)
+    }
+
+    /// Unit variants are strings, other variants a table with one entry named after them
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, UbusError> {
+        match self.0 {
+            BlobMsgPayload::String(variant) => visitor.visit_enum(Variant {
+                name: variant,
+                value: None,
+            }),
+            BlobMsgPayload::Table(entries) if entries.len() == 1 => {
+                let (name, value) = entries.iter().next().unwrap();
+                visitor.visit_enum(Variant {
+                    name,
+                    value: Some(value),
+                })
+            }
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, UbusError> {
+        visitor.visit_unit()
+    }
+}
+
+/// The elements of an array
+struct Elements<'p, 'de>(slice::Iter<'p, BlobMsg<'de>>);
+
+impl<'de> de::SeqAccess<'de> for Elements<'_, 'de> {
+    type Error = UbusError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, UbusError> {
+        self.0
+            .next()
+            .map(|element| seed.deserialize(PayloadDeserializer(&element.data)))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+/// The entries of a table, handing out each name and then its value
+struct Entries<'p, 'de> {
+    iter: hash_map::Iter<'p, &'de str, BlobMsgPayload<'de>>,
+    value: Option<&'p BlobMsgPayload<'de>>,
+}
+
+impl<'de> de::MapAccess<'de> for Entries<'_, 'de> {
+    type Error = UbusError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, UbusError> {
+        let Some((name, value)) = self.iter.next() else {
+            return Ok(None);
+        };
+        self.value = Some(value);
+        seed.deserialize(BorrowedStrDeserializer::<'de, UbusError>::new(name))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, UbusError> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| UbusError::custom("table value requested before its name"))?;
+        seed.deserialize(PayloadDeserializer(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+/// An enum variant, with the value it carries unless it is a unit variant
+struct Variant<'p, 'de> {
+    name: &'de str,
+    value: Option<&'p BlobMsgPayload<'de>>,
+}
+
+impl<'p, 'de> de::EnumAccess<'de> for Variant<'p, 'de> {
+    type Error = UbusError;
+    type Variant = Self;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self), UbusError> {
+        Ok((
+            seed.deserialize(BorrowedStrDeserializer::<'de, UbusError>::new(self.name))?,
+            self,
+        ))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Variant<'_, 'de> {
+    type Error = UbusError;
+
+    fn unit_variant(self) -> Result<(), UbusError> {
+        match self.value {
+            None => Ok(()),
+            Some(value) => Err(PayloadDeserializer(value).invalid_type(&"unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, UbusError> {
+        seed.deserialize(PayloadDeserializer(self.payload("newtype variant")?))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, UbusError> {
+        de::Deserializer::deserialize_seq(
+            PayloadDeserializer(self.payload("tuple variant")?),
+            visitor,
+        )
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, UbusError> {
+        de::Deserializer::deserialize_map(
+            PayloadDeserializer(self.payload("struct variant")?),
+            visitor,
+        )
+    }
+}
+
+impl<'p, 'de> Variant<'p, 'de> {
+    fn payload(&self, exp: &'static str) -> Result<&'p BlobMsgPayload<'de>, UbusError> {
+        self.value
+            .ok_or_else(|| UbusError::invalid_type(Unexpected::UnitVariant, &exp))
+    }
+}
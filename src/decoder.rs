@@ -0,0 +1,61 @@
+use crate::{BlobTag, UbusError, UbusMsg, UbusMsgHeader, UbusMsgVersion, UbusOwnedMsg};
+use alloc::vec::Vec;
+
+/// Reassembles messages from bytes that arrive in pieces of any size, e.g. from a
+/// non-blocking socket driven by an epoll/mio style loop
+#[derive(Clone, Debug, Default)]
+pub struct UbusMsgDecoder {
+    buffer: Vec<u8>,
+}
+
+impl UbusMsgDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append `data` and return the next complete message, if there is one. Bytes after it
+    /// stay buffered, so call [`UbusMsgDecoder::next_msg`] until it returns `None` before
+    /// waiting for more input.
+    pub fn push(&mut self, data: &[u8]) -> Result<Option<UbusOwnedMsg>, UbusError> {
+        self.buffer.extend_from_slice(data);
+        self.next_msg()
+    }
+
+    /// Take the next complete message out of the buffer. A message is complete once its
+    /// header, blob tag and payload are in; the outer blob is sent unpadded. A bad header
+    /// or tag is reported as soon as it has arrived, and the stream can't be resynchronised.
+    pub fn next_msg(&mut self) -> Result<Option<UbusOwnedMsg>, UbusError> {
+        let preamble = UbusMsgHeader::SIZE + BlobTag::SIZE;
+        if self.buffer.len() < preamble {
+            return Ok(None);
+        }
+        let (_, tag) = UbusMsg::parse_preamble(&self.buffer[..preamble])?;
+        let len = UbusMsgHeader::SIZE + tag.size();
+        if self.buffer.len() < len {
+            return Ok(None);
+        }
+        let rest = self.buffer.split_off(len);
+        let bytes = core::mem::replace(&mut self.buffer, rest);
+        UbusOwnedMsg::from_bytes(bytes).map(Some)
+    }
+
+    /// The header of the next message, as soon as its 8 bytes have arrived and before the
+    /// payload has, so an event loop can route by type early. Nothing is consumed: the
+    /// whole message still comes out of [`UbusMsgDecoder::next_msg`], and must be taken
+    /// before the one after it.
+    pub fn peek_header(&self) -> Result<Option<UbusMsgHeader>, UbusError> {
+        let Some(header) = self.buffer.get(..UbusMsgHeader::SIZE) else {
+            return Ok(None);
+        };
+        let header = UbusMsgHeader::from_bytes(header.try_into().unwrap());
+        if header.version != UbusMsgVersion::CURRENT {
+            return Err(UbusError::UnsupportedVersion(header.version));
+        }
+        Ok(Some(header))
+    }
+
+    /// Number of bytes received but not yet returned as a message
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+}
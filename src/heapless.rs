@@ -0,0 +1,20 @@
+use super::*;
+
+impl UbusMsgBuilder<'_> {
+    /// Build a message into a `heapless::Vec` of capacity `N`, so no buffer has to be kept
+    /// alive alongside the builder. `build` adds the attributes as with a builder over a
+    /// fixed buffer; running out of capacity fails the same way. The bytes are identical to
+    /// those the slice builder writes.
+    pub fn build_heapless<const N: usize>(
+        header: &UbusMsgHeader,
+        build: impl FnOnce(&mut UbusMsgBuilder) -> Result<(), UbusError>,
+    ) -> Result<::heapless::Vec<u8, N>, UbusError> {
+        let mut buffer = ::heapless::Vec::new();
+        buffer.resize(N, 0).unwrap();
+        let mut message = UbusMsgBuilder::new(&mut buffer, header)?;
+        build(&mut message)?;
+        let len = message.finish().len();
+        buffer.truncate(len);
+        Ok(buffer)
+    }
+}
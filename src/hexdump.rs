@@ -0,0 +1,97 @@
+use crate::UbusMsg;
+use core::fmt::{Debug, Formatter, Result};
+
+/// Prints raw bytes as a classic offset / hex / ascii dump, 16 bytes per line:
+///
+/// `00000000  00 02 00 01 00 00 24 68  03 00 00 0c 00 03 00 08  |......$h........|`
+///
+/// Useful on a buffer that failed to parse. Formatting needs no allocation.
+#[derive(Copy, Clone)]
+pub struct HexDump<'a>(pub &'a [u8]);
+
+impl Debug for HexDump<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write_dump(f, self.0.iter().copied())
+    }
+}
+
+/// Prints a message's header fields, then a [`HexDump`] of its wire bytes
+#[derive(Copy, Clone)]
+pub struct UbusMsgHexDump<'a>(pub &'a UbusMsg<'a>);
+
+impl Debug for UbusMsgHexDump<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let UbusMsg { header, blob } = self.0;
+        writeln!(
+            f,
+            "{:?} seq={} peer={:08x} len={}",
+            header.cmd_type,
+            header.sequence,
+            header.peer,
+            blob.tag.size()
+        )?;
+        let bytes = header
+            .to_bytes()
+            .into_iter()
+            .chain(blob.tag.to_bytes())
+            .chain(blob.data.iter().copied());
+        write_dump(f, bytes)
+    }
+}
+
+impl<'a> UbusMsg<'a> {
+    /// Dump the header and blob as hex, for use with `{:?}`
+    pub fn hex_dump(&'a self) -> UbusMsgHexDump<'a> {
+        UbusMsgHexDump(self)
+    }
+}
+
+const LINE: usize = 16;
+
+fn write_dump(f: &mut Formatter, mut bytes: impl Iterator<Item = u8>) -> Result {
+    let mut offset = 0;
+    loop {
+        let mut line = [0u8; LINE];
+        let mut len = 0;
+        while len < LINE {
+            match bytes.next() {
+                Some(byte) => line[len] = byte,
+                None => break,
+            }
+            len += 1;
+        }
+        if len == 0 && offset > 0 {
+            return Ok(());
+        }
+        if offset > 0 {
+            writeln!(f)?;
+        }
+
+        write!(f, "{:08x} ", offset)?;
+        for (i, byte) in line.iter().enumerate() {
+            if i % 8 == 0 {
+                write!(f, " ")?;
+            }
+            if i < len {
+                write!(f, "{:02x} ", byte)?;
+            } else {
+                write!(f, "   ")?;
+            }
+        }
+        write!(f, " |")?;
+        for &byte in &line[..len] {
+            let c = if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            };
+            write!(f, "{}", c)?;
+        }
+        write!(f, "|")?;
+
+        offset += len;
+        if len < LINE {
+            return Ok(());
+        }
+    }
+}
@@ -1,10 +1,13 @@
 #![no_std]
 #![allow(dead_code)]
 
-#[cfg(not(no_std))]
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
 extern crate std;
 
-/// Macro for defining helpful enum-like opaque structs
+/// Macro for defining helpful enum-like opaque structs. `Display` prints the symbolic name,
+/// or `Unknown(0x..)`; types with their own `Display` are declared with `, custom_display`.
 macro_rules! values {
     (
         $vis:vis $name:ident ( $repr:ty ) -> $other:ty {
@@ -25,21 +28,41 @@ macro_rules! values {
         $vis:vis $name:ident ( $repr:ty ) {
             $( $variant:ident = $value:literal ),* $(,)?
         }
+    ) => {
+        values!($vis $name($repr), custom_display { $( $variant = $value , )* });
+        impl core::fmt::Display for $name {
+            fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                match self.name() {
+                    Some(name) => f.write_str(name),
+                    None => write!(f, "Unknown({:#x})", self.0),
+                }
+            }
+        }
+    };
+    (
+        $vis:vis $name:ident ( $repr:ty ), custom_display {
+            $( $variant:ident = $value:literal ),* $(,)?
+        }
     ) => {
         #[repr(transparent)]
-        #[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+        #[derive(Copy, Clone, PartialEq, Eq)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
         $vis struct $name($repr);
         impl $name {
             $( pub const $variant: Self = Self($value); )*
             pub fn known(self) -> bool {
-                match self {
-                    $( Self::$variant => true, )*
-                    _ => false,
-                }
+                self.name().is_some()
             }
             pub fn value(self) -> $repr {
                 self.0
             }
+            /// Symbolic name of a known value
+            pub fn name(self) -> Option<&'static str> {
+                match self {
+                    $( Self::$variant => Some(stringify!($variant)), )*
+                    _ => None,
+                }
+            }
         }
         impl From<$repr> for $name {
             fn from(other: $repr) -> Self {
@@ -48,35 +71,30 @@ macro_rules! values {
         }
         impl core::fmt::Debug for $name {
             fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-                match *self {
-                    $( Self::$variant => write!(f, stringify!($variant)), )*
-                    unknown => write!(f, "UNKNOWN({})", unknown.0),
+                match self.name() {
+                    Some(name) => f.write_str(name),
+                    None => write!(f, "UNKNOWN({})", self.0),
                 }
             }
         }
     };
 }
 
-macro_rules! invalid_data_panic {
-    ($($arg:tt)*) => (if cfg!(debug_assertions) { panic!($($arg)*); })
-}
-
+/// Return `InvalidData` from the enclosing function unless the condition holds. This guards
+/// parsing of bytes from the socket, so it must not panic, not even in debug builds.
 macro_rules! valid_data {
     (($left:expr) >= ($right:expr), $msg:literal) => {{
         if !(($left) >= ($right)) {
-            invalid_data_panic!("Invalid Data: {} ({:?} < {:?})", $msg, $left, $right);
             return Err(Error::InvalidData($msg));
         }
     }};
     (($left:expr) == ($right:expr), $msg:literal) => {{
         if !(($left) == ($right)) {
-            invalid_data_panic!("Invalid Data: {} ({:?} != {:?})", $msg, $left, $right);
             return Err(Error::InvalidData($msg));
         }
     }};
     ($thing:expr, $msg:literal) => {{
         if !($thing) {
-            invalid_data_panic!("Invalid Data: {}", $msg);
             return Err(UbusError::InvalidData($msg));
         }
     }};
@@ -126,25 +144,125 @@ impl<T> From<core::convert::Infallible> for Error<T> {
 }
 
 pub trait IOError {}
+/// For transports that never fail, like `MockIo`
+impl IOError for core::convert::Infallible {}
 
 pub trait IO {
     type Error: IOError;
+    /// Write all of `data`, or return an error. Whole messages are handed over at once, and
+    /// a partial write must not be reported as success; transports that can accept fewer
+    /// bytes should loop with [`write_all`].
     fn put(&mut self, data: &[u8]) -> Result<(), UbusError>;
+    /// Fill all of `data`, or return an error. A short read must never be reported as
+    /// success, since the parser would then see stale buffer contents; transports that can
+    /// return fewer bytes should loop with [`read_exact`].
     fn get(&mut self, data: &mut [u8]) -> Result<(), UbusError>;
 }
 
+/// Non-blocking counterpart of [`IO`], with the same contract for `put` and `get`
+pub trait AsyncIO {
+    fn put(&mut self, data: &[u8]) -> impl core::future::Future<Output = Result<(), UbusError>>;
+    fn get(&mut self, data: &mut [u8])
+        -> impl core::future::Future<Output = Result<(), UbusError>>;
+}
+
+/// Fill `data` by calling `read` until it is full. `read` returns how many bytes it stored
+/// at the front of the slice it is given; returning 0 means end of stream, which is reported
+/// as `UbusError::IO` with `UnexpectedEof` (`UbusError::Disconnected` without `std`).
+/// Interrupted reads are retried.
+pub fn read_exact(
+    data: &mut [u8],
+    mut read: impl FnMut(&mut [u8]) -> Result<usize, UbusError>,
+) -> Result<(), UbusError> {
+    let mut filled = 0;
+    while filled < data.len() {
+        match read(&mut data[filled..]) {
+            #[cfg(feature = "std")]
+            Ok(0) => return Err(UbusError::IO(std::io::ErrorKind::UnexpectedEof.into())),
+            #[cfg(not(feature = "std"))]
+            Ok(0) => return Err(UbusError::Disconnected),
+            Ok(n) => filled = data.len().min(filled + n),
+            #[cfg(feature = "std")]
+            Err(UbusError::IO(e)) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Write all of `data` by calling `write` until it has taken everything. `write` returns
+/// how many bytes from the front of the slice it is given it accepted; returning 0 is
+/// reported as `UbusError::IO` with `WriteZero` (`UbusError::Disconnected` without `std`).
+/// Interrupted writes are retried.
+pub fn write_all(
+    mut data: &[u8],
+    mut write: impl FnMut(&[u8]) -> Result<usize, UbusError>,
+) -> Result<(), UbusError> {
+    while !data.is_empty() {
+        match write(data) {
+            #[cfg(feature = "std")]
+            Ok(0) => return Err(UbusError::IO(std::io::ErrorKind::WriteZero.into())),
+            #[cfg(not(feature = "std"))]
+            Ok(0) => return Err(UbusError::Disconnected),
+            Ok(n) => data = &data[data.len().min(n)..],
+            #[cfg(feature = "std")]
+            Err(UbusError::IO(e)) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
 mod blob;
 mod blobmsg;
+#[cfg(feature = "alloc")]
+mod buffered;
+#[cfg(feature = "std")]
 mod connection;
+#[cfg(feature = "serde")]
+mod de;
+#[cfg(feature = "alloc")]
+mod decoder;
+#[cfg(feature = "heapless")]
+mod heapless;
+mod hexdump;
+#[cfg(feature = "mock")]
+mod mock;
+#[cfg(feature = "alloc")]
+mod owned;
+#[cfg(feature = "serde")]
+mod ser;
+#[cfg(feature = "std")]
+mod server;
+#[cfg(feature = "tokio")]
+mod tokio;
 mod ubuserror;
 mod ubusmsg;
+#[cfg(feature = "std")]
 mod ubusobj;
-#[cfg(not(no_std))]
+#[cfg(feature = "std")]
 mod usock;
 
 pub use blob::*;
 pub use blobmsg::*;
+#[cfg(feature = "alloc")]
+pub use buffered::*;
+#[cfg(feature = "std")]
 pub use connection::*;
+#[cfg(feature = "serde")]
+pub use de::*;
+#[cfg(feature = "alloc")]
+pub use decoder::*;
+pub use hexdump::*;
+#[cfg(feature = "mock")]
+pub use mock::*;
+#[cfg(feature = "alloc")]
+pub use owned::*;
+#[cfg(feature = "serde")]
+pub use ser::*;
+#[cfg(feature = "std")]
+pub use server::*;
 pub use ubuserror::*;
 pub use ubusmsg::*;
+#[cfg(feature = "std")]
 pub use ubusobj::*;
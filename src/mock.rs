@@ -0,0 +1,81 @@
+use crate::{read_exact, UbusError, IO};
+use alloc::vec::Vec;
+use core::convert::Infallible;
+
+/// In-memory transport for driving [`UbusMsg::from_io`](crate::UbusMsg::from_io) or a
+/// [`Connection`](crate::Connection) without ubusd: reads are served from canned bytes and
+/// writes are recorded. Running out of bytes to read looks like ubusd closing the socket.
+#[derive(Clone, Debug, Default)]
+pub struct MockIo {
+    rx: Vec<u8>,
+    read: usize,
+    tx: Vec<u8>,
+    chunk: Option<usize>,
+}
+
+impl MockIo {
+    /// Serve `rx` to reads, in order
+    pub fn new(rx: impl Into<Vec<u8>>) -> Self {
+        Self {
+            rx: rx.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Hand out at most `chunk` bytes per underlying read, to exercise short reads
+    pub fn with_chunk(mut self, chunk: usize) -> Self {
+        self.chunk = Some(chunk.max(1));
+        self
+    }
+
+    /// Queue more bytes after those not yet read
+    pub fn push_rx(&mut self, data: &[u8]) {
+        self.rx.extend_from_slice(data);
+    }
+
+    /// Number of bytes still waiting to be read
+    pub fn remaining(&self) -> usize {
+        self.rx.len() - self.read
+    }
+
+    /// Everything written so far
+    pub fn written(&self) -> &[u8] {
+        &self.tx
+    }
+
+    /// Take everything written so far, starting afresh
+    pub fn take_written(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.tx)
+    }
+}
+
+impl IO for MockIo {
+    type Error = Infallible;
+    fn put(&mut self, data: &[u8]) -> Result<(), UbusError> {
+        self.tx.extend_from_slice(data);
+        Ok(())
+    }
+    fn get(&mut self, data: &mut [u8]) -> Result<(), UbusError> {
+        read_exact(data, |buf| {
+            let left = &self.rx[self.read..];
+            let n = buf
+                .len()
+                .min(left.len())
+                .min(self.chunk.unwrap_or(usize::MAX));
+            buf[..n].copy_from_slice(&left[..n]);
+            self.read += n;
+            Ok(n)
+        })
+    }
+}
+
+/// Lend a mock to a [`Connection`](crate::Connection), to look at what it wrote afterwards
+impl IO for &mut MockIo {
+    type Error = Infallible;
+    fn put(&mut self, data: &[u8]) -> Result<(), UbusError> {
+        (**self).put(data)
+    }
+    fn get(&mut self, data: &mut [u8]) -> Result<(), UbusError> {
+        (**self).get(data)
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{Blob, BlobTag, UbusError, UbusMsg, UbusMsgBuilder, UbusMsgHeader, MAX_MSG_SIZE};
+use alloc::{vec, vec::Vec};
+
+/// A message that owns its wire bytes, so it can outlive the receive buffer it came from
+#[derive(Clone)]
+pub struct UbusOwnedMsg {
+    header: UbusMsgHeader,
+    bytes: Vec<u8>,
+}
+
+impl UbusOwnedMsg {
+    /// Take ownership of the wire bytes of one complete message, checking they parse.
+    /// Anything after the end of the message is dropped.
+    pub fn from_bytes(mut bytes: Vec<u8>) -> Result<Self, UbusError> {
+        let message = UbusMsg::from_bytes(&bytes)?;
+        let (header, len) = (message.header, message.blob.data.len());
+        bytes.truncate(UbusMsgHeader::SIZE + BlobTag::SIZE + len);
+        Ok(Self { header, bytes })
+    }
+
+    pub fn header(&self) -> UbusMsgHeader {
+        self.header
+    }
+
+    /// Borrow as a [`UbusMsg`]
+    pub fn as_msg(&self) -> UbusMsg<'_> {
+        let tag = BlobTag::from_bytes(
+            self.bytes[UbusMsgHeader::SIZE..UbusMsgHeader::SIZE + BlobTag::SIZE]
+                .try_into()
+                .unwrap(),
+        );
+        let data = &self.bytes[UbusMsgHeader::SIZE + BlobTag::SIZE..];
+        UbusMsg {
+            header: self.header,
+            blob: Blob { tag, data },
+        }
+    }
+
+    /// The full wire bytes: header, blob tag and payload
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+impl From<&UbusMsg<'_>> for UbusOwnedMsg {
+    fn from(message: &UbusMsg<'_>) -> Self {
+        Self {
+            header: message.header,
+            bytes: message.to_vec(),
+        }
+    }
+}
+
+impl UbusMsg<'_> {
+    /// Copy out the wire bytes: header, blob tag and payload
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(UbusMsgHeader::SIZE + self.blob.tag.size());
+        bytes.extend_from_slice(&self.header.to_bytes());
+        bytes.extend_from_slice(&self.blob.tag.to_bytes());
+        bytes.extend_from_slice(self.blob.data);
+        bytes
+    }
+}
+
+impl UbusMsgBuilder<'_> {
+    /// Build a message into a freshly allocated buffer. `build` adds the attributes, as
+    /// with a builder over a fixed buffer, and may write up to [`MAX_MSG_SIZE`]; the result
+    /// holds exactly the bytes written, without spare capacity.
+    pub fn build_vec(
+        header: &UbusMsgHeader,
+        build: impl FnOnce(&mut UbusMsgBuilder) -> Result<(), UbusError>,
+    ) -> Result<Vec<u8>, UbusError> {
+        let mut buffer = vec![0u8; UbusMsgHeader::SIZE + MAX_MSG_SIZE];
+        let mut message = UbusMsgBuilder::new(&mut buffer, header)?;
+        build(&mut message)?;
+        let len = message.finish().len();
+        buffer.truncate(len);
+        buffer.shrink_to_fit();
+        Ok(buffer)
+    }
+}
+
+impl core::fmt::Debug for UbusOwnedMsg {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        self.as_msg().fmt(f)
+    }
+}
@@ -0,0 +1,559 @@
+use crate::{
+    BlobAttrId, BlobMsgType, BlobTag, TableBuilder, UbusCmdType, UbusError, UbusMsgBuilder,
+    UbusMsgHeader,
+};
+use alloc::string::{String, ToString};
+use serde::ser::{self, Error as _, Impossible, Serialize};
+
+impl UbusMsgBuilder<'_> {
+    /// Append `value` as a blobmsg table under `attr` (usually `DATA`). `value` must
+    /// serialize as a struct or map, and each of its fields becomes a named entry.
+    pub fn put_serialized<T: ?Sized + Serialize>(
+        &mut self,
+        attr: BlobAttrId,
+        value: &T,
+    ) -> Result<(), UbusError> {
+        let table = self.open_table(attr)?;
+        value.serialize(TableSerializer(table))
+    }
+}
+
+impl TableBuilder<'_, '_> {
+    /// Append `value` as an entry called `name`. See [`to_blobmsg`] for the type mapping.
+    pub fn put_serialized<T: ?Sized + Serialize>(
+        &mut self,
+        name: &str,
+        value: &T,
+    ) -> Result<(), UbusError> {
+        value.serialize(EntrySerializer { parent: self, name })
+    }
+}
+
+/// Write the fields of `value` into `buffer` as blobmsg entries, laid out exactly like
+/// libubox's `blobmsg_add_*` would, and return the bytes written. The result can be passed
+/// as the `args` of [`crate::Connection::invoke`].
+///
+/// Structs and maps become tables and sequences become arrays. Integers use the blobmsg
+/// type of their width (unsigned values are stored bit-for-bit, as `blobmsg_add_u32` does),
+/// floats are DOUBLE, `bool` is BOOL, and strings and unit enum variants are STRING.
+/// `None` fields are left out, `()` and byte strings are UNSPEC, and newtype enum variants
+/// are a table holding one entry named after the variant.
+pub fn to_blobmsg<'b, T: ?Sized + Serialize>(
+    buffer: &'b mut [u8],
+    value: &T,
+) -> Result<&'b [u8], UbusError> {
+    let header = UbusMsgHeader::new(UbusCmdType::DATA, 0, 0);
+    let mut message = UbusMsgBuilder::new(buffer, &header)?;
+    message.put_serialized(BlobAttrId::DATA, value)?;
+    // Skip the header, the outer blob tag and the DATA tag to leave just the entries
+    Ok(&message.finish()[UbusMsgHeader::SIZE + 2 * BlobTag::SIZE..])
+}
+
+fn unsupported(what: &str) -> UbusError {
+    UbusError::custom(format_args!("{what} can't be serialized as blobmsg"))
+}
+
+/// Serializes the top-level value straight into an already open table
+struct TableSerializer<'p, 'a>(TableBuilder<'p, 'a>);
+
+fn not_a_table() -> UbusError {
+    UbusError::custom("top-level value must be a struct or map")
+}
+
+impl<'p, 'a> ser::Serializer for TableSerializer<'p, 'a> {
+    type Ok = ();
+    type Error = UbusError;
+    type SerializeSeq = Impossible<(), UbusError>;
+    type SerializeTuple = Impossible<(), UbusError>;
+    type SerializeTupleStruct = Impossible<(), UbusError>;
+    type SerializeTupleVariant = Impossible<(), UbusError>;
+    type SerializeMap = Compound<'p, 'a>;
+    type SerializeStruct = Compound<'p, 'a>;
+    type SerializeStructVariant = Impossible<(), UbusError>;
+
+    fn serialize_bool(self, _v: bool) -> Result<(), UbusError> {
+        Err(not_a_table())
+    }
+    fn serialize_i8(self, _v: i8) -> Result<(), UbusError> {
+        Err(not_a_table())
+    }
+    fn serialize_i16(self, _v: i16) -> Result<(), UbusError> {
+        Err(not_a_table())
+    }
+    fn serialize_i32(self, _v: i32) -> Result<(), UbusError> {
+        Err(not_a_table())
+    }
+    fn serialize_i64(self, _v: i64) -> Result<(), UbusError> {
+        Err(not_a_table())
+    }
+    fn serialize_u8(self, _v: u8) -> Result<(), UbusError> {
+        Err(not_a_table())
+    }
+    fn serialize_u16(self, _v: u16) -> Result<(), UbusError> {
+        Err(not_a_table())
+    }
+    fn serialize_u32(self, _v: u32) -> Result<(), UbusError> {
+        Err(not_a_table())
+    }
+    fn serialize_u64(self, _v: u64) -> Result<(), UbusError> {
+        Err(not_a_table())
+    }
+    fn serialize_f32(self, _v: f32) -> Result<(), UbusError> {
+        Err(not_a_table())
+    }
+    fn serialize_f64(self, _v: f64) -> Result<(), UbusError> {
+        Err(not_a_table())
+    }
+    fn serialize_char(self, _v: char) -> Result<(), UbusError> {
+        Err(not_a_table())
+    }
+    fn serialize_str(self, _v: &str) -> Result<(), UbusError> {
+        Err(not_a_table())
+    }
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), UbusError> {
+        Err(not_a_table())
+    }
+    fn serialize_none(self) -> Result<(), UbusError> {
+        Err(not_a_table())
+    }
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), UbusError> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<(), UbusError> {
+        Err(not_a_table())
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), UbusError> {
+        Err(not_a_table())
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+    ) -> Result<(), UbusError> {
+        Err(not_a_table())
+    }
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), UbusError> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        mut self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), UbusError> {
+        self.0.put_serialized(variant, value)?;
+        self.0.close()
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, UbusError> {
+        Err(not_a_table())
+    }
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, UbusError> {
+        Err(not_a_table())
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, UbusError> {
+        Err(not_a_table())
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, UbusError> {
+        Err(unsupported("Tuple variant"))
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, UbusError> {
+        Ok(Compound::new(self.0))
+    }
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, UbusError> {
+        Ok(Compound::new(self.0))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, UbusError> {
+        Err(unsupported("Struct variant"))
+    }
+}
+
+/// Serializes one value as an entry called `name` inside `parent`
+struct EntrySerializer<'p, 'b, 'a> {
+    parent: &'p mut TableBuilder<'b, 'a>,
+    name: &'p str,
+}
+
+impl EntrySerializer<'_, '_, '_> {
+    fn put(self, ty: BlobMsgType, data: &[u8]) -> Result<(), UbusError> {
+        self.parent.put_msg(ty, self.name, data)
+    }
+}
+
+impl<'p, 'a> ser::Serializer for EntrySerializer<'p, '_, 'a> {
+    type Ok = ();
+    type Error = UbusError;
+    type SerializeSeq = Compound<'p, 'a>;
+    type SerializeTuple = Compound<'p, 'a>;
+    type SerializeTupleStruct = Compound<'p, 'a>;
+    type SerializeTupleVariant = Impossible<(), UbusError>;
+    type SerializeMap = Compound<'p, 'a>;
+    type SerializeStruct = Compound<'p, 'a>;
+    type SerializeStructVariant = Impossible<(), UbusError>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), UbusError> {
+        self.put(BlobMsgType::BOOL, &[v as u8])
+    }
+    fn serialize_i8(self, v: i8) -> Result<(), UbusError> {
+        self.serialize_u8(v as u8)
+    }
+    fn serialize_i16(self, v: i16) -> Result<(), UbusError> {
+        self.serialize_u16(v as u16)
+    }
+    fn serialize_i32(self, v: i32) -> Result<(), UbusError> {
+        self.serialize_u32(v as u32)
+    }
+    fn serialize_i64(self, v: i64) -> Result<(), UbusError> {
+        self.serialize_u64(v as u64)
+    }
+    fn serialize_u8(self, v: u8) -> Result<(), UbusError> {
+        self.put(BlobMsgType::INT8, &[v])
+    }
+    fn serialize_u16(self, v: u16) -> Result<(), UbusError> {
+        self.put(BlobMsgType::INT16, &v.to_be_bytes())
+    }
+    fn serialize_u32(self, v: u32) -> Result<(), UbusError> {
+        self.put(BlobMsgType::INT32, &v.to_be_bytes())
+    }
+    fn serialize_u64(self, v: u64) -> Result<(), UbusError> {
+        self.put(BlobMsgType::INT64, &v.to_be_bytes())
+    }
+    fn serialize_f32(self, v: f32) -> Result<(), UbusError> {
+        self.serialize_f64(v.into())
+    }
+    fn serialize_f64(self, v: f64) -> Result<(), UbusError> {
+        self.put(BlobMsgType::DOUBLE, &v.to_bits().to_be_bytes())
+    }
+    fn serialize_char(self, v: char) -> Result<(), UbusError> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+    fn serialize_str(self, v: &str) -> Result<(), UbusError> {
+        self.parent.put_str(self.name, v)
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), UbusError> {
+        self.put(BlobMsgType::UNSPEC, v)
+    }
+    fn serialize_none(self) -> Result<(), UbusError> {
+        Ok(())
+    }
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), UbusError> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<(), UbusError> {
+        self.put(BlobMsgType::UNSPEC, &[])
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), UbusError> {
+        self.serialize_unit()
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<(), UbusError> {
+        self.serialize_str(variant)
+    }
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), UbusError> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), UbusError> {
+        let mut table = self.parent.open_table(self.name)?;
+        table.put_serialized(variant, value)?;
+        table.close()
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, UbusError> {
+        Ok(Compound::new(self.parent.open_array(self.name)?.0))
+    }
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, UbusError> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, UbusError> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, UbusError> {
+        Err(unsupported("Tuple variant"))
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, UbusError> {
+        Ok(Compound::new(self.parent.open_table(self.name)?))
+    }
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, UbusError> {
+        self.serialize_map(Some(len))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, UbusError> {
+        Err(unsupported("Struct variant"))
+    }
+}
+
+/// An open table or array; entries are appended as they are serialized
+struct Compound<'p, 'a> {
+    table: TableBuilder<'p, 'a>,
+    key: Option<String>,
+}
+
+impl<'p, 'a> Compound<'p, 'a> {
+    fn new(table: TableBuilder<'p, 'a>) -> Self {
+        Self { table, key: None }
+    }
+}
+
+impl ser::SerializeSeq for Compound<'_, '_> {
+    type Ok = ();
+    type Error = UbusError;
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), UbusError> {
+        self.table.put_serialized("", value)
+    }
+    fn end(self) -> Result<(), UbusError> {
+        self.table.close()
+    }
+}
+
+impl ser::SerializeTuple for Compound<'_, '_> {
+    type Ok = ();
+    type Error = UbusError;
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), UbusError> {
+        self.table.put_serialized("", value)
+    }
+    fn end(self) -> Result<(), UbusError> {
+        self.table.close()
+    }
+}
+
+impl ser::SerializeTupleStruct for Compound<'_, '_> {
+    type Ok = ();
+    type Error = UbusError;
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), UbusError> {
+        self.table.put_serialized("", value)
+    }
+    fn end(self) -> Result<(), UbusError> {
+        self.table.close()
+    }
+}
+
+impl ser::SerializeMap for Compound<'_, '_> {
+    type Ok = ();
+    type Error = UbusError;
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), UbusError> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), UbusError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| UbusError::custom("map value without a key"))?;
+        self.table.put_serialized(&key, value)
+    }
+    fn end(self) -> Result<(), UbusError> {
+        self.table.close()
+    }
+}
+
+impl ser::SerializeStruct for Compound<'_, '_> {
+    type Ok = ();
+    type Error = UbusError;
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), UbusError> {
+        self.table.put_serialized(key, value)
+    }
+    fn end(self) -> Result<(), UbusError> {
+        self.table.close()
+    }
+}
+
+/// Turns map keys into blobmsg entry names. Strings are used as-is and integers are
+/// formatted in decimal, like JSON object keys.
+struct KeySerializer;
+
+fn not_a_key() -> UbusError {
+    UbusError::custom("map keys must be strings or integers")
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = UbusError;
+    type SerializeSeq = Impossible<String, UbusError>;
+    type SerializeTuple = Impossible<String, UbusError>;
+    type SerializeTupleStruct = Impossible<String, UbusError>;
+    type SerializeTupleVariant = Impossible<String, UbusError>;
+    type SerializeMap = Impossible<String, UbusError>;
+    type SerializeStruct = Impossible<String, UbusError>;
+    type SerializeStructVariant = Impossible<String, UbusError>;
+
+    fn serialize_bool(self, _v: bool) -> Result<String, UbusError> {
+        Err(not_a_key())
+    }
+    fn serialize_i8(self, v: i8) -> Result<String, UbusError> {
+        Ok(v.to_string())
+    }
+    fn serialize_i16(self, v: i16) -> Result<String, UbusError> {
+        Ok(v.to_string())
+    }
+    fn serialize_i32(self, v: i32) -> Result<String, UbusError> {
+        Ok(v.to_string())
+    }
+    fn serialize_i64(self, v: i64) -> Result<String, UbusError> {
+        Ok(v.to_string())
+    }
+    fn serialize_u8(self, v: u8) -> Result<String, UbusError> {
+        Ok(v.to_string())
+    }
+    fn serialize_u16(self, v: u16) -> Result<String, UbusError> {
+        Ok(v.to_string())
+    }
+    fn serialize_u32(self, v: u32) -> Result<String, UbusError> {
+        Ok(v.to_string())
+    }
+    fn serialize_u64(self, v: u64) -> Result<String, UbusError> {
+        Ok(v.to_string())
+    }
+    fn serialize_f32(self, _v: f32) -> Result<String, UbusError> {
+        Err(not_a_key())
+    }
+    fn serialize_f64(self, _v: f64) -> Result<String, UbusError> {
+        Err(not_a_key())
+    }
+    fn serialize_char(self, v: char) -> Result<String, UbusError> {
+        Ok(v.to_string())
+    }
+    fn serialize_str(self, v: &str) -> Result<String, UbusError> {
+        Ok(v.to_string())
+    }
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, UbusError> {
+        Err(not_a_key())
+    }
+    fn serialize_none(self) -> Result<String, UbusError> {
+        Err(not_a_key())
+    }
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<String, UbusError> {
+        Err(not_a_key())
+    }
+    fn serialize_unit(self) -> Result<String, UbusError> {
+        Err(not_a_key())
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, UbusError> {
+        Err(not_a_key())
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<String, UbusError> {
+        Ok(variant.to_string())
+    }
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, UbusError> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, UbusError> {
+        Err(not_a_key())
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, UbusError> {
+        Err(not_a_key())
+    }
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, UbusError> {
+        Err(not_a_key())
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, UbusError> {
+        Err(not_a_key())
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, UbusError> {
+        Err(not_a_key())
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, UbusError> {
+        Err(not_a_key())
+    }
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, UbusError> {
+        Err(not_a_key())
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, UbusError> {
+        Err(not_a_key())
+    }
+}
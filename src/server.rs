@@ -0,0 +1,160 @@
+use crate::*;
+use alloc::{vec, vec::Vec};
+
+/// A method an [`Object`] exposes: its name and the argument policy registered with ubusd
+#[derive(Copy, Clone, Debug)]
+pub struct MethodDef<'a> {
+    pub name: &'a str,
+    pub args: &'a [(&'a str, BlobMsgType)],
+}
+
+impl MethodDef<'_> {
+    /// Whether the blobmsg table `args` fits this method's policy, checked the way libubox's
+    /// `blobmsg_parse` would: every entry named in the policy has the declared type (UNSPEC
+    /// allows any), entries it doesn't name are ignored, and all of them may be missing
+    pub fn accepts(&self, args: &Blob) -> bool {
+        args.iter().all(|arg| {
+            let Ok(msg) = TryInto::<BlobMsg>::try_into(arg) else {
+                return false;
+            };
+            self.args
+                .iter()
+                .filter(|(name, _)| *name == msg.name)
+                .all(|(_, ty)| *ty == BlobMsgType::UNSPEC || arg.tag.msg_type() == Some(*ty))
+        })
+    }
+}
+
+/// A ubus object implemented in Rust, served by [`Connection::serve`]
+pub trait Object {
+    fn methods(&self) -> &[MethodDef<'_>];
+
+    /// Handle a call of `method`, one of [`Object::methods`], with the blobmsg table of
+    /// arguments in `args`. `reply` is the DATA table of the reply, already opened: entries
+    /// put there are sent back once this returns OK, and the returned status follows in
+    /// the STATUS.
+    fn invoke(&mut self, method: &str, args: &Blob, reply: &mut TableBuilder) -> UbusStatus;
+
+    /// Have [`Connection::serve`] answer calls whose arguments don't fit the method's
+    /// policy (see [`MethodDef::accepts`]) with INVALID_ARGUMENT, without calling
+    /// [`Object::invoke`]. Off by default, for methods taking arbitrary payloads.
+    fn validate_args(&self) -> bool {
+        false
+    }
+}
+
+impl<T: IO> Connection<T> {
+    /// Register `object` at `obj_path` with the methods it lists, to be served by
+    /// [`Connection::serve`] under the returned id
+    pub fn register_object<'a>(
+        &mut self,
+        obj_path: &'a str,
+        object: &dyn Object,
+    ) -> Result<ObjectResult<'a>, UbusError> {
+        let methods: Vec<(&str, &[(&str, BlobMsgType)])> = object
+            .methods()
+            .iter()
+            .map(|method| (method.name, method.args))
+            .collect();
+        self.add_object(obj_path, &methods)
+    }
+
+    /// Run the event loop of a server: read messages, answering PINGs, and dispatch each
+    /// INVOKE to the object in `objects` with its OBJID. The handler's reply table is closed
+    /// and sent as a DATA message followed by a STATUS, or just a STATUS if it was left
+    /// empty; calls carrying NO_REPLY get neither. Failures are answered with
+    /// [`reply_error`]: the handler's status, NOT_FOUND and METHOD_NOT_FOUND for unknown
+    /// objects and methods, and INVALID_ARGUMENT for INVOKEs missing OBJID or METHOD and for
+    /// arguments that don't fit the method's policy if the object asks for
+    /// [`Object::validate_args`]. Only returns on error, e.g.
+    /// when the connection to ubusd fails.
+    pub fn serve(&mut self, objects: &mut [(u32, &mut dyn Object)]) -> Result<(), UbusError> {
+        let mut buffer = vec![0u8; 64 * 1024];
+        loop {
+            let message = self.next_message()?;
+            if message.header.cmd_type != UbusCmdType::INVOKE {
+                continue;
+            }
+            let blob = message.blob;
+            let no_reply = blob.get_bool(BlobAttrId::NO_REPLY) == Some(true);
+            let request = message.header;
+            let (Some(obj_id), Some(method)) = (
+                blob.get_u32(BlobAttrId::OBJID),
+                blob.get_str(BlobAttrId::METHOD),
+            ) else {
+                if !no_reply {
+                    let obj_id = blob.get_u32(BlobAttrId::OBJID).unwrap_or(0);
+                    let (sequence, peer) = (request.sequence.into(), request.peer_id());
+                    let status = UbusStatus::INVALID_ARGUMENT;
+                    reply_error(self.io_mut(), sequence, peer, obj_id, status)?;
+                }
+                continue;
+            };
+            let args = match blob.get(BlobAttrId::DATA) {
+                Some(args) => args,
+                None => empty_data()?,
+            };
+
+            let header = UbusMsgHeader {
+                cmd_type: UbusCmdType::DATA,
+                ..request
+            };
+            let mut reply = UbusMsgBuilder::new(&mut buffer, &header)?;
+            reply.put_u32(BlobAttrId::OBJID, obj_id)?;
+            let mut data = reply.open_table(BlobAttrId::DATA)?;
+            let status = match objects.iter_mut().find(|(id, _)| *id == obj_id) {
+                None => UbusStatus::NOT_FOUND,
+                Some((_, object)) => {
+                    match object.methods().iter().find(|known| known.name == method) {
+                        None => UbusStatus::METHOD_NOT_FOUND,
+                        Some(known) if object.validate_args() && !known.accepts(&args) => {
+                            UbusStatus::INVALID_ARGUMENT
+                        }
+                        Some(_) => object.invoke(method, &args, &mut data),
+                    }
+                }
+            };
+
+            if no_reply {
+                continue;
+            }
+            if status != UbusStatus::OK {
+                let sequence = request.sequence.into();
+                reply_error(self.io_mut(), sequence, request.peer_id(), obj_id, status)?;
+                continue;
+            }
+            if !data.is_empty() {
+                data.close()?;
+                self.send(reply)?;
+            }
+            let header = UbusMsgHeader {
+                cmd_type: UbusCmdType::STATUS,
+                ..request
+            };
+            let mut buffer = [0u8; 64];
+            let mut reply = UbusMsgBuilder::new(&mut buffer, &header)?;
+            reply.put_i32(BlobAttrId::STATUS, UbusStatus::OK.value() as i32)?;
+            reply.put_u32(BlobAttrId::OBJID, obj_id)?;
+            self.send(reply)?;
+        }
+    }
+}
+
+/// Answer a request with a STATUS carrying `status` and no data, e.g. when its handler
+/// failed. `sequence` and `peer` are taken from the request's header, so it reaches the
+/// caller that is waiting on it. `obj_id` is the invoked object: ubusd drops replies from
+/// an object's owner that lack OBJID, so it follows STATUS as libubus sends it.
+pub fn reply_error<T: IO>(
+    io: &mut T,
+    sequence: u16,
+    peer: PeerId,
+    obj_id: u32,
+    status: UbusStatus,
+) -> Result<(), UbusError> {
+    let header = UbusMsgHeader::new(UbusCmdType::STATUS, sequence, peer.into());
+    let mut buffer = [0u8; UbusMsgHeader::SIZE + 3 * BlobTag::SIZE + 2 * 4];
+    let mut reply = UbusMsgBuilder::new(&mut buffer, &header)?;
+    reply.put_i32(BlobAttrId::STATUS, status.value() as i32)?;
+    reply.put_u32(BlobAttrId::OBJID, obj_id)?;
+    io.put(reply.finish())
+}
@@ -0,0 +1,15 @@
+use super::*;
+use ::tokio::io::{AsyncReadExt, AsyncWriteExt};
+use ::tokio::net::UnixStream;
+
+impl AsyncIO for UnixStream {
+    async fn put(&mut self, data: &[u8]) -> Result<(), UbusError> {
+        self.write_all(data).await.map_err(UbusError::IO)
+    }
+    async fn get(&mut self, data: &mut [u8]) -> Result<(), UbusError> {
+        self.read_exact(data)
+            .await
+            .map(|_| ())
+            .map_err(UbusError::IO)
+    }
+}
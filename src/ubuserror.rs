@@ -1,22 +1,116 @@
-extern crate alloc;
+use core::fmt;
 use core::str::Utf8Error;
-use std::io;
 
+use crate::{BlobTag, UbusMsgVersion, UbusStatus};
+#[cfg(feature = "alloc")]
 use alloc::string::String;
-use thiserror::Error;
+#[cfg(feature = "serde")]
+use alloc::string::ToString;
+#[cfg(feature = "std")]
+use std::io;
 
-#[derive(Debug, Error)]
+/// Variants holding a `String`, `std::io::Error` or `serde_json::Error` only exist with the
+/// `alloc`, `std` and `json` features respectively
+#[derive(Debug)]
 pub enum UbusError {
-    #[error("io error")]
-    IO(#[from] io::Error),
-    #[error("Invalid decoding string")]
-    Utf8(#[from] Utf8Error),
-    #[error("Invalid Data")]
+    #[cfg(feature = "std")]
+    IO(io::Error),
+    Utf8(Utf8Error),
     InvalidData(&'static str),
-    #[error("Ubus return ErrorCode({0})")]
-    Status(i32),
-    #[error("Error parse arguments string:{0}")]
-    ParseArguments(#[from] serde_json::Error),
-    #[error("Invalid method:{0}")]
+    InvalidBlobTag(BlobTag),
+    UnsupportedVersion(UbusMsgVersion),
+    MessageTooLarge(usize),
+    Disconnected,
+    Timeout,
+    Status(UbusStatus),
+    #[cfg(feature = "json")]
+    ParseArguments(serde_json::Error),
+    #[cfg(feature = "alloc")]
     InvalidMethod(String),
+    #[cfg(feature = "alloc")]
+    Serialize(String),
+    #[cfg(feature = "alloc")]
+    Deserialize(String),
+}
+
+impl fmt::Display for UbusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            UbusError::IO(_) => f.write_str("io error"),
+            UbusError::Utf8(_) => f.write_str("Invalid decoding string"),
+            UbusError::InvalidData(_) => f.write_str("Invalid Data"),
+            UbusError::InvalidBlobTag(tag) => write!(f, "Invalid blob tag {:?}", tag),
+            UbusError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported message version {:?}", version)
+            }
+            UbusError::MessageTooLarge(len) => {
+                write!(f, "Message of {} bytes exceeds MAX_MSG_SIZE", len)
+            }
+            UbusError::Disconnected => f.write_str("Disconnected from ubusd"),
+            UbusError::Timeout => f.write_str("Timed out waiting for ubusd"),
+            UbusError::Status(status) => write!(f, "Ubus returned status: {}", status),
+            #[cfg(feature = "json")]
+            UbusError::ParseArguments(e) => write!(f, "Error parse arguments string:{}", e),
+            #[cfg(feature = "alloc")]
+            UbusError::InvalidMethod(method) => write!(f, "Invalid method:{}", method),
+            #[cfg(feature = "alloc")]
+            UbusError::Serialize(msg) => write!(f, "Serialize error:{}", msg),
+            #[cfg(feature = "alloc")]
+            UbusError::Deserialize(msg) => write!(f, "Deserialize error:{}", msg),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UbusError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UbusError::IO(e) => Some(e),
+            UbusError::Utf8(e) => Some(e),
+            #[cfg(feature = "json")]
+            UbusError::ParseArguments(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for UbusError {
+    fn from(error: io::Error) -> Self {
+        UbusError::IO(error)
+    }
+}
+
+impl From<Utf8Error> for UbusError {
+    fn from(error: Utf8Error) -> Self {
+        UbusError::Utf8(error)
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for UbusError {
+    fn from(error: serde_json::Error) -> Self {
+        UbusError::ParseArguments(error)
+    }
+}
+
+impl From<UbusStatus> for UbusError {
+    fn from(status: UbusStatus) -> Self {
+        UbusError::Status(status)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::ser::Error for UbusError {
+    fn custom<T: core::fmt::Display>(msg: T) -> Self {
+        UbusError::Serialize(msg.to_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::de::Error for UbusError {
+    fn custom<T: core::fmt::Display>(msg: T) -> Self {
+        UbusError::Deserialize(msg.to_string())
+    }
 }
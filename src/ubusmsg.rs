@@ -1,7 +1,9 @@
-use crate::{Blob, BlobBuilder, BlobIter, BlobMsgPayload, BlobTag, Payload, UbusError, IO};
+use crate::{AsyncIO, Blob, BlobBuilder, BlobIter, BlobMsgType, BlobTag, Payload, UbusError, IO};
+#[cfg(feature = "std")]
+use crate::{BlobMsg, BlobMsgPayload};
 use core::convert::TryInto;
-use core::mem::{size_of, transmute};
-use serde::{Deserialize, Serialize};
+use core::mem::size_of;
+#[cfg(feature = "std")]
 use std::collections::HashMap;
 use storage_endian::{BEu16, BEu32};
 
@@ -20,10 +22,15 @@ values!(pub UbusCmdType(u8) {
     REMOVE_OBJECT   = 0x07,
     SUBSCRIBE       = 0x08,
     UNSUBSCRIBE     = 0x09,
-    NOTIFY          = 0x10,
-    MONITOR         = 0x11,
+    NOTIFY          = 0x0a,
+    MONITOR         = 0x0b,
 });
 
+/// Largest message blob ubusd accepts (`UBUS_MAX_MSGLEN`): the blob tag's length, which
+/// excludes the header. ubusd drops clients that send more, so larger ones are refused when
+/// building and rejected from the tag alone when reading.
+pub const MAX_MSG_SIZE: usize = 1024 * 1024;
+
 values!(pub BlobAttrId(u32) {
     UNSPEC      = 0x00,
     STATUS      = 0x01,
@@ -41,6 +48,68 @@ values!(pub BlobAttrId(u32) {
     GROUP       = 0x0d,
 });
 
+values!(pub UbusStatus(u32), custom_display {
+    OK                  = 0,
+    INVALID_COMMAND     = 1,
+    INVALID_ARGUMENT    = 2,
+    METHOD_NOT_FOUND    = 3,
+    NOT_FOUND           = 4,
+    NO_DATA             = 5,
+    PERMISSION_DENIED   = 6,
+    TIMEOUT             = 7,
+    NOT_SUPPORTED       = 8,
+    UNKNOWN_ERROR       = 9,
+    CONNECTION_FAILED   = 10,
+    NO_MEMORY           = 11,
+    PARSE_ERROR         = 12,
+    SYSTEM_ERROR        = 13,
+});
+
+impl UbusStatus {
+    /// `Ok` for `UbusStatus::OK`, otherwise the status as an error
+    pub fn into_result(self) -> Result<(), UbusStatus> {
+        match self {
+            UbusStatus::OK => Ok(()),
+            status => Err(status),
+        }
+    }
+}
+
+impl core::fmt::Display for UbusStatus {
+    /// Same wording as libubus' `ubus_strerror`
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let s = match *self {
+            UbusStatus::OK => "Success",
+            UbusStatus::INVALID_COMMAND => "Invalid command",
+            UbusStatus::INVALID_ARGUMENT => "Invalid argument",
+            UbusStatus::METHOD_NOT_FOUND => "Method not found",
+            UbusStatus::NOT_FOUND => "Not found",
+            UbusStatus::NO_DATA => "No response",
+            UbusStatus::PERMISSION_DENIED => "Permission denied",
+            UbusStatus::TIMEOUT => "Request timed out",
+            UbusStatus::NOT_SUPPORTED => "Operation not supported",
+            UbusStatus::UNKNOWN_ERROR => "Unknown error",
+            UbusStatus::CONNECTION_FAILED => "Connection failed",
+            UbusStatus::NO_MEMORY => "Out of memory",
+            UbusStatus::PARSE_ERROR => "Parsing message data failed",
+            UbusStatus::SYSTEM_ERROR => "System error",
+            unknown => return write!(f, "Unknown status {}", unknown.value()),
+        };
+        f.write_str(s)
+    }
+}
+
+// Attributes of a MONITOR message, which wraps a copy of another message
+values!(pub UbusMonitorAttrId(u32) {
+    UNSPEC      = 0x00,
+    CLIENT      = 0x01,
+    PEER        = 0x02,
+    SEND        = 0x03,
+    SEQ         = 0x04,
+    TYPE        = 0x05,
+    DATA        = 0x06,
+});
+
 #[derive(Copy, Clone, Debug)]
 #[repr(C)]
 pub struct UbusMsgHeader {
@@ -53,13 +122,89 @@ pub struct UbusMsgHeader {
 impl UbusMsgHeader {
     pub const SIZE: usize = size_of::<Self>();
 
+    /// Header of the current protocol version
+    pub fn new(cmd_type: UbusCmdType, sequence: u16, peer: u32) -> Self {
+        Self {
+            version: UbusMsgVersion::CURRENT,
+            cmd_type,
+            sequence: sequence.into(),
+            peer: peer.into(),
+        }
+    }
+
     /// Create MessageHeader from a byte array
     pub fn from_bytes(buffer: [u8; Self::SIZE]) -> Self {
-        unsafe { transmute(buffer) }
+        let [version, cmd_type, sequence @ .., p0, p1, p2, p3] = buffer;
+        Self {
+            version: version.into(),
+            cmd_type: cmd_type.into(),
+            sequence: u16::from_be_bytes(sequence).into(),
+            peer: u32::from_be_bytes([p0, p1, p2, p3]).into(),
+        }
     }
     // Dump out bytes of MessageHeader
     pub fn to_bytes(self) -> [u8; Self::SIZE] {
-        unsafe { core::mem::transmute(self) }
+        let [s0, s1] = u16::from(self.sequence).to_be_bytes();
+        let [p0, p1, p2, p3] = u32::from(self.peer).to_be_bytes();
+        let (version, cmd_type) = (self.version.value(), self.cmd_type.value());
+        [version, cmd_type, s0, s1, p0, p1, p2, p3]
+    }
+
+    /// Read just the header and the outer blob tag of the next message, checked as
+    /// [`UbusMsg::from_io`] would, to decide what to do with it before its payload. The
+    /// payload (`tag.inner_len()` bytes) has to be consumed next, with
+    /// [`UbusMsg::read_blob`] or [`UbusMsg::skip_blob`], to keep the stream in sync.
+    pub fn from_io<T: IO>(io: &mut T) -> Result<(Self, BlobTag), UbusError> {
+        let mut pre_buffer = [0u8; Self::SIZE + BlobTag::SIZE];
+        io.get(&mut pre_buffer).map_err(disconnected)?;
+        UbusMsg::parse_preamble(&pre_buffer)
+    }
+
+    /// The peer field as a [`PeerId`]. ubusd fills it with the sending client's id;
+    /// requests we send carry the target object's id there instead.
+    pub fn peer_id(&self) -> PeerId {
+        self.peer.into()
+    }
+}
+
+/// Id ubusd assigns each client connection, sent in its HELLO. Kept apart from object
+/// ids, which share the header's peer field in requests.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct PeerId(pub u32);
+
+impl From<u32> for PeerId {
+    fn from(id: u32) -> Self {
+        Self(id)
+    }
+}
+
+impl From<PeerId> for u32 {
+    fn from(id: PeerId) -> Self {
+        id.0
+    }
+}
+
+impl From<BEu32> for PeerId {
+    fn from(id: BEu32) -> Self {
+        Self(id.into())
+    }
+}
+
+impl From<PeerId> for BEu32 {
+    fn from(id: PeerId) -> Self {
+        id.0.into()
+    }
+}
+
+impl core::fmt::Display for PeerId {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "peer:{:#010x}", self.0)
+    }
+}
+
+impl core::fmt::Debug for PeerId {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        core::fmt::Display::fmt(self, f)
     }
 }
 
@@ -71,29 +216,218 @@ pub struct UbusMsg<'a> {
 
 impl<'a> UbusMsg<'a> {
     pub fn from_io<T: IO>(io: &mut T, buffer: &'a mut [u8]) -> Result<Self, UbusError> {
-        let (pre_buffer, buffer) = buffer.split_at_mut(UbusMsgHeader::SIZE + BlobTag::SIZE);
+        let (pre_buffer, buffer) = Self::split_buffer(buffer)?;
+
+        // Read in the message header and the following blob tag, keeping their bytes in
+        // front of the payload so `buffer` holds the whole message
+        let (header, tag) = UbusMsgHeader::from_io(io)?;
+        pre_buffer[..UbusMsgHeader::SIZE].copy_from_slice(&header.to_bytes());
+        pre_buffer[UbusMsgHeader::SIZE..].copy_from_slice(&tag.to_bytes());
+
+        let blob = Self::read_blob(io, tag, buffer)?;
+
+        Ok(UbusMsg { header, blob })
+    }
+
+    /// Second half of a two-phase read started with [`UbusMsgHeader::from_io`]: receive the
+    /// payload announced by `tag` into `buffer`. A payload too big for `buffer` is read and
+    /// dropped before the error is returned, so the next read starts at the next message.
+    pub fn read_blob<T: IO>(
+        io: &mut T,
+        tag: BlobTag,
+        buffer: &'a mut [u8],
+    ) -> Result<Blob<'a>, UbusError> {
+        let data = match Self::payload(buffer, tag) {
+            Ok(data) => data,
+            Err(error) => {
+                // If the stream fails meanwhile, the next read reports that
+                let _ = Self::skip_blob(io, tag);
+                return Err(error);
+            }
+        };
+        io.get(data).map_err(disconnected)?;
+        Blob::from_tag_and_data(tag, data)
+    }
+
+    /// Read and drop the payload announced by `tag`, for a message whose header shows it
+    /// isn't wanted. Needs no buffer the size of the message.
+    pub fn skip_blob<T: IO>(io: &mut T, tag: BlobTag) -> Result<(), UbusError> {
+        let mut scratch = [0u8; 256];
+        let mut left = tag.inner_len();
+        while left > 0 {
+            let len = left.min(scratch.len());
+            io.get(&mut scratch[..len]).map_err(disconnected)?;
+            left -= len;
+        }
+        Ok(())
+    }
+
+    /// User name ubusd tagged the message with, for access control
+    pub fn user(&self) -> Option<&'a str> {
+        self.blob.get_str(BlobAttrId::USER)
+    }
+
+    /// Group name ubusd tagged the message with, for access control
+    pub fn group(&self) -> Option<&'a str> {
+        self.blob.get_str(BlobAttrId::GROUP)
+    }
+
+    /// Object id of the TARGET attribute, naming the object a subscription or
+    /// notification concerns
+    pub fn target(&self) -> Option<u32> {
+        self.blob.get_u32(BlobAttrId::TARGET)
+    }
+
+    /// How many subscribers the SUBSCRIBERS attribute of a NOTIFY's STATUS reply lists.
+    /// ubusd nests one entry per subscriber it forwarded the event to; 0 if absent.
+    pub fn subscribers(&self) -> usize {
+        self.blob
+            .get(BlobAttrId::SUBSCRIBERS)
+            .map_or(0, |subscribers| subscribers.iter().count())
+    }
+
+    /// The ACTIVE flag of a NOTIFY ubusd sends an object's owner when the object gains
+    /// its first subscriber (true) or loses its last (false)
+    pub fn active(&self) -> Option<bool> {
+        if self.header.cmd_type != UbusCmdType::NOTIFY {
+            return None;
+        }
+        self.blob.get_bool(BlobAttrId::ACTIVE)
+    }
+
+    /// Read the status code of a STATUS message
+    pub fn status(&self) -> Result<UbusStatus, UbusError> {
+        if self.header.cmd_type != UbusCmdType::STATUS {
+            return Err(UbusError::InvalidData("Not a status message"));
+        }
+        let status = self.blob.get_u32(BlobAttrId::STATUS);
+        status
+            .map(UbusStatus::from)
+            .ok_or(UbusError::InvalidData("Invalid status message"))
+    }
+
+    /// Iterate the top-level blobmsg entries inside the `DATA` attribute, e.g. each row of
+    /// a reply that lists several tables. `self.blob.iter()` walks the outer attributes
+    /// (OBJID, METHOD, DATA, ...); this walks the contents of DATA. Empty without DATA,
+    /// and stops at the first malformed entry.
+    #[cfg(feature = "std")]
+    pub fn data_entries(&self) -> impl Iterator<Item = BlobMsg<'a>> {
+        let data = self.blob.get(BlobAttrId::DATA);
+        data.into_iter()
+            .flat_map(|data| data.iter())
+            .map_while(|blob| blob.try_into().ok())
+    }
+
+    /// Parse a complete message from `buffer`, as read by [`UbusMsg::from_io`]
+    pub fn from_bytes(buffer: &'a [u8]) -> Result<Self, UbusError> {
+        if buffer.len() < UbusMsgHeader::SIZE + BlobTag::SIZE {
+            return Err(UbusError::InvalidData("Message is too short"));
+        }
+        let (pre_buffer, buffer) = buffer.split_at(UbusMsgHeader::SIZE + BlobTag::SIZE);
+        let (header, tag) = Self::parse_preamble(pre_buffer)?;
+        let blob = Blob::from_tag_and_data(tag, buffer)?;
+
+        Ok(UbusMsg { header, blob })
+    }
+
+    /// Write the wire bytes (header, blob tag and payload) to any `std::io::Write`, e.g. to
+    /// record a message stream to disk. [`UbusMsg::from_bytes`] or a `UbusMsgDecoder` reads
+    /// them back; a built message's `finish()` bytes can be written directly.
+    #[cfg(feature = "std")]
+    pub fn write_to(&self, mut writer: impl std::io::Write) -> Result<(), UbusError> {
+        writer.write_all(&self.header.to_bytes())?;
+        writer.write_all(&self.blob.tag.to_bytes())?;
+        writer.write_all(self.blob.data)?;
+        Ok(())
+    }
+
+    /// Async version of [`UbusMsg::from_io`], awaiting the header and tag, then the payload
+    pub async fn from_async_io<T: AsyncIO>(
+        io: &mut T,
+        buffer: &'a mut [u8],
+    ) -> Result<Self, UbusError> {
+        let (pre_buffer, buffer) = Self::split_buffer(buffer)?;
 
-        // Read in the message header and the following blob tag
-        io.get(pre_buffer)?;
+        io.get(pre_buffer).await.map_err(disconnected)?;
+        let (header, tag) = Self::parse_preamble(pre_buffer)?;
+
+        let data = match Self::payload(buffer, tag) {
+            Ok(data) => data,
+            Err(error) => {
+                // Drop the payload as `read_blob` does, to stay in sync
+                let mut scratch = [0u8; 256];
+                let mut left = tag.inner_len();
+                while left > 0 {
+                    let len = left.min(scratch.len());
+                    if io.get(&mut scratch[..len]).await.is_err() {
+                        break;
+                    }
+                    left -= len;
+                }
+                return Err(error);
+            }
+        };
+        io.get(data).await.map_err(disconnected)?;
+
+        let blob = Blob::from_tag_and_data(tag, data)?;
+
+        Ok(UbusMsg { header, blob })
+    }
 
+    /// Split the receive buffer into room for the header and tag, and room for the payload
+    fn split_buffer(buffer: &mut [u8]) -> Result<(&mut [u8], &mut [u8]), UbusError> {
+        if buffer.len() < UbusMsgHeader::SIZE + BlobTag::SIZE {
+            return Err(UbusError::InvalidData("Receive buffer is too small"));
+        }
+        Ok(buffer.split_at_mut(UbusMsgHeader::SIZE + BlobTag::SIZE))
+    }
+
+    pub(crate) fn parse_preamble(pre_buffer: &[u8]) -> Result<(UbusMsgHeader, BlobTag), UbusError> {
         let (header, tag) = pre_buffer.split_at(UbusMsgHeader::SIZE);
 
         let header = UbusMsgHeader::from_bytes(header.try_into().unwrap());
-        valid_data!(header.version == UbusMsgVersion::CURRENT, "Wrong version");
+        if header.version != UbusMsgVersion::CURRENT {
+            return Err(UbusError::UnsupportedVersion(header.version));
+        }
 
         let tag = BlobTag::from_bytes(tag.try_into().unwrap());
         tag.is_valid()?;
+        if tag.size() > MAX_MSG_SIZE {
+            return Err(UbusError::MessageTooLarge(tag.size()));
+        }
 
-        // Get a slice the size of the blob's data bytes (do we need to worry about padding here?)
-        let data = &mut buffer[..tag.inner_len()];
-
-        // Receive data into slice
-        io.get(data)?;
+        Ok((header, tag))
+    }
 
-        // Create the blob from our parts
-        let blob = Blob::from_tag_and_data(tag, data).unwrap();
+    /// Get a slice the size of the blob's data bytes. The outer blob is sent unpadded
+    /// (libubus reads exactly blob_len() here too); its length is normally aligned anyway
+    /// because every attribute inside it is padded, but reading past it would desync.
+    fn payload(buffer: &mut [u8], tag: BlobTag) -> Result<&mut [u8], UbusError> {
+        buffer
+            .get_mut(..tag.inner_len())
+            .ok_or(UbusError::InvalidData(
+                "Message does not fit in receive buffer",
+            ))
+    }
+}
 
-        Ok(UbusMsg { header, blob })
+/// The stream ending or being reset part way through a read means ubusd has gone away
+fn disconnected(error: UbusError) -> UbusError {
+    #[cfg(feature = "std")]
+    use std::io::ErrorKind;
+    match error {
+        #[cfg(feature = "std")]
+        UbusError::IO(error)
+            if matches!(
+                error.kind(),
+                ErrorKind::UnexpectedEof
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+            ) =>
+        {
+            UbusError::Disconnected
+        }
+        error => error,
     }
 }
 
@@ -110,6 +444,62 @@ impl core::fmt::Debug for UbusMsg<'_> {
     }
 }
 
+/// Walks the top-level attributes, the same as `message.blob.iter()`
+impl<'a> IntoIterator for &UbusMsg<'a> {
+    type Item = Blob<'a>;
+    type IntoIter = BlobIter<'a, Blob<'a>>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.blob.iter()
+    }
+}
+
+/// A message seen by ubusd, decoded from the MONITOR frame it sends to monitoring clients
+#[derive(Copy, Clone, Debug)]
+pub struct UbusMonitorMsg<'a> {
+    /// Client the message was sent to or received from
+    pub client: u32,
+    /// Whether ubusd was sending the message (true) or receiving it (false)
+    pub send: bool,
+    /// The original message, with its header rebuilt from the monitor attributes
+    pub message: UbusMsg<'a>,
+}
+
+impl<'a> UbusMonitorMsg<'a> {
+    pub fn from_msg(monitor: &UbusMsg<'a>) -> Result<Self, UbusError> {
+        if monitor.header.cmd_type != UbusCmdType::MONITOR {
+            return Err(UbusError::InvalidData("Not a monitor message"));
+        }
+
+        let attr = |id: UbusMonitorAttrId| BlobAttrId::from(id.value());
+        let missing = || UbusError::InvalidData("Incomplete monitor message");
+        let blob = &monitor.blob;
+        let u32_attr = |id| blob.get_u32(attr(id)).ok_or_else(missing);
+
+        let header = UbusMsgHeader {
+            version: monitor.header.version,
+            cmd_type: (u32_attr(UbusMonitorAttrId::TYPE)? as u8).into(),
+            sequence: (u32_attr(UbusMonitorAttrId::SEQ)? as u16).into(),
+            peer: u32_attr(UbusMonitorAttrId::PEER)?.into(),
+        };
+        let data = blob
+            .get(attr(UbusMonitorAttrId::DATA))
+            .ok_or_else(missing)?
+            .data;
+        let tag = BlobTag::new(0, BlobTag::SIZE + data.len(), false)?;
+
+        Ok(Self {
+            client: u32_attr(UbusMonitorAttrId::CLIENT)?,
+            send: blob
+                .get_bool(attr(UbusMonitorAttrId::SEND))
+                .ok_or_else(missing)?,
+            message: UbusMsg {
+                header,
+                blob: Blob::from_tag_and_data(tag, data)?,
+            },
+        })
+    }
+}
+
 pub struct UbusMsgBuilder<'a> {
     buffer: &'a mut [u8],
     offset: usize,
@@ -117,10 +507,9 @@ pub struct UbusMsgBuilder<'a> {
 
 impl<'a> UbusMsgBuilder<'a> {
     pub fn new(buffer: &'a mut [u8], header: &UbusMsgHeader) -> Result<Self, UbusError> {
-        valid_data!(
-            buffer.len() >= (UbusMsgHeader::SIZE + BlobTag::SIZE),
-            "Builder buffer is too small"
-        );
+        if buffer.len() < UbusMsgHeader::SIZE + BlobTag::SIZE {
+            return Err(UbusError::InvalidData("Builder buffer is too small"));
+        }
 
         let header_buf = &mut buffer[..UbusMsgHeader::SIZE];
         let header_buf: &mut [u8; UbusMsgHeader::SIZE] = header_buf.try_into().unwrap();
@@ -131,28 +520,247 @@ impl<'a> UbusMsgBuilder<'a> {
         Ok(Self { buffer, offset })
     }
 
-    pub fn put(&mut self, attr: UbusMsgAttr) -> Result<(), UbusError> {
-        let mut blob = BlobBuilder::from_bytes(&mut self.buffer[self.offset..]);
+    /// Bytes written so far, header and blob tag included
+    pub fn len(&self) -> usize {
+        self.offset
+    }
 
-        match attr {
-            UbusMsgAttr::Status(val) => blob.push_u32(BlobAttrId::STATUS.value(), val as u32)?,
-            UbusMsgAttr::ObjPath(val) => blob.push_str(BlobAttrId::OBJPATH.value(), val)?,
-            UbusMsgAttr::ObjId(val) => blob.push_u32(BlobAttrId::OBJID.value(), val)?,
-            UbusMsgAttr::Method(val) => blob.push_str(BlobAttrId::METHOD.value(), val)?,
-            UbusMsgAttr::ObjType(val) => blob.push_u32(BlobAttrId::STATUS.value(), val)?,
-            UbusMsgAttr::Signature(_) => unimplemented!(),
-            UbusMsgAttr::Data(val) => blob.push_bytes(BlobAttrId::DATA.value(), val)?,
-            UbusMsgAttr::Target(val) => blob.push_u32(BlobAttrId::TARGET.value(), val)?,
-            UbusMsgAttr::Active(val) => blob.push_bool(BlobAttrId::USER.value(), val)?,
-            UbusMsgAttr::NoReply(val) => blob.push_bool(BlobAttrId::USER.value(), val)?,
-            UbusMsgAttr::Subscribers(_) => unimplemented!(),
-            UbusMsgAttr::User(val) => blob.push_str(BlobAttrId::USER.value(), val)?,
-            UbusMsgAttr::Group(val) => blob.push_str(BlobAttrId::GROUP.value(), val)?,
-            UbusMsgAttr::Unknown(id, val) => blob.push_bytes(id.value(), val)?,
+    /// Whether no attributes have been added yet
+    pub fn is_empty(&self) -> bool {
+        self.offset == UbusMsgHeader::SIZE + BlobTag::SIZE
+    }
+
+    /// Reuse the receive buffer holding a message, as read by [`UbusMsg::from_io`], to build
+    /// the reply: the header is rewritten in place with `cmd_type`, keeping the sequence
+    /// number and peer, and new attributes are appended after the existing ones.
+    ///
+    /// A parsed [`UbusMsg`] borrows these same bytes, so it has to be dropped before calling
+    /// this; copy out anything still needed first (the header is `Copy`). The original
+    /// attributes stay in the reply, and appended ones overwrite whatever follows the
+    /// message in `buffer`. To drop the original attributes use [`UbusMsgBuilder::new`]
+    /// on the same buffer instead.
+    pub fn reply_in_place(buffer: &'a mut [u8], cmd_type: UbusCmdType) -> Result<Self, UbusError> {
+        let message = UbusMsg::from_bytes(buffer)?;
+        let header = UbusMsgHeader {
+            cmd_type,
+            ..message.header
         };
+        let offset = UbusMsgHeader::SIZE + message.blob.tag.size();
+
+        let header_buf = &mut buffer[..UbusMsgHeader::SIZE];
+        let header_buf: &mut [u8; UbusMsgHeader::SIZE] = header_buf.try_into().unwrap();
+        *header_buf = header.to_bytes();
+
+        Ok(Self { buffer, offset })
+    }
 
-        self.offset += blob.len();
+    pub fn put(&mut self, attr: UbusMsgAttr) -> Result<(), UbusError> {
+        match attr {
+            UbusMsgAttr::Status(val) => self.put_i32(BlobAttrId::STATUS, val),
+            UbusMsgAttr::ObjPath(val) => self.put_str(BlobAttrId::OBJPATH, val),
+            UbusMsgAttr::ObjId(val) => self.put_u32(BlobAttrId::OBJID, val),
+            UbusMsgAttr::Method(val) => self.put_str(BlobAttrId::METHOD, val),
+            UbusMsgAttr::ObjType(val) => self.put_u32(BlobAttrId::OBJTYPE, val),
+            #[cfg(feature = "std")]
+            UbusMsgAttr::Signature(_) => Err(UbusError::InvalidData("Can't build signature")),
+            UbusMsgAttr::Data(val) => self.put_bytes(BlobAttrId::DATA, val),
+            UbusMsgAttr::Target(val) => self.put_u32(BlobAttrId::TARGET, val),
+            UbusMsgAttr::Active(val) => self.put_bool(BlobAttrId::ACTIVE, val),
+            UbusMsgAttr::NoReply(val) => self.put_bool(BlobAttrId::NO_REPLY, val),
+            UbusMsgAttr::Subscribers(_) => Err(UbusError::InvalidData("Can't build subscribers")),
+            UbusMsgAttr::User(val) => self.put_str(BlobAttrId::USER, val),
+            UbusMsgAttr::Group(val) => self.put_str(BlobAttrId::GROUP, val),
+            UbusMsgAttr::Unknown(id, val) => self.put_bytes(id, val),
+        }
+    }
+
+    /// Append a string attribute, including the trailing NUL ubus expects
+    pub fn put_str(&mut self, attr: BlobAttrId, value: &str) -> Result<(), UbusError> {
+        self.put_blob(|blob| blob.push_str(attr.value(), value))
+    }
+
+    /// Append a big-endian u32 attribute
+    pub fn put_u32(&mut self, attr: BlobAttrId, value: u32) -> Result<(), UbusError> {
+        self.put_blob(|blob| blob.push_u32(attr.value(), value))
+    }
+
+    /// Append a big-endian u64 attribute
+    pub fn put_u64(&mut self, attr: BlobAttrId, value: u64) -> Result<(), UbusError> {
+        self.put_blob(|blob| blob.push_u64(attr.value(), value))
+    }
+
+    /// Append a big-endian two's-complement i32 attribute
+    pub fn put_i32(&mut self, attr: BlobAttrId, value: i32) -> Result<(), UbusError> {
+        self.put_u32(attr, value as u32)
+    }
 
+    /// Append a big-endian two's-complement i64 attribute
+    pub fn put_i64(&mut self, attr: BlobAttrId, value: i64) -> Result<(), UbusError> {
+        self.put_u64(attr, value as u64)
+    }
+
+    /// Append a single-byte (0 or 1) boolean attribute
+    pub fn put_bool(&mut self, attr: BlobAttrId, value: bool) -> Result<(), UbusError> {
+        self.put_blob(|blob| blob.push_bool(attr.value(), value))
+    }
+
+    /// Add the USER access control attribute, e.g. to forward a caller's credentials
+    pub fn put_user(&mut self, user: &str) -> Result<(), UbusError> {
+        self.put_str(BlobAttrId::USER, user)
+    }
+
+    /// Add the GROUP access control attribute
+    pub fn put_group(&mut self, group: &str) -> Result<(), UbusError> {
+        self.put_str(BlobAttrId::GROUP, group)
+    }
+
+    /// Add the TARGET attribute: the object a subscription or notification concerns
+    pub fn put_target(&mut self, target: u32) -> Result<(), UbusError> {
+        self.put_u32(BlobAttrId::TARGET, target)
+    }
+
+    /// Append an attribute carrying `data` verbatim
+    pub fn put_bytes(&mut self, attr: BlobAttrId, data: &[u8]) -> Result<(), UbusError> {
+        self.put_blob(|blob| blob.push_bytes(attr.value(), data))
+    }
+
+    // Chainable versions of the above, taking and returning the builder so a message can
+    // be built in one expression:
+    // `UbusMsgBuilder::new(buf, &header)?.with_u32(OBJID, id)?.with_str(METHOD, "status")?.finish()`
+
+    pub fn with(mut self, attr: UbusMsgAttr) -> Result<Self, UbusError> {
+        self.put(attr)?;
+        Ok(self)
+    }
+
+    pub fn with_str(mut self, attr: BlobAttrId, value: &str) -> Result<Self, UbusError> {
+        self.put_str(attr, value)?;
+        Ok(self)
+    }
+
+    pub fn with_u32(mut self, attr: BlobAttrId, value: u32) -> Result<Self, UbusError> {
+        self.put_u32(attr, value)?;
+        Ok(self)
+    }
+
+    pub fn with_u64(mut self, attr: BlobAttrId, value: u64) -> Result<Self, UbusError> {
+        self.put_u64(attr, value)?;
+        Ok(self)
+    }
+
+    pub fn with_i32(mut self, attr: BlobAttrId, value: i32) -> Result<Self, UbusError> {
+        self.put_i32(attr, value)?;
+        Ok(self)
+    }
+
+    pub fn with_i64(mut self, attr: BlobAttrId, value: i64) -> Result<Self, UbusError> {
+        self.put_i64(attr, value)?;
+        Ok(self)
+    }
+
+    pub fn with_bool(mut self, attr: BlobAttrId, value: bool) -> Result<Self, UbusError> {
+        self.put_bool(attr, value)?;
+        Ok(self)
+    }
+
+    pub fn with_bytes(mut self, attr: BlobAttrId, data: &[u8]) -> Result<Self, UbusError> {
+        self.put_bytes(attr, data)?;
+        Ok(self)
+    }
+
+    /// Chainable [`UbusMsgBuilder::put_nested`]
+    pub fn with_nested(
+        mut self,
+        attr: BlobAttrId,
+        build: impl FnOnce(&mut TableBuilder<'_, 'a>) -> Result<(), UbusError>,
+    ) -> Result<Self, UbusError> {
+        self.put_nested(attr, build)?;
+        Ok(self)
+    }
+
+    /// Open a blobmsg table under `attr` (usually `DATA`). Entries are written through
+    /// the returned builder, and the table length is filled in by `TableBuilder::close`.
+    pub fn open_table(&mut self, attr: BlobAttrId) -> Result<TableBuilder<'_, 'a>, UbusError> {
+        let start = self.offset;
+        self.put_bytes(attr, &[])?;
+        Ok(TableBuilder {
+            message: self,
+            start,
+        })
+    }
+
+    /// Open a blobmsg array under `attr`. Elements are written through the returned
+    /// builder, and the array length is filled in by `ArrayBuilder::close`.
+    pub fn open_array(&mut self, attr: BlobAttrId) -> Result<ArrayBuilder<'_, 'a>, UbusError> {
+        Ok(ArrayBuilder(self.open_table(attr)?))
+    }
+
+    /// Write a blobmsg table under `attr` through `build`, then fill in its length. Unlike
+    /// [`UbusMsgBuilder::open_table`] there is no `close` to forget, and nested calls
+    /// patch the length at every level.
+    pub fn put_nested(
+        &mut self,
+        attr: BlobAttrId,
+        build: impl FnOnce(&mut TableBuilder<'_, 'a>) -> Result<(), UbusError>,
+    ) -> Result<(), UbusError> {
+        let mut table = self.open_table(attr)?;
+        build(&mut table)?;
+        table.close()
+    }
+
+    /// Append a SIGNATURE attribute in libubox's policy format: one blobmsg table per
+    /// method, mapping each argument name to its `BlobMsgType` as an int32
+    pub fn put_signature(
+        &mut self,
+        methods: &[(&str, &[(&str, BlobMsgType)])],
+    ) -> Result<(), UbusError> {
+        let mut signature = self.open_table(BlobAttrId::SIGNATURE)?;
+        for (name, args) in methods {
+            let mut method = signature.open_table(name)?;
+            for (arg, ty) in *args {
+                method.put_u32(arg, ty.value())?;
+            }
+            method.close()?;
+        }
+        signature.close()
+    }
+
+    /// Append a named blobmsg value
+    fn put_msg<'b>(
+        &mut self,
+        ty: BlobMsgType,
+        name: &str,
+        data: impl IntoIterator<Item = &'b u8>,
+    ) -> Result<(), UbusError> {
+        self.put_blob(|blob| blob.push_msg(ty.value(), name, data))
+    }
+
+    /// Update the tag at `start` so it covers everything written after it
+    fn close_nested(&mut self, start: usize) -> Result<(), UbusError> {
+        let tag_buf = &mut self.buffer[start..start + BlobTag::SIZE];
+        let tag_buf: &mut [u8; BlobTag::SIZE] = tag_buf.try_into().unwrap();
+        let tag = BlobTag::from_bytes(*tag_buf);
+        let tag = BlobTag::new(tag.id(), self.offset - start, tag.is_extended())?;
+        *tag_buf = tag.to_bytes();
+        Ok(())
+    }
+
+    /// Write one attribute into the free space after `offset`, then advance past it.
+    /// On error nothing is committed and the builder can still be used.
+    fn put_blob(
+        &mut self,
+        write: impl FnOnce(&mut BlobBuilder) -> Result<(), UbusError>,
+    ) -> Result<(), UbusError> {
+        let mut blob = BlobBuilder::from_bytes(&mut self.buffer[self.offset..]);
+        write(&mut blob)?;
+        let offset = self.offset + blob.len();
+        // The outer tag must still be able to describe the whole message
+        let size = offset - UbusMsgHeader::SIZE;
+        if size > MAX_MSG_SIZE {
+            return Err(UbusError::MessageTooLarge(size));
+        }
+        BlobTag::new(0, size, false)?;
+        self.offset = offset;
         Ok(())
     }
 
@@ -165,6 +773,216 @@ impl<'a> UbusMsgBuilder<'a> {
         &self.buffer[..self.offset]
     }
 }
+
+/// A message builder that owns a `[u8; N]`, so small messages can be built on the stack
+/// without keeping a separate buffer alive. Running out of room fails as it does with
+/// [`UbusMsgBuilder`].
+///
+/// Only `put`, `put_str` and `put_u32` are offered directly. Everything else
+/// [`UbusMsgBuilder`] can add (`put_u64`, `put_bool`, `put_nested`, `open_table`, ...) goes
+/// through [`UbusStackMsgBuilder::build`]: a `UbusMsgBuilder` borrows its buffer, so this
+/// can't deref to one.
+pub struct UbusStackMsgBuilder<const N: usize> {
+    buffer: [u8; N],
+    offset: usize,
+}
+
+impl<const N: usize> UbusStackMsgBuilder<N> {
+    pub fn new(header: &UbusMsgHeader) -> Result<Self, UbusError> {
+        let mut buffer = [0u8; N];
+        let offset = UbusMsgBuilder::new(&mut buffer, header)?.offset;
+        Ok(Self { buffer, offset })
+    }
+
+    /// Add attributes through a [`UbusMsgBuilder`] writing into the internal array
+    pub fn build(
+        &mut self,
+        build: impl FnOnce(&mut UbusMsgBuilder) -> Result<(), UbusError>,
+    ) -> Result<&mut Self, UbusError> {
+        let mut message = self.builder();
+        build(&mut message)?;
+        self.offset = message.offset;
+        Ok(self)
+    }
+
+    pub fn put(&mut self, attr: UbusMsgAttr) -> Result<&mut Self, UbusError> {
+        self.build(|message| message.put(attr))
+    }
+
+    pub fn put_str(&mut self, attr: BlobAttrId, value: &str) -> Result<&mut Self, UbusError> {
+        self.build(|message| message.put_str(attr, value))
+    }
+
+    pub fn put_u32(&mut self, attr: BlobAttrId, value: u32) -> Result<&mut Self, UbusError> {
+        self.build(|message| message.put_u32(attr, value))
+    }
+
+    /// Fill in the outer length and borrow the finished message bytes
+    pub fn finish(&mut self) -> &[u8] {
+        self.builder().finish()
+    }
+
+    fn builder(&mut self) -> UbusMsgBuilder<'_> {
+        UbusMsgBuilder {
+            buffer: &mut self.buffer,
+            offset: self.offset,
+        }
+    }
+}
+
+/// Writes named blobmsg values into a table opened by [`UbusMsgBuilder::open_table`]
+pub struct TableBuilder<'b, 'a> {
+    message: &'b mut UbusMsgBuilder<'a>,
+    start: usize,
+}
+
+impl<'a> TableBuilder<'_, 'a> {
+    pub fn put_str(&mut self, name: &str, value: &str) -> Result<(), UbusError> {
+        let data = value.as_bytes().iter().chain(&[0]);
+        self.message.put_msg(BlobMsgType::STRING, name, data)
+    }
+
+    pub fn put_bool(&mut self, name: &str, value: bool) -> Result<(), UbusError> {
+        self.message
+            .put_msg(BlobMsgType::BOOL, name, &[value as u8])
+    }
+
+    pub fn put_u32(&mut self, name: &str, value: u32) -> Result<(), UbusError> {
+        self.message
+            .put_msg(BlobMsgType::INT32, name, &value.to_be_bytes())
+    }
+
+    pub fn put_u64(&mut self, name: &str, value: u64) -> Result<(), UbusError> {
+        self.message
+            .put_msg(BlobMsgType::INT64, name, &value.to_be_bytes())
+    }
+
+    pub fn put_i32(&mut self, name: &str, value: i32) -> Result<(), UbusError> {
+        self.put_u32(name, value as u32)
+    }
+
+    pub fn put_i64(&mut self, name: &str, value: i64) -> Result<(), UbusError> {
+        self.put_u64(name, value as u64)
+    }
+
+    /// Append a big-endian IEEE-754 double. Like libubox, the 8-byte payload is only
+    /// aligned to 4 bytes.
+    pub fn put_double(&mut self, name: &str, value: f64) -> Result<(), UbusError> {
+        self.message
+            .put_msg(BlobMsgType::DOUBLE, name, &value.to_be_bytes())
+    }
+
+    /// Append a named blobmsg value of any type
+    pub(crate) fn put_msg<'b>(
+        &mut self,
+        ty: BlobMsgType,
+        name: &str,
+        data: impl IntoIterator<Item = &'b u8>,
+    ) -> Result<(), UbusError> {
+        self.message.put_msg(ty, name, data)
+    }
+
+    /// Open a table nested inside this one
+    pub fn open_table(&mut self, name: &str) -> Result<TableBuilder<'_, 'a>, UbusError> {
+        let start = self.message.offset;
+        self.message.put_msg(BlobMsgType::TABLE, name, &[])?;
+        Ok(TableBuilder {
+            message: self.message,
+            start,
+        })
+    }
+
+    /// Open an array nested inside this table
+    pub fn open_array(&mut self, name: &str) -> Result<ArrayBuilder<'_, 'a>, UbusError> {
+        let start = self.message.offset;
+        self.message.put_msg(BlobMsgType::ARRAY, name, &[])?;
+        Ok(ArrayBuilder(TableBuilder {
+            message: self.message,
+            start,
+        }))
+    }
+
+    /// Write a table nested inside this one through `build`, then fill in its length
+    pub fn put_nested(
+        &mut self,
+        name: &str,
+        build: impl FnOnce(&mut TableBuilder<'_, 'a>) -> Result<(), UbusError>,
+    ) -> Result<(), UbusError> {
+        let mut table = self.open_table(name)?;
+        build(&mut table)?;
+        table.close()
+    }
+
+    /// Whether no entries have been written since the table was opened
+    pub fn is_empty(&self) -> bool {
+        // The tag still holds the length it was opened with: just its own header
+        let tag = &self.message.buffer[self.start..self.start + BlobTag::SIZE];
+        let tag = BlobTag::from_bytes(tag.try_into().unwrap());
+        self.message.offset == self.start + tag.size()
+    }
+
+    /// Finish the table, filling in its length
+    pub fn close(self) -> Result<(), UbusError> {
+        self.message.close_nested(self.start)
+    }
+}
+
+/// Writes anonymous blobmsg elements into an array opened by [`UbusMsgBuilder::open_array`]
+pub struct ArrayBuilder<'b, 'a>(pub(crate) TableBuilder<'b, 'a>);
+
+impl<'a> ArrayBuilder<'_, 'a> {
+    pub fn push_str(&mut self, value: &str) -> Result<(), UbusError> {
+        self.0.put_str("", value)
+    }
+
+    pub fn push_bool(&mut self, value: bool) -> Result<(), UbusError> {
+        self.0.put_bool("", value)
+    }
+
+    pub fn push_u32(&mut self, value: u32) -> Result<(), UbusError> {
+        self.0.put_u32("", value)
+    }
+
+    pub fn push_u64(&mut self, value: u64) -> Result<(), UbusError> {
+        self.0.put_u64("", value)
+    }
+
+    pub fn push_i32(&mut self, value: i32) -> Result<(), UbusError> {
+        self.0.put_i32("", value)
+    }
+
+    pub fn push_i64(&mut self, value: i64) -> Result<(), UbusError> {
+        self.0.put_i64("", value)
+    }
+
+    pub fn push_double(&mut self, value: f64) -> Result<(), UbusError> {
+        self.0.put_double("", value)
+    }
+
+    /// Open a table as the next element
+    pub fn open_table(&mut self) -> Result<TableBuilder<'_, 'a>, UbusError> {
+        self.0.open_table("")
+    }
+
+    /// Open an array as the next element
+    pub fn open_array(&mut self) -> Result<ArrayBuilder<'_, 'a>, UbusError> {
+        self.0.open_array("")
+    }
+
+    /// Write a table as the next element through `build`, then fill in its length
+    pub fn push_nested(
+        &mut self,
+        build: impl FnOnce(&mut TableBuilder<'_, 'a>) -> Result<(), UbusError>,
+    ) -> Result<(), UbusError> {
+        self.0.put_nested("", build)
+    }
+
+    /// Finish the array, filling in its length
+    pub fn close(self) -> Result<(), UbusError> {
+        self.0.close()
+    }
+}
+
 impl<'a> Into<&'a [u8]> for UbusMsgBuilder<'a> {
     fn into(self) -> &'a [u8] {
         self.finish()
@@ -178,6 +996,8 @@ pub enum UbusMsgAttr<'a> {
     ObjId(u32),
     Method(&'a str),
     ObjType(u32),
+    /// Needs `std`; without it a SIGNATURE attribute decodes as `Unknown`
+    #[cfg(feature = "std")]
     Signature(HashMap<&'a str, BlobMsgPayload<'a>>),
     Data(&'a [u8]),
     Target(u32),
@@ -186,27 +1006,87 @@ pub enum UbusMsgAttr<'a> {
     Subscribers(BlobIter<'a, Blob<'a>>),
     User(&'a str),
     Group(&'a str),
+    /// An id this crate doesn't know, kept with its raw payload so [`UbusMsgBuilder::put`]
+    /// writes it back unchanged
     Unknown(BlobAttrId, &'a [u8]),
 }
 
+impl UbusMsgAttr<'_> {
+    /// Id of the attribute this value is carried in
+    pub fn id(&self) -> BlobAttrId {
+        match self {
+            UbusMsgAttr::Status(_) => BlobAttrId::STATUS,
+            UbusMsgAttr::ObjPath(_) => BlobAttrId::OBJPATH,
+            UbusMsgAttr::ObjId(_) => BlobAttrId::OBJID,
+            UbusMsgAttr::Method(_) => BlobAttrId::METHOD,
+            UbusMsgAttr::ObjType(_) => BlobAttrId::OBJTYPE,
+            #[cfg(feature = "std")]
+            UbusMsgAttr::Signature(_) => BlobAttrId::SIGNATURE,
+            UbusMsgAttr::Data(_) => BlobAttrId::DATA,
+            UbusMsgAttr::Target(_) => BlobAttrId::TARGET,
+            UbusMsgAttr::Active(_) => BlobAttrId::ACTIVE,
+            UbusMsgAttr::NoReply(_) => BlobAttrId::NO_REPLY,
+            UbusMsgAttr::Subscribers(_) => BlobAttrId::SUBSCRIBERS,
+            UbusMsgAttr::User(_) => BlobAttrId::USER,
+            UbusMsgAttr::Group(_) => BlobAttrId::GROUP,
+            UbusMsgAttr::Unknown(id, _) => *id,
+        }
+    }
+}
+
+impl core::fmt::Display for UbusMsgAttr<'_> {
+    /// The attribute name and a short rendering of its value, e.g. `OBJPATH: "system"`
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{}: ", self.id())?;
+        match self {
+            UbusMsgAttr::Status(status) => write!(f, "{:?}", UbusStatus::from(*status as u32)),
+            UbusMsgAttr::ObjPath(s)
+            | UbusMsgAttr::Method(s)
+            | UbusMsgAttr::User(s)
+            | UbusMsgAttr::Group(s) => write!(f, "{:?}", s),
+            UbusMsgAttr::ObjId(id) | UbusMsgAttr::ObjType(id) | UbusMsgAttr::Target(id) => {
+                write!(f, "{:#010x}", id)
+            }
+            #[cfg(feature = "std")]
+            UbusMsgAttr::Signature(methods) => write!(f, "{} methods", methods.len()),
+            UbusMsgAttr::Active(b) | UbusMsgAttr::NoReply(b) => write!(f, "{}", b),
+            UbusMsgAttr::Subscribers(subscribers) => {
+                write!(f, "{} subscribers", subscribers.clone().count())
+            }
+            UbusMsgAttr::Data(data) | UbusMsgAttr::Unknown(_, data) => {
+                write!(f, "{} bytes", data.len())
+            }
+        }
+    }
+}
+
+/// An attribute whose payload doesn't decode as its id's type (e.g. an OBJID that isn't
+/// 4 bytes) comes out as `Unknown`, so malformed input never panics
 impl<'a> From<Blob<'a>> for UbusMsgAttr<'a> {
     fn from(blob: Blob<'a>) -> Self {
+        Self::decode(blob).unwrap_or(UbusMsgAttr::Unknown(blob.tag.id().into(), blob.data))
+    }
+}
+
+impl<'a> UbusMsgAttr<'a> {
+    fn decode(blob: Blob<'a>) -> Result<Self, UbusError> {
         let payload = Payload::from(blob.data);
-        match blob.tag.id().into() {
-            BlobAttrId::STATUS => UbusMsgAttr::Status(payload.try_into().unwrap()),
-            BlobAttrId::OBJPATH => UbusMsgAttr::ObjPath(payload.try_into().unwrap()),
-            BlobAttrId::OBJID => UbusMsgAttr::ObjId(payload.try_into().unwrap()),
-            BlobAttrId::METHOD => UbusMsgAttr::Method(payload.try_into().unwrap()),
-            BlobAttrId::OBJTYPE => UbusMsgAttr::ObjType(payload.try_into().unwrap()),
-            BlobAttrId::SIGNATURE => UbusMsgAttr::Signature(payload.try_into().unwrap()),
-            BlobAttrId::DATA => UbusMsgAttr::Data(payload.try_into().unwrap()),
-            BlobAttrId::TARGET => UbusMsgAttr::Target(payload.try_into().unwrap()),
-            BlobAttrId::ACTIVE => UbusMsgAttr::Active(payload.try_into().unwrap()),
-            BlobAttrId::NO_REPLY => UbusMsgAttr::NoReply(payload.try_into().unwrap()),
-            BlobAttrId::SUBSCRIBERS => UbusMsgAttr::Subscribers(payload.try_into().unwrap()),
-            BlobAttrId::USER => UbusMsgAttr::User(payload.try_into().unwrap()),
-            BlobAttrId::GROUP => UbusMsgAttr::Group(payload.try_into().unwrap()),
-            id => UbusMsgAttr::Unknown(id, blob.data.into()),
-        }
+        Ok(match blob.tag.id().into() {
+            BlobAttrId::STATUS => UbusMsgAttr::Status(payload.try_into()?),
+            BlobAttrId::OBJPATH => UbusMsgAttr::ObjPath(payload.try_into()?),
+            BlobAttrId::OBJID => UbusMsgAttr::ObjId(payload.try_into()?),
+            BlobAttrId::METHOD => UbusMsgAttr::Method(payload.try_into()?),
+            BlobAttrId::OBJTYPE => UbusMsgAttr::ObjType(payload.try_into()?),
+            #[cfg(feature = "std")]
+            BlobAttrId::SIGNATURE => UbusMsgAttr::Signature(payload.try_into()?),
+            BlobAttrId::DATA => UbusMsgAttr::Data(blob.data),
+            BlobAttrId::TARGET => UbusMsgAttr::Target(payload.try_into()?),
+            BlobAttrId::ACTIVE => UbusMsgAttr::Active(payload.try_into()?),
+            BlobAttrId::NO_REPLY => UbusMsgAttr::NoReply(payload.try_into()?),
+            BlobAttrId::SUBSCRIBERS => UbusMsgAttr::Subscribers(BlobIter::new(blob.data)),
+            BlobAttrId::USER => UbusMsgAttr::User(payload.try_into()?),
+            BlobAttrId::GROUP => UbusMsgAttr::Group(payload.try_into()?),
+            id => UbusMsgAttr::Unknown(id, blob.data),
+        })
     }
 }
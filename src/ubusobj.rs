@@ -1,16 +1,19 @@
-extern crate alloc;
 use crate::*;
-use alloc::{string::ToString, vec::Vec};
-use serde::{Deserialize, Serialize};
+use alloc::string::{String, ToString};
+#[cfg(feature = "json")]
+use alloc::vec::Vec;
+#[cfg(feature = "json")]
 use serde_json::Value;
 use std::collections::HashMap;
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Method<'a> {
     pub name: &'a str,
     pub policy: HashMap<&'a str, BlobMsgType>,
 }
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UbusObject<'a> {
     pub path: &'a str,
     pub id: u32,
@@ -18,7 +21,56 @@ pub struct UbusObject<'a> {
     pub methods: HashMap<&'a str, Method<'a>>,
 }
 
+/// An owned copy of a [`UbusObject`], which can be kept while the connection is used again
+#[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ObjectInfo {
+    pub path: String,
+    pub id: u32,
+    pub ty: u32,
+    /// Method name -> argument name -> argument type
+    pub methods: HashMap<String, HashMap<String, BlobMsgType>>,
+}
+
+impl From<&UbusObject<'_>> for ObjectInfo {
+    fn from(obj: &UbusObject<'_>) -> Self {
+        let methods = obj.methods.values().map(|method| {
+            let policy = method.policy.iter();
+            let policy = policy.map(|(arg, ty)| (arg.to_string(), *ty)).collect();
+            (method.name.to_string(), policy)
+        });
+        Self {
+            path: obj.path.to_string(),
+            id: obj.id,
+            ty: obj.ty,
+            methods: methods.collect(),
+        }
+    }
+}
+
 impl<'a> UbusObject<'a> {
+    /// Parse an object from the attributes of one LOOKUP reply: OBJPATH, OBJID, OBJTYPE and
+    /// a SIGNATURE table mapping each method name to a table of argument name -> type id
+    pub fn from_attrs(attrs: impl IntoIterator<Item = UbusMsgAttr<'a>>) -> Result<Self, UbusError> {
+        let mut obj = UbusObject::default();
+        for attr in attrs {
+            match attr {
+                UbusMsgAttr::ObjPath(path) => obj.path = path,
+                UbusMsgAttr::ObjId(id) => obj.id = id,
+                UbusMsgAttr::ObjType(ty) => obj.ty = ty,
+                UbusMsgAttr::Signature(nested) => {
+                    for (name, policy) in nested {
+                        let policy = method_policy(&policy)?;
+                        obj.methods.insert(name, Method { name, policy });
+                    }
+                }
+                _ => continue,
+            }
+        }
+        Ok(obj)
+    }
+
+    #[cfg(feature = "json")]
     pub fn args_from_json(&self, method: &'a str, json: &'a str) -> Result<Vec<u8>, UbusError> {
         let mut args = Vec::new();
         if json.len() == 0 {
@@ -85,3 +137,28 @@ impl<'a> UbusObject<'a> {
         }
     }
 }
+
+/// Parse a SIGNATURE attribute, as sent in LOOKUP replies, into each method's name and
+/// the type of each of its arguments. Methods come out in the order they were sent.
+pub fn parse_signature<'a>(
+    blob: &Blob<'a>,
+) -> impl Iterator<Item = Result<(&'a str, HashMap<&'a str, BlobMsgType>), UbusError>> {
+    blob.msgs()
+        .map(|(name, policy)| Ok((name, method_policy(&policy)?)))
+}
+
+/// Decode one method's policy: a table mapping each argument name to its type id as an int32
+pub(crate) fn method_policy<'a>(
+    policy: &BlobMsgPayload<'a>,
+) -> Result<HashMap<&'a str, BlobMsgType>, UbusError> {
+    let BlobMsgPayload::Table(table) = policy else {
+        return Err(UbusError::InvalidData("Method policy is not a table"));
+    };
+    table
+        .iter()
+        .map(|(k, v)| match *v {
+            BlobMsgPayload::Int32(typeid) => Ok((*k, BlobMsgType::from(typeid as u32))),
+            _ => Err(UbusError::InvalidData("Argument type is not an int32")),
+        })
+        .collect()
+}
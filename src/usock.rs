@@ -2,6 +2,7 @@ use super::*;
 use std::io::{Read, Write};
 use std::os::unix::net::UnixStream;
 use std::path::Path;
+use std::time::Duration;
 
 impl IO for UnixStream {
     type Error = std::io::Error;
@@ -9,13 +10,75 @@ impl IO for UnixStream {
         self.write_all(data).map_err(UbusError::IO)
     }
     fn get(&mut self, data: &mut [u8]) -> Result<(), UbusError> {
-        self.read_exact(data).map_err(UbusError::IO)
+        self.read_exact(data).map_err(read_error)
+    }
+}
+
+/// Shared sockets can be read and written through a reference, so one stream can be split
+/// between a reader and a writer
+impl IO for &UnixStream {
+    type Error = std::io::Error;
+    fn put(&mut self, data: &[u8]) -> Result<(), UbusError> {
+        self.write_all(data).map_err(UbusError::IO)
+    }
+    fn get(&mut self, data: &mut [u8]) -> Result<(), UbusError> {
+        self.read_exact(data).map_err(read_error)
+    }
+}
+
+/// A read that ran into the socket's read timeout is reported as [`UbusError::Timeout`]
+fn read_error(error: std::io::Error) -> UbusError {
+    match error.kind() {
+        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => UbusError::Timeout,
+        _ => UbusError::IO(error),
     }
 }
 
 impl Connection<UnixStream> {
+    /// Connect to the ubusd socket at `path`. [`Connection::reconnect`] connects to the
+    /// same path again.
     pub fn connect(path: &Path) -> Result<Self, UbusError> {
-        Self::new(UnixStream::connect(path).map_err(UbusError::IO)?)
+        let path = path.to_path_buf();
+        Self::with_connector(move || UnixStream::connect(&path).map_err(UbusError::IO))
+    }
+
+    /// Limit how long each read from ubusd may block; `None` waits forever. A read that
+    /// times out fails with [`UbusError::Timeout`]. If that happens part way through a
+    /// message the stream is out of step, and the connection should be dropped.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), UbusError> {
+        self.io().set_read_timeout(timeout).map_err(UbusError::IO)
+    }
+
+    /// The limit set with [`Connection::set_read_timeout`]
+    pub fn read_timeout(&self) -> Result<Option<Duration>, UbusError> {
+        self.io().read_timeout().map_err(UbusError::IO)
+    }
+
+    /// [`Connection::invoke`], giving up if ubusd sends nothing for `timeout`. Giving up
+    /// returns `UbusError::Status(UbusStatus::TIMEOUT)`, the status ubusd itself uses for
+    /// calls that were not answered in time. The read timeout set before is restored
+    /// afterwards.
+    pub fn invoke_timeout(
+        &mut self,
+        obj: u32,
+        method: &str,
+        args: &[u8],
+        timeout: Duration,
+        on_result: impl FnMut(BlobIter<Blob>),
+    ) -> Result<(), UbusError> {
+        let previous = self.read_timeout()?;
+        let header = self.invoke_request(obj, method, args)?;
+        self.set_read_timeout(Some(timeout))?;
+        let result = self.wait_invoke(&header, on_result);
+        // The call's own result matters more than a failure to restore the timeout
+        let _ = self.set_read_timeout(previous);
+        match result {
+            Err(UbusError::Timeout) => {
+                self.cancel(&header);
+                Err(UbusStatus::TIMEOUT.into())
+            }
+            result => result,
+        }
     }
 }
 
@@ -0,0 +1,90 @@
+#![cfg(feature = "std")]
+use std::future::Future;
+use std::io;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use ubus::*;
+
+struct NoopWaker;
+
+impl Wake for NoopWaker {
+    fn wake(self: Arc<Self>) {}
+}
+
+/// Poll a future to completion on the current thread
+fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = Waker::from(Arc::new(NoopWaker));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+/// Serves bytes from a slice, yielding once before every read
+struct Replay<'a>(&'a [u8]);
+
+/// Returns `Pending` the first time it is polled
+struct YieldOnce(bool);
+
+impl Future for YieldOnce {
+    type Output = ();
+    fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+impl AsyncIO for Replay<'_> {
+    async fn put(&mut self, _data: &[u8]) -> Result<(), UbusError> {
+        Ok(())
+    }
+    async fn get(&mut self, data: &mut [u8]) -> Result<(), UbusError> {
+        YieldOnce(false).await;
+        if data.len() > self.0.len() {
+            return Err(UbusError::IO(io::ErrorKind::UnexpectedEof.into()));
+        }
+        let (head, tail) = self.0.split_at(data.len());
+        data.copy_from_slice(head);
+        self.0 = tail;
+        Ok(())
+    }
+}
+
+const STATUS: [u8; 20] = [
+    0x00, 0x01, 0x00, 0x01, 0x13, 0x33, 0x33, 0x37, 0x00, 0x00, 0x00, 0x0c, 0x01, 0x00, 0x00, 0x08,
+    0x00, 0x00, 0x00, 0x00,
+];
+
+#[test]
+fn from_async_io() {
+    let mut io = Replay(&STATUS);
+    let mut buffer = [0u8; 64];
+    let message = block_on(UbusMsg::from_async_io(&mut io, &mut buffer)).unwrap();
+    assert_eq!(message.header.cmd_type, UbusCmdType::STATUS);
+    assert_eq!(u32::from(message.header.peer), 0x13333337);
+    assert_eq!(message.blob.get_u32(BlobAttrId::STATUS), Some(0));
+    assert!(io.0.is_empty());
+}
+
+#[test]
+fn from_async_io_errors() {
+    let mut buffer = [0u8; 64];
+    let result = block_on(UbusMsg::from_async_io(
+        &mut Replay(&STATUS[..16]),
+        &mut buffer,
+    ));
+    assert!(matches!(result, Err(UbusError::Disconnected)));
+
+    let mut bytes = STATUS;
+    bytes[0] = 1;
+    let result = block_on(UbusMsg::from_async_io(&mut Replay(&bytes), &mut buffer));
+    assert!(matches!(result, Err(UbusError::UnsupportedVersion(_))));
+}
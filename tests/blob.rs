@@ -0,0 +1,463 @@
+#![cfg(feature = "std")]
+use ubus::*;
+
+#[test]
+fn iter() {
+    // Outer blob holding OBJID, METHOD "info" and a trailing unpadded bool
+    let bytes = [
+        0x00, 0x00, 0x00, 0x1d, 0x03, 0x00, 0x00, 0x08, 0x13, 0x33, 0x33, 0x37, 0x04, 0x00, 0x00,
+        0x09, 0x69, 0x6e, 0x66, 0x6f, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x05, 0x01,
+    ];
+    let blob = Blob::from_bytes(&bytes).unwrap();
+    let attrs: Vec<Blob> = blob.iter().collect();
+    assert_eq!(attrs.len(), 3);
+    assert_eq!(attrs[0].tag.id(), BlobAttrId::OBJID.value());
+    assert_eq!(attrs[0].data, &[0x13, 0x33, 0x33, 0x37]);
+    assert_eq!(attrs[1].tag.id(), BlobAttrId::METHOD.value());
+    assert_eq!(attrs[1].data, b"info\0");
+    assert_eq!(attrs[2].tag.id(), BlobAttrId::NO_REPLY.value());
+    assert_eq!(attrs[2].data, &[0x01]);
+}
+
+#[test]
+fn iter_stops_on_short_tail() {
+    // One attribute followed by fewer bytes than a tag
+    let data = [0x03, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x01, 0x05, 0x00];
+    let mut iter = BlobIter::<Blob>::new(&data);
+    assert_eq!(iter.next().unwrap().data, &[0x00, 0x00, 0x00, 0x01]);
+    assert!(iter.next().is_none());
+    assert!(iter.next().is_none());
+}
+
+// First object of a `ubus list` reply: OBJPATH "dnsmasq", OBJID, OBJTYPE, SIGNATURE
+const LOOKUP_REPLY: &[u8] = &[
+    0x00, 0x00, 0x00, 0x34, 0x02, 0x00, 0x00, 0x0c, 0x64, 0x6e, 0x73, 0x6d, 0x61, 0x73, 0x71, 0x00,
+    0x03, 0x00, 0x00, 0x08, 0x06, 0xdb, 0x43, 0x10, 0x05, 0x00, 0x00, 0x08, 0xd6, 0xc3, 0x72, 0x01,
+    0x06, 0x00, 0x00, 0x14, 0x82, 0x00, 0x00, 0x10, 0x00, 0x07, 0x6d, 0x65, 0x74, 0x72, 0x69, 0x63,
+    0x73, 0x00, 0x00, 0x00,
+];
+
+#[test]
+fn get() {
+    let blob = Blob::from_bytes(LOOKUP_REPLY).unwrap();
+    let objid = blob.get(BlobAttrId::OBJID).unwrap();
+    assert_eq!(objid.data, &[0x06, 0xdb, 0x43, 0x10]);
+    assert_eq!(blob.get(BlobAttrId::SIGNATURE).unwrap().tag.inner_len(), 16);
+    assert!(blob.get(BlobAttrId::METHOD).is_none());
+
+    // Cut the reply off in the middle of OBJTYPE
+    let truncated = Blob {
+        tag: blob.tag,
+        data: &blob.data[..26],
+    };
+    assert!(truncated.get(BlobAttrId::OBJID).is_some());
+    assert!(truncated.get(BlobAttrId::OBJTYPE).is_none());
+    assert!(truncated.get(BlobAttrId::SIGNATURE).is_none());
+}
+
+#[test]
+fn get_str() {
+    let blob = Blob::from_bytes(LOOKUP_REPLY).unwrap();
+    assert_eq!(blob.get_str(BlobAttrId::OBJPATH), Some("dnsmasq"));
+    assert_eq!(blob.get_str(BlobAttrId::METHOD), None);
+
+    let mut buffer = [0u8; 64];
+    let mut builder = BlobBuilder::from_bytes(&mut buffer);
+    builder
+        .push_str(BlobAttrId::METHOD.value(), "a\0b")
+        .unwrap();
+    builder
+        .push_bytes(BlobAttrId::USER.value(), b"\xff\xfe\0")
+        .unwrap();
+    let len = builder.len();
+    let blob = Blob {
+        tag: BlobTag::new(0, BlobTag::SIZE + len, false).unwrap(),
+        data: &buffer[..len],
+    };
+    // Only the terminator is stripped, not the interior NUL
+    assert_eq!(blob.get_str(BlobAttrId::METHOD), Some("a\0b"));
+    assert_eq!(blob.get_str(BlobAttrId::USER), None);
+}
+
+#[test]
+fn strings_need_terminator() {
+    // OBJPATH "system" with the NUL dropped, followed by one that has it
+    let mut buffer = [0u8; 64];
+    let mut builder = BlobBuilder::from_bytes(&mut buffer);
+    builder
+        .push_bytes(BlobAttrId::OBJPATH.value(), b"system")
+        .unwrap();
+    builder
+        .push_str(BlobAttrId::METHOD.value(), "info")
+        .unwrap();
+    let len = builder.len();
+    let blob = Blob {
+        tag: BlobTag::new(0, BlobTag::SIZE + len, false).unwrap(),
+        data: &buffer[..len],
+    };
+    assert_eq!(blob.get_str(BlobAttrId::OBJPATH), None);
+    assert_eq!(blob.get_str(BlobAttrId::METHOD), Some("info"));
+    let attrs: Vec<UbusMsgAttr> = BlobIter::new(blob.data).collect();
+    assert!(matches!(attrs[0], UbusMsgAttr::Unknown(_, b"system")));
+
+    // put_str writes exactly one NUL, even for an empty string
+    let header = UbusMsgHeader::new(UbusCmdType::LOOKUP, 1, 0);
+    let bytes = UbusMsgBuilder::build_vec(&header, |message| {
+        message.put_str(BlobAttrId::OBJPATH, "abc")?;
+        message.put_str(BlobAttrId::METHOD, "")
+    })
+    .unwrap();
+    let message = UbusMsg::from_bytes(&bytes).unwrap();
+    assert_eq!(
+        message.blob.get(BlobAttrId::OBJPATH).unwrap().data,
+        b"abc\0"
+    );
+    assert_eq!(message.blob.get(BlobAttrId::METHOD).unwrap().data, b"\0");
+}
+
+#[test]
+fn get_u32() {
+    let blob = Blob::from_bytes(LOOKUP_REPLY).unwrap();
+    assert_eq!(blob.get_u32(BlobAttrId::OBJID), Some(0x06db4310));
+    assert_eq!(blob.get_u32(BlobAttrId::OBJTYPE), Some(0xd6c37201));
+    assert_eq!(blob.get_u32(BlobAttrId::STATUS), None);
+
+    let mut buffer = [0u8; 16];
+    let mut builder = BlobBuilder::from_bytes(&mut buffer);
+    builder
+        .push_bytes(BlobAttrId::OBJID.value(), &[1, 2, 3])
+        .unwrap();
+    let len = builder.len();
+    let blob = Blob {
+        tag: BlobTag::new(0, BlobTag::SIZE + len, false).unwrap(),
+        data: &buffer[..len],
+    };
+    assert_eq!(blob.get_u32(BlobAttrId::OBJID), None);
+}
+
+#[test]
+fn iter_rejects_overlong_tags() {
+    // 16-byte blob whose first attribute claims 0xffff bytes
+    let bytes = [
+        0x00, 0x00, 0x00, 0x10, 0x07, 0x00, 0xff, 0xff, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07,
+        0x08,
+    ];
+    let blob = Blob::from_bytes(&bytes).unwrap();
+    assert_eq!(blob.iter().count(), 0);
+    assert!(blob.get(BlobAttrId::DATA).is_none());
+
+    // Tags that are smaller than a tag can't be advanced over either
+    let bytes = [
+        0x00, 0x00, 0x00, 0x0c, 0x07, 0x00, 0x00, 0x02, 0x07, 0x00, 0x00, 0x04,
+    ];
+    let blob = Blob::from_bytes(&bytes).unwrap();
+    assert_eq!(blob.iter().count(), 0);
+}
+
+#[test]
+fn malformed_blobmsg() {
+    let cases: &[&[u8]] = &[
+        // Extended tag with no room for the name length
+        &[0x85, 0x00, 0x00, 0x05, 0x00],
+        // Name longer than the payload
+        &[0x85, 0x00, 0x00, 0x08, 0x00, 0x09, 0x61, 0x62],
+        // Name without its nul terminator
+        &[0x85, 0x00, 0x00, 0x07, 0x00, 0x01, 0x61],
+        // Name terminator but no padding before the value
+        &[0x85, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00],
+        // INT32 with a two byte payload
+        &[0x85, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x01, 0x02],
+        // Invalid UTF-8 name
+        &[0x85, 0x00, 0x00, 0x08, 0x00, 0x01, 0xff, 0x00],
+    ];
+    for case in cases {
+        let blob = BlobIter::<Blob>::new(case).next().unwrap();
+        let msg: Result<BlobMsg, _> = blob.try_into();
+        assert!(msg.is_err(), "{:?}", case);
+    }
+}
+
+#[test]
+fn tag_accessors() {
+    let tag = BlobTag::from_bytes([0x85, 0x00, 0x01, 0x02]);
+    assert!(tag.is_extended());
+    assert_eq!(tag.id(), 5);
+    assert_eq!(tag.size(), 0x102);
+    assert_eq!(tag.inner_len(), 0x102 - BlobTag::SIZE);
+    assert_eq!(tag.padded_len(), 0x104);
+
+    let tag = BlobTag::new(BlobAttrId::OBJPATH.value(), 9, false).unwrap();
+    assert_eq!(tag.to_bytes(), [0x02, 0x00, 0x00, 0x09]);
+    assert_eq!(tag.padded_len(), 12);
+}
+
+#[test]
+fn aligned_len() {
+    for (len, aligned) in [(0, 0), (1, 4), (3, 4), (4, 4), (5, 8)] {
+        assert_eq!(BlobTag::aligned_len(len), aligned, "len {len}");
+    }
+    // The outer tag of a built message covers the padded attributes inside it
+    let header = UbusMsgHeader::new(UbusCmdType::INVOKE, 1, 0);
+    let bytes =
+        UbusMsgBuilder::build_vec(&header, |message| message.put_bytes(BlobAttrId::DATA, b"x"))
+            .unwrap();
+    let outer = &bytes[UbusMsgHeader::SIZE..];
+    let tag = BlobTag::from_bytes(outer[..4].try_into().unwrap());
+    assert_eq!(
+        tag.size(),
+        BlobTag::SIZE + BlobTag::aligned_len(BlobTag::SIZE + 1)
+    );
+    assert_eq!(bytes.len(), UbusMsgHeader::SIZE + tag.size());
+}
+
+#[test]
+fn large_lengths() {
+    // 70 KB doesn't fit in 16 bits; the length field is 24 bits wide
+    let len = 70 * 1024 + BlobTag::SIZE;
+    let tag = BlobTag::from_bytes([0x87, 0x01, 0x18, 0x04]);
+    assert!(tag.is_extended());
+    assert_eq!(tag.id(), 7);
+    assert_eq!(tag.size(), len);
+    assert_eq!(tag.inner_len(), 70 * 1024);
+    assert!(tag.is_valid().is_ok());
+
+    let tag = BlobTag::new(BlobAttrId::DATA.value(), len, false).unwrap();
+    assert_eq!(tag.to_bytes(), [0x07, 0x01, 0x18, 0x04]);
+    assert!(!tag.is_extended());
+    assert!(BlobTag::new(0, 0x100_0000, false).is_err());
+
+    let header = UbusMsgHeader {
+        version: UbusMsgVersion::CURRENT,
+        cmd_type: UbusCmdType::DATA,
+        sequence: 1u16.into(),
+        peer: 0u32.into(),
+    };
+    let payload = vec![0x5a; 70 * 1024];
+    let mut buffer = vec![0u8; 80 * 1024];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header).unwrap();
+    message.put_bytes(BlobAttrId::DATA, &payload).unwrap();
+    let message = UbusMsg::from_bytes(message.finish()).unwrap();
+    let data = message.blob.get(BlobAttrId::DATA).unwrap();
+    assert_eq!(data.tag.size(), len);
+    assert_eq!(data.data, &payload[..]);
+}
+
+#[test]
+fn msgs() {
+    let header = UbusMsgHeader {
+        version: UbusMsgVersion::CURRENT,
+        cmd_type: UbusCmdType::DATA,
+        sequence: 1u16.into(),
+        peer: 0u32.into(),
+    };
+    let mut buffer = [0u8; 256];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header).unwrap();
+    // Names of every length modulo 4, so each padding case is hit
+    let mut table = message.open_table(BlobAttrId::DATA).unwrap();
+    table.put_str("a", "one").unwrap();
+    table.put_u32("ab", 2).unwrap();
+    table.put_bool("abc", true).unwrap();
+    table.put_i64("abcd", -4).unwrap();
+    let mut nested = table.open_table("nested").unwrap();
+    nested.put_str("inner", "x").unwrap();
+    nested.close().unwrap();
+    table.close().unwrap();
+    let bytes = message.finish();
+
+    let message = UbusMsg::from_bytes(bytes).unwrap();
+    let data = message.blob.get(BlobAttrId::DATA).unwrap();
+    let names: Vec<_> = data.msgs().map(|(name, _)| name).collect();
+    assert_eq!(names, ["a", "ab", "abc", "abcd", "nested"]);
+
+    assert!(matches!(
+        data.get_msg("a"),
+        Some(BlobMsgPayload::String("one"))
+    ));
+    assert!(matches!(data.get_msg("ab"), Some(BlobMsgPayload::Int32(2))));
+    assert!(matches!(data.get_msg("abc"), Some(BlobMsgPayload::Int8(1))));
+    assert!(matches!(
+        data.get_msg("abcd"),
+        Some(BlobMsgPayload::Int64(-4))
+    ));
+    let Some(BlobMsgPayload::Table(nested)) = data.get_msg("nested") else {
+        panic!("nested table missing");
+    };
+    assert!(matches!(nested["inner"], BlobMsgPayload::String("x")));
+    assert!(data.get_msg("missing").is_none());
+}
+
+#[test]
+fn msg_types() {
+    assert_eq!(BlobMsgType::INT8, BlobMsgType::BOOL);
+    assert_eq!(BlobMsgType::INT8.value(), 7);
+    assert_eq!(BlobMsgType::DOUBLE.value(), 8);
+    assert_eq!(format!("{:?}", BlobMsgType::from(7)), "BOOL");
+    assert!(!BlobMsgType::from(9).known());
+
+    // Extended tags carry the blobmsg type in their id
+    let tag = BlobTag::new(BlobMsgType::STRING.value(), 12, true).unwrap();
+    assert_eq!(tag.msg_type(), Some(BlobMsgType::STRING));
+    let tag = BlobTag::new(BlobAttrId::DATA.value(), 12, false).unwrap();
+    assert_eq!(tag.msg_type(), None);
+
+    let mut builder = BlobMsgBuilder::new_extended(BlobMsgType::INT32.value(), "n");
+    builder.push_int32(5).unwrap();
+    let blob = BlobIter::<Blob>::new(builder.data()).next().unwrap();
+    assert_eq!(blob.tag.msg_type(), Some(BlobMsgType::INT32));
+    let msg: BlobMsg = blob.try_into().unwrap();
+    assert_eq!(msg.data.ty(), BlobMsgType::INT32);
+    assert_eq!(BlobMsgPayload::String("x").ty(), BlobMsgType::STRING);
+    assert_eq!(BlobMsgPayload::Unknown(42, &[]).ty(), BlobMsgType::from(42));
+}
+
+#[test]
+fn debug_lists_attributes() {
+    let header = UbusMsgHeader {
+        version: UbusMsgVersion::CURRENT,
+        cmd_type: UbusCmdType::INVOKE,
+        sequence: 1u16.into(),
+        peer: 0u32.into(),
+    };
+    let mut buffer = [0u8; 128];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header).unwrap();
+    message.put_u32(BlobAttrId::OBJID, 0x2468).unwrap();
+    message.put_str(BlobAttrId::METHOD, "info").unwrap();
+    let mut data = message.open_table(BlobAttrId::DATA).unwrap();
+    data.put_bool("up", true).unwrap();
+    data.close().unwrap();
+    let message = UbusMsg::from_bytes(message.finish()).unwrap();
+    assert_eq!(
+        format!("{:?}", message.blob),
+        "Blob(len=40) [OBJID -> 4 bytes: 0x00002468, METHOD -> 5 bytes: \"info\", \
+         DATA -> 16 bytes: [\"up\" (BOOL) -> 9 bytes: 1]]"
+    );
+}
+
+#[test]
+fn debug_depth_is_limited() {
+    // SUBSCRIBERS nested ten deep, each holding only the next one
+    let mut inner = Vec::new();
+    for _ in 0..10 {
+        let mut buffer = [0u8; 128];
+        let mut builder = BlobBuilder::from_bytes(&mut buffer);
+        builder
+            .push_bytes(BlobAttrId::SUBSCRIBERS.value(), &inner)
+            .unwrap();
+        let len = builder.len();
+        inner = buffer[..len].to_vec();
+    }
+    let blob = Blob::from_bytes(&inner).unwrap();
+    let debug = format!("{:?}", blob);
+    assert_eq!(debug.matches("SUBSCRIBERS").count(), 4);
+    assert!(debug.ends_with("[..]]]]]"));
+}
+
+#[test]
+fn debug_scalar_blob() {
+    let blob = |id: BlobAttrId, data: &[u8]| {
+        let mut buffer = [0u8; 32];
+        let mut builder = BlobBuilder::from_bytes(&mut buffer);
+        builder.push_bytes(id.value(), data).unwrap();
+        let len = builder.len();
+        format!("{:?}", Blob::from_bytes(&buffer[..len]).unwrap())
+    };
+    // Four bytes that would also read as an empty attribute header
+    assert_eq!(
+        blob(BlobAttrId::OBJID, &[0x00, 0x00, 0x00, 0x04]),
+        "Blob(len=4) OBJID -> 4 bytes: 0x00000004"
+    );
+    assert_eq!(
+        blob(BlobAttrId::METHOD, b"info\0"),
+        "Blob(len=5) METHOD -> 5 bytes: \"info\""
+    );
+    // A value that doesn't fit its id's type shows its bytes
+    assert_eq!(
+        blob(BlobAttrId::OBJTYPE, &[0x00, 0x00, 0x00, 0x04, 0xff]),
+        "Blob(len=5) OBJTYPE -> 5 bytes: [00, 00, 00, 04, ff]"
+    );
+}
+
+#[test]
+fn debug_depth_covers_blobmsg() {
+    // Tables nested ten deep inside DATA
+    fn nest(table: &mut TableBuilder, depth: usize) -> Result<(), UbusError> {
+        match depth {
+            0 => table.put_i32("n", 1),
+            _ => table.put_nested("t", |inner| nest(inner, depth - 1)),
+        }
+    }
+    let header = UbusMsgHeader::new(UbusCmdType::DATA, 1, 0);
+    let bytes = UbusMsgBuilder::build_vec(&header, |message| {
+        message.put_nested(BlobAttrId::DATA, |table| nest(table, 10))
+    })
+    .unwrap();
+    let message = UbusMsg::from_bytes(&bytes).unwrap();
+    let debug = format!("{:?}", message.blob);
+    assert_eq!(debug.matches("\"t\" (TABLE)").count(), 3);
+    assert!(debug.ends_with("[..]]]]]"));
+}
+
+#[test]
+fn equality() {
+    let header = UbusMsgHeader::new(UbusCmdType::DATA, 1, 0);
+    let build = |flag| {
+        UbusMsgBuilder::build_vec(&header, |message| {
+            message.put_nested(BlobAttrId::DATA, |table| {
+                table.put_bool("abc", flag)?;
+                table.put_str("name", "lan")
+            })
+        })
+        .unwrap()
+    };
+    let bytes = build(true);
+    let parsed = UbusMsg::from_bytes(&bytes).unwrap().blob;
+    let same = build(true);
+    assert_eq!(parsed, UbusMsg::from_bytes(&same).unwrap().blob);
+
+    // Garbage in the padding after the bool only matters byte for byte
+    let mut padded = bytes.clone();
+    let padding = UbusMsgHeader::SIZE + 2 * BlobTag::SIZE + 13;
+    padded[padding..padding + 3].copy_from_slice(&[0xaa; 3]);
+    let padded = UbusMsg::from_bytes(&padded).unwrap().blob;
+    assert_ne!(parsed, padded);
+    assert!(parsed.structural_eq(&padded));
+
+    let other = build(false);
+    let other = UbusMsg::from_bytes(&other).unwrap().blob;
+    assert_ne!(parsed, other);
+    assert!(!parsed.structural_eq(&other));
+}
+
+#[test]
+fn unknown_ids_are_skipped() {
+    // Tag ids are 7 bits, so 0x7f is the highest; 0xff in the first byte would be the
+    // extended bit on top of it
+    let header = UbusMsgHeader::new(UbusCmdType::DATA, 1, 0);
+    let bytes = UbusMsgBuilder::build_vec(&header, |message| {
+        message.put_bytes(BlobAttrId::from(0x7f), &[1, 2, 3])?;
+        message.put_u32(BlobAttrId::OBJID, 0x2468)?;
+        message.put_bytes(BlobAttrId::from(0x42), &[])?;
+        message.put_str(BlobAttrId::METHOD, "info")
+    })
+    .unwrap();
+    let message = UbusMsg::from_bytes(&bytes).unwrap();
+    assert_eq!(message.blob.get_u32(BlobAttrId::OBJID), Some(0x2468));
+    assert_eq!(message.blob.get_str(BlobAttrId::METHOD), Some("info"));
+    assert_eq!(message.blob.iter().count(), 4);
+
+    let attrs: Vec<UbusMsgAttr> = BlobIter::new(message.blob.data).collect();
+    let ids: Vec<u32> = attrs.iter().map(|attr| attr.id().value()).collect();
+    assert_eq!(
+        ids,
+        [
+            0x7f,
+            BlobAttrId::OBJID.value(),
+            0x42,
+            BlobAttrId::METHOD.value()
+        ]
+    );
+    assert!(matches!(attrs[0], UbusMsgAttr::Unknown(_, [1, 2, 3])));
+    assert!(matches!(attrs[1], UbusMsgAttr::ObjId(0x2468)));
+    assert!(matches!(attrs[2], UbusMsgAttr::Unknown(_, [])));
+    assert!(matches!(attrs[3], UbusMsgAttr::Method("info")));
+}
@@ -0,0 +1,316 @@
+#![cfg(feature = "json")]
+use ubus::*;
+
+/// Build a DATA message; `build` fills in its blobmsg table
+fn data(buffer: &mut [u8], build: impl FnOnce(&mut TableBuilder)) -> &[u8] {
+    let header = UbusMsgHeader {
+        version: UbusMsgVersion::CURRENT,
+        cmd_type: UbusCmdType::DATA,
+        sequence: 1u16.into(),
+        peer: 0u32.into(),
+    };
+    let mut message = UbusMsgBuilder::new(buffer, &header).unwrap();
+    let mut table = message.open_table(BlobAttrId::DATA).unwrap();
+    build(&mut table);
+    table.close().unwrap();
+    message.finish()
+}
+
+/// The first blobmsg entry of the DATA attribute
+fn first(bytes: &[u8]) -> BlobMsg<'_> {
+    let data = UbusMsg::from_bytes(bytes)
+        .unwrap()
+        .blob
+        .get(BlobAttrId::DATA);
+    data.unwrap().iter().next().unwrap().try_into().unwrap()
+}
+
+#[test]
+fn as_table() {
+    let mut buffer = [0u8; 256];
+    // Shaped like a `network.interface.lan status` reply
+    let bytes = data(&mut buffer, |reply| {
+        let mut status = reply.open_table("status").unwrap();
+        status.put_bool("up", true).unwrap();
+        status.put_bool("pending", false).unwrap();
+        status.put_str("l3_device", "eth0").unwrap();
+        status.put_u32("uptime", 1234).unwrap();
+        status.put_str("proto", "dhcp").unwrap();
+        status.put_str("proto", "static").unwrap();
+        status.close().unwrap();
+    });
+
+    let status = first(bytes);
+    assert_eq!(status.name, "status");
+    let mut names: Vec<_> = status.as_table().unwrap().map(|(name, _)| name).collect();
+    names.sort();
+    assert_eq!(names, ["l3_device", "pending", "proto", "up", "uptime"]);
+
+    assert!(matches!(status.get("up"), Some(BlobMsgPayload::Int8(1))));
+    assert!(matches!(
+        status.get("pending"),
+        Some(BlobMsgPayload::Int8(0))
+    ));
+    assert!(matches!(
+        status.get("l3_device"),
+        Some(BlobMsgPayload::String("eth0"))
+    ));
+    assert!(matches!(
+        status.get("uptime"),
+        Some(BlobMsgPayload::Int32(1234))
+    ));
+    // Duplicate keys: last wins
+    assert!(matches!(
+        status.get("proto"),
+        Some(BlobMsgPayload::String("static"))
+    ));
+    assert!(status.get("missing").is_none());
+}
+
+#[test]
+fn as_table_on_scalar() {
+    let mut buffer = [0u8; 64];
+    let bytes = data(&mut buffer, |reply| reply.put_str("name", "lan").unwrap());
+    let name = first(bytes);
+    assert!(name.as_table().is_none());
+    assert!(name.get("name").is_none());
+}
+
+#[test]
+fn as_array() {
+    let mut buffer = [0u8; 256];
+    let bytes = data(&mut buffer, |reply| {
+        let mut mixed = reply.open_array("mixed").unwrap();
+        mixed.push_str("eth0").unwrap();
+        mixed.push_u32(7).unwrap();
+        mixed.push_bool(true).unwrap();
+        mixed.push_i64(-1).unwrap();
+        mixed.close().unwrap();
+    });
+
+    let mixed = first(bytes);
+    assert!(mixed.as_table().is_none());
+    let elements: Vec<_> = mixed.as_array().unwrap().collect();
+    assert_eq!(elements.len(), 4);
+    assert!(matches!(elements[0], BlobMsgPayload::String("eth0")));
+    assert!(matches!(elements[1], BlobMsgPayload::Int32(7)));
+    assert!(matches!(elements[2], BlobMsgPayload::Int8(1)));
+    assert!(matches!(elements[3], BlobMsgPayload::Int64(-1)));
+}
+
+#[test]
+fn array_of_tables() {
+    let mut buffer = [0u8; 256];
+    // Like the `ipv4-address` field of an interface dump
+    let bytes = data(&mut buffer, |reply| {
+        let mut addresses = reply.open_array("ipv4-address").unwrap();
+        for (address, mask) in [("192.168.1.1", 24), ("10.0.0.1", 8)] {
+            let mut entry = addresses.open_table().unwrap();
+            entry.put_str("address", address).unwrap();
+            entry.put_u32("mask", mask).unwrap();
+            entry.close().unwrap();
+        }
+        addresses.close().unwrap();
+    });
+
+    let addresses = first(bytes);
+    let addresses: Vec<_> = addresses
+        .as_array()
+        .unwrap()
+        .map(|entry| {
+            let BlobMsgPayload::Table(entry) = entry else {
+                panic!("array element is not a table");
+            };
+            (entry["address"].to_string(), entry["mask"].to_string())
+        })
+        .collect();
+    assert_eq!(
+        addresses,
+        [
+            ("\"192.168.1.1\"".to_string(), "24".to_string()),
+            ("\"10.0.0.1\"".to_string(), "8".to_string())
+        ]
+    );
+}
+
+#[test]
+fn to_json() {
+    let mut buffer = [0u8; 512];
+    let bytes = data(&mut buffer, |reply| {
+        reply.put_bool("up", true).unwrap();
+        reply.put_str("l3_device", "eth0").unwrap();
+        reply.put_u32("uptime", 1234).unwrap();
+        reply.put_i64("rx_bytes", i64::MAX).unwrap();
+        reply.put_i32("metric", -1).unwrap();
+        let mut addresses = reply.open_array("ipv4-address").unwrap();
+        let mut entry = addresses.open_table().unwrap();
+        entry.put_str("address", "192.168.1.1").unwrap();
+        entry.put_u32("mask", 24).unwrap();
+        entry.close().unwrap();
+        addresses.close().unwrap();
+    });
+    let message = UbusMsg::from_bytes(bytes).unwrap();
+    let json = blobmsg_to_json(&message.blob.get(BlobAttrId::DATA).unwrap());
+
+    assert_eq!(json.get("up").unwrap().as_bool(), Some(true));
+    assert_eq!(json.get("l3_device").unwrap().as_str(), Some("eth0"));
+    assert_eq!(json.get("uptime").unwrap().as_i64(), Some(1234));
+    assert_eq!(json.get("rx_bytes").unwrap().as_i64(), Some(i64::MAX));
+    assert_eq!(json.get("metric").unwrap().as_i64(), Some(-1));
+    let addresses = json.get("ipv4-address").unwrap().as_array().unwrap();
+    assert_eq!(addresses.len(), 1);
+    assert_eq!(
+        addresses[0].get("address").unwrap().as_str(),
+        Some("192.168.1.1")
+    );
+    assert_eq!(addresses[0].get("mask").unwrap().as_i64(), Some(24));
+}
+
+#[test]
+fn scalar_to_json() {
+    use serde_json::Value;
+    assert_eq!(
+        Value::from(&BlobMsgPayload::Double(1.5)).as_f64(),
+        Some(1.5)
+    );
+    assert!(Value::from(&BlobMsgPayload::Double(f64::NAN)).is_null());
+    assert!(Value::from(&BlobMsgPayload::Unknown(42, &[1])).is_null());
+    assert_eq!(Value::from(&BlobMsgPayload::Int8(0)).as_bool(), Some(false));
+    assert_eq!(Value::from(&BlobMsgPayload::Int16(-2)).as_i64(), Some(-2));
+}
+
+#[test]
+fn doubles() {
+    let mut buffer = [0u8; 256];
+    let bytes = data(&mut buffer, |reply| {
+        reply.put_double("load", 1.5).unwrap();
+        reply.put_double("inf", f64::INFINITY).unwrap();
+        reply.put_u32("count", 3).unwrap();
+        let mut samples = reply.open_array("samples").unwrap();
+        samples.push_double(-0.25).unwrap();
+        samples.push_double(f64::NAN).unwrap();
+        samples.close().unwrap();
+    });
+
+    // blobmsg_add_double(&b, "load", 1.5): name padded to 4, then the big-endian bits
+    let entry = first(bytes);
+    assert_eq!(entry.name, "load");
+    assert!(matches!(entry.data, BlobMsgPayload::Double(value) if value == 1.5));
+    let data_start = UbusMsgHeader::SIZE + 2 * BlobTag::SIZE;
+    assert_eq!(
+        &bytes[data_start..data_start + 20],
+        &[
+            0x88, 0x00, 0x00, 0x14, 0x00, 0x04, b'l', b'o', b'a', b'd', 0x00, 0x00, 0x3f, 0xf8,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+        ]
+    );
+
+    let message = UbusMsg::from_bytes(bytes).unwrap();
+    let data = message.blob.get(BlobAttrId::DATA).unwrap();
+    assert_eq!(data.get_double("load"), Some(1.5));
+    assert_eq!(data.get_double("inf"), Some(f64::INFINITY));
+    assert_eq!(data.get_double("count"), None);
+    assert_eq!(data.get_double("missing"), None);
+
+    // JSON has no NaN or Infinity, so those become null
+    let json = blobmsg_to_json(&data);
+    assert_eq!(json.get("load").unwrap().as_f64(), Some(1.5));
+    assert!(json.get("inf").unwrap().is_null());
+    let samples = json.get("samples").unwrap().as_array().unwrap();
+    assert_eq!(samples[0].as_f64(), Some(-0.25));
+    assert!(samples[1].is_null());
+}
+
+#[test]
+fn json_round_trip() {
+    let json: serde_json::Value = serde_json::from_str(
+        r#"{
+            "name": "lan",
+            "up": true,
+            "small": -5,
+            "big": 1099511627776,
+            "ratio": 0.5,
+            "nothing": null,
+            "dns": ["1.1.1.1", 53, [false]],
+            "route": {"target": "0.0.0.0", "mask": 0, "empty": {}},
+            "huge": 18446744073709551615
+        }"#,
+    )
+    .unwrap();
+    let header = UbusMsgHeader::new(UbusCmdType::INVOKE, 1, 0);
+    let bytes =
+        UbusMsgBuilder::build_vec(&header, |message| json_to_blobmsg(&json, message)).unwrap();
+    let message = UbusMsg::from_bytes(&bytes).unwrap();
+    let data = message.blob.get(BlobAttrId::DATA).unwrap();
+
+    let ty = |name| data.get_msg(name).unwrap().ty();
+    assert_eq!(ty("small"), BlobMsgType::INT32);
+    assert_eq!(ty("big"), BlobMsgType::INT64);
+    assert_eq!(ty("ratio"), BlobMsgType::DOUBLE);
+    assert_eq!(ty("nothing"), BlobMsgType::UNSPEC);
+    assert_eq!(ty("dns"), BlobMsgType::ARRAY);
+    // Too big for either integer type, so it can only come back as a double
+    assert_eq!(ty("huge"), BlobMsgType::DOUBLE);
+    assert_eq!(data.get_double("huge"), Some(u64::MAX as f64));
+
+    let back = blobmsg_to_json(&data);
+    for (name, value) in json.as_object().unwrap() {
+        if name != "huge" {
+            assert_eq!(back.get(name), Some(value), "{name}");
+        }
+    }
+    assert_eq!(
+        back.as_object().unwrap().len(),
+        json.as_object().unwrap().len()
+    );
+}
+
+#[test]
+fn json_must_be_object() {
+    let header = UbusMsgHeader::new(UbusCmdType::INVOKE, 1, 0);
+    let list: serde_json::Value = serde_json::from_str("[1, 2]").unwrap();
+    let result = UbusMsgBuilder::build_vec(&header, |message| json_to_blobmsg(&list, message));
+    assert!(matches!(result, Err(UbusError::InvalidData(_))));
+}
+
+#[test]
+fn to_json_string() {
+    let mut buffer = [0u8; 256];
+    // Shaped like a `system board` reply
+    let bytes = data(&mut buffer, |reply| {
+        reply.put_str("kernel", "5.15.150").unwrap();
+        reply.put_str("hostname", "OpenWrt").unwrap();
+        let mut release = reply.open_table("release").unwrap();
+        release.put_str("distribution", "OpenWrt").unwrap();
+        release.put_u32("revision", 23).unwrap();
+        release.close().unwrap();
+        let mut flags = reply.open_array("flags").unwrap();
+        flags.push_bool(true).unwrap();
+        flags.push_str("a\"b").unwrap();
+        flags.close().unwrap();
+        reply.open_table("empty").unwrap().close().unwrap();
+    });
+    let message = UbusMsg::from_bytes(bytes).unwrap();
+    let data = message.blob.get(BlobAttrId::DATA).unwrap();
+
+    assert_eq!(
+        blobmsg_to_json_string(&data, false),
+        r#"{"empty":{},"flags":[true,"a\"b"],"hostname":"OpenWrt","kernel":"5.15.150","release":{"distribution":"OpenWrt","revision":23}}"#
+    );
+    assert_eq!(
+        blobmsg_to_json_string(&data, true),
+        r#"{
+  "empty": {},
+  "flags": [
+    true,
+    "a\"b"
+  ],
+  "hostname": "OpenWrt",
+  "kernel": "5.15.150",
+  "release": {
+    "distribution": "OpenWrt",
+    "revision": 23
+  }
+}"#
+    );
+}
@@ -0,0 +1,474 @@
+#![cfg(feature = "std")]
+use ubus::*;
+
+fn header() -> UbusMsgHeader {
+    UbusMsgHeader {
+        version: UbusMsgVersion::CURRENT,
+        cmd_type: UbusCmdType::INVOKE,
+        sequence: 1u16.into(),
+        peer: 0x13333337u32.into(),
+    }
+}
+
+#[test]
+fn put_str() {
+    let mut buffer = [0u8; 64];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    message.put_str(BlobAttrId::METHOD, "info").unwrap();
+    message.put_str(BlobAttrId::OBJPATH, "system").unwrap();
+    assert_eq!(
+        message.finish(),
+        &[
+            0x00, 0x05, 0x00, 0x01, 0x13, 0x33, 0x33, 0x37, 0x00, 0x00, 0x00, 0x1c, 0x04, 0x00,
+            0x00, 0x09, 0x69, 0x6e, 0x66, 0x6f, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x0b,
+            0x73, 0x79, 0x73, 0x74, 0x65, 0x6d, 0x00, 0x00,
+        ]
+    );
+}
+
+#[test]
+fn put_u32() {
+    let mut buffer = [0u8; 64];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    message.put_u32(BlobAttrId::OBJID, 0x13333337).unwrap();
+    assert_eq!(
+        message.finish(),
+        &[
+            0x00, 0x05, 0x00, 0x01, 0x13, 0x33, 0x33, 0x37, 0x00, 0x00, 0x00, 0x0c, 0x03, 0x00,
+            0x00, 0x08, 0x13, 0x33, 0x33, 0x37,
+        ]
+    );
+}
+
+#[test]
+fn put_u32_overflow() {
+    // Room for the header, outer tag and the attribute tag, but not the payload
+    let mut buffer = [0u8; UbusMsgHeader::SIZE + BlobTag::SIZE + BlobTag::SIZE];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    assert!(message.put_u32(BlobAttrId::OBJID, 0x13333337).is_err());
+}
+
+#[test]
+fn put_u64() {
+    let mut buffer = [0u8; 64];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    message.put_str(BlobAttrId::METHOD, "a").unwrap();
+    message
+        .put_u64(BlobAttrId::DATA, 0x0123456789abcdef)
+        .unwrap();
+    let bytes = message.finish();
+    assert_eq!(bytes.len(), UbusMsgHeader::SIZE + 4 + 8 + 12);
+
+    let outer = Blob::from_bytes(&bytes[UbusMsgHeader::SIZE..]).unwrap();
+    assert_eq!(outer.tag.size(), 4 + 8 + 12);
+    let mut attrs = BlobIter::<Blob>::new(outer.data);
+    let method = attrs.next().unwrap();
+    assert_eq!(method.tag.id(), BlobAttrId::METHOD.value());
+    let data = attrs.next().unwrap();
+    assert_eq!(data.tag.id(), BlobAttrId::DATA.value());
+    // Payload must start on a 4-byte boundary after the padded string
+    assert_eq!(data.data.as_ptr() as usize - bytes.as_ptr() as usize, 24);
+    let value: u64 = Payload::from(data.data).try_into().unwrap();
+    assert_eq!(value, 0x0123456789abcdef);
+    assert!(attrs.next().is_none());
+}
+
+fn signed_roundtrip(value32: i32, value64: i64) {
+    let mut buffer = [0u8; 64];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    message.put_i32(BlobAttrId::STATUS, value32).unwrap();
+    message.put_i64(BlobAttrId::DATA, value64).unwrap();
+    let bytes = message.finish();
+    let blob = Blob::from_bytes(&bytes[UbusMsgHeader::SIZE..]).unwrap();
+    assert_eq!(blob.get_i32(BlobAttrId::STATUS), Some(value32));
+    assert_eq!(blob.get_i64(BlobAttrId::DATA), Some(value64));
+}
+
+#[test]
+fn put_signed() {
+    signed_roundtrip(-1, -1);
+    signed_roundtrip(i32::MIN, i64::MIN);
+    signed_roundtrip(i32::MAX, i64::MAX);
+    // The high bit of the 32-bit value must not bleed into the 64-bit reader
+    signed_roundtrip(i32::MIN, i32::MIN as u32 as i64);
+}
+
+#[test]
+fn put_bool() {
+    let mut buffer = [0u8; 64];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    message.put_bool(BlobAttrId::NO_REPLY, true).unwrap();
+    message.put_u32(BlobAttrId::OBJID, 0x13333337).unwrap();
+    message.put_bool(BlobAttrId::ACTIVE, false).unwrap();
+    let bytes = message.finish();
+    assert_eq!(
+        &bytes[UbusMsgHeader::SIZE..],
+        &[
+            0x00, 0x00, 0x00, 0x1c, 0x0a, 0x00, 0x00, 0x05, 0x01, 0x00, 0x00, 0x00, 0x03, 0x00,
+            0x00, 0x08, 0x13, 0x33, 0x33, 0x37, 0x09, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00,
+        ]
+    );
+    let blob = Blob::from_bytes(&bytes[UbusMsgHeader::SIZE..]).unwrap();
+    assert_eq!(blob.get_bool(BlobAttrId::NO_REPLY), Some(true));
+    assert_eq!(blob.get_bool(BlobAttrId::ACTIVE), Some(false));
+    assert_eq!(blob.get_bool(BlobAttrId::OBJID), None);
+}
+
+#[test]
+fn put_bytes() {
+    let mut buffer = [0u8; 64];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    message.put_bytes(BlobAttrId::DATA, &[]).unwrap();
+    message.put_bytes(BlobAttrId::DATA, &[1, 2, 3]).unwrap();
+    let bytes = message.finish();
+    assert_eq!(
+        &bytes[UbusMsgHeader::SIZE..],
+        &[
+            0x00, 0x00, 0x00, 0x10, 0x07, 0x00, 0x00, 0x04, 0x07, 0x00, 0x00, 0x07, 0x01, 0x02,
+            0x03, 0x00
+        ]
+    );
+
+    let blob = Blob::from_bytes(&bytes[UbusMsgHeader::SIZE..]).unwrap();
+    let mut attrs = BlobIter::<Blob>::new(blob.data);
+    let empty = attrs.next().unwrap();
+    assert_eq!(empty.tag.inner_len(), 0);
+    assert!(empty.data.is_empty());
+    assert_eq!(attrs.next().unwrap().data, &[1, 2, 3]);
+}
+
+#[test]
+fn put_bytes_overflow() {
+    let mut buffer = [0u8; UbusMsgHeader::SIZE + BlobTag::SIZE + 2];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    assert!(message.put_bytes(BlobAttrId::DATA, &[]).is_err());
+}
+
+fn blobmsgs(data: &[u8]) -> Vec<BlobMsg<'_>> {
+    BlobIter::<Blob>::new(data)
+        .map(|blob| blob.try_into().unwrap())
+        .collect()
+}
+
+#[test]
+fn open_table() {
+    let mut buffer = [0u8; 256];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    message.put_u32(BlobAttrId::OBJID, 0x13333337).unwrap();
+    let mut table = message.open_table(BlobAttrId::DATA).unwrap();
+    table.put_str("name", "eth0").unwrap();
+    let mut nested = table.open_table("opts").unwrap();
+    nested.put_u32("mtu", 1500).unwrap();
+    let mut inner = nested.open_table("flags").unwrap();
+    inner.put_bool("up", true).unwrap();
+    inner.close().unwrap();
+    nested.close().unwrap();
+    table.put_i64("rx", -2).unwrap();
+    table.close().unwrap();
+    message.put_str(BlobAttrId::METHOD, "status").unwrap();
+    let bytes = message.finish();
+
+    let blob = Blob::from_bytes(&bytes[UbusMsgHeader::SIZE..]).unwrap();
+    assert_eq!(blob.tag.size(), bytes.len() - UbusMsgHeader::SIZE);
+    let attrs: Vec<Blob> = BlobIter::new(blob.data).collect();
+    assert_eq!(attrs.len(), 3);
+    assert_eq!(attrs[1].tag.id(), BlobAttrId::DATA.value());
+    assert_eq!(attrs[2].data, b"status\0");
+
+    let entries = blobmsgs(attrs[1].data);
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0].name, "name");
+    assert!(matches!(entries[0].data, BlobMsgPayload::String("eth0")));
+    assert_eq!(entries[1].name, "opts");
+    let BlobMsgPayload::Table(opts) = &entries[1].data else {
+        panic!("opts is not a table");
+    };
+    assert!(matches!(opts["mtu"], BlobMsgPayload::Int32(1500)));
+    let BlobMsgPayload::Table(flags) = &opts["flags"] else {
+        panic!("flags is not a table");
+    };
+    assert_eq!(flags.len(), 1);
+    assert_eq!(entries[2].name, "rx");
+    assert!(matches!(entries[2].data, BlobMsgPayload::Int64(-2)));
+}
+
+#[test]
+fn put_nested() {
+    let mut buffer = [0u8; 256];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    message.put_u32(BlobAttrId::OBJID, 0x13333337).unwrap();
+    message
+        .put_nested(BlobAttrId::DATA, |table| {
+            table.put_str("name", "eth0")?;
+            table.put_nested("opts", |nested| {
+                nested.put_u32("mtu", 1500)?;
+                nested.put_nested("flags", |inner| inner.put_bool("up", true))
+            })?;
+            table.put_i64("rx", -2)
+        })
+        .unwrap();
+    message.put_str(BlobAttrId::METHOD, "status").unwrap();
+    let nested = message.finish().to_vec();
+
+    let mut buffer = [0u8; 256];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    message.put_u32(BlobAttrId::OBJID, 0x13333337).unwrap();
+    let mut table = message.open_table(BlobAttrId::DATA).unwrap();
+    table.put_str("name", "eth0").unwrap();
+    let mut opts = table.open_table("opts").unwrap();
+    opts.put_u32("mtu", 1500).unwrap();
+    let mut flags = opts.open_table("flags").unwrap();
+    flags.put_bool("up", true).unwrap();
+    flags.close().unwrap();
+    opts.close().unwrap();
+    table.put_i64("rx", -2).unwrap();
+    table.close().unwrap();
+    message.put_str(BlobAttrId::METHOD, "status").unwrap();
+    assert_eq!(nested, message.finish());
+}
+
+#[test]
+fn table_matches_blobmsg_builder() {
+    let mut buffer = [0u8; 64];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    let mut table = message.open_table(BlobAttrId::DATA).unwrap();
+    table.put_str("name", "eth0").unwrap();
+    table.close().unwrap();
+    let bytes = message.finish();
+
+    let mut expected = BlobMsgBuilder::new_extended(BlobMsgType::STRING.value(), "name");
+    expected.push_str("eth0").unwrap();
+    let offset = UbusMsgHeader::SIZE + 2 * BlobTag::SIZE;
+    assert_eq!(&bytes[offset..], expected.data());
+}
+
+#[test]
+fn open_array() {
+    let mut buffer = [0u8; 128];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    let mut table = message.open_table(BlobAttrId::DATA).unwrap();
+    let mut array = table.open_array("interfaces").unwrap();
+    array.push_str("lan").unwrap();
+    array.push_str("wan").unwrap();
+    array.close().unwrap();
+    table.close().unwrap();
+    let bytes = message.finish();
+
+    let blob = Blob::from_bytes(&bytes[UbusMsgHeader::SIZE..]).unwrap();
+    let data = BlobIter::<Blob>::new(blob.data).next().unwrap();
+    let entries = blobmsgs(data.data);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].name, "interfaces");
+    let BlobMsgPayload::Array(elements) = &entries[0].data else {
+        panic!("interfaces is not an array");
+    };
+    assert_eq!(elements.len(), 2);
+    assert_eq!(elements[0].name, "");
+    assert!(matches!(elements[0].data, BlobMsgPayload::String("lan")));
+    assert_eq!(elements[1].name, "");
+    assert!(matches!(elements[1].data, BlobMsgPayload::String("wan")));
+}
+
+#[test]
+fn overflow_is_clean() {
+    // "abc" needs a tag plus four payload bytes; leave room for one byte less
+    let mut buffer = [0u8; UbusMsgHeader::SIZE + BlobTag::SIZE + 8 + 7];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    message.put_u32(BlobAttrId::OBJID, 1).unwrap();
+    assert!(message.put_str(BlobAttrId::METHOD, "abc").is_err());
+    // Payload fits but the padding after it doesn't
+    assert!(message.put_bool(BlobAttrId::NO_REPLY, true).is_err());
+    let mut table = message.open_table(BlobAttrId::DATA).unwrap();
+    assert!(table.put_str("", "").is_err());
+    table.close().unwrap();
+    // A failed write leaves the builder usable
+    assert_eq!(
+        message.finish().len(),
+        UbusMsgHeader::SIZE + BlobTag::SIZE + 8 + 4
+    );
+
+    let mut small = [0u8; UbusMsgHeader::SIZE + BlobTag::SIZE - 1];
+    assert!(UbusMsgBuilder::new(&mut small, &header()).is_err());
+}
+
+#[test]
+fn attributes_are_aligned() {
+    // Start from a dirty buffer so stale bytes would show up as bad padding
+    let mut buffer = [0xffu8; 64];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    message.put_bool(BlobAttrId::NO_REPLY, true).unwrap();
+    message.put_u32(BlobAttrId::OBJID, 2).unwrap();
+    message.put_str(BlobAttrId::METHOD, "ab").unwrap();
+    message.put_u32(BlobAttrId::TARGET, 3).unwrap();
+    let bytes = message.finish();
+    assert_eq!(bytes.len(), UbusMsgHeader::SIZE + 4 + 8 + 8 + 8 + 8);
+
+    let blob = Blob::from_bytes(&bytes[UbusMsgHeader::SIZE..]).unwrap();
+    let base = blob.data.as_ptr() as usize;
+    let offsets: Vec<usize> = BlobIter::<Blob>::new(blob.data)
+        .map(|attr| attr.data.as_ptr() as usize - base)
+        .collect();
+    // Payload offsets, each just after a 4-byte tag on an aligned boundary
+    assert_eq!(offsets, [4, 12, 20, 28]);
+    assert_eq!(&blob.data[5..8], &[0, 0, 0]);
+    assert_eq!(&blob.data[23..24], &[0]);
+}
+
+#[test]
+fn put_signature() {
+    let mut buffer = [0u8; 128];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    message.put_str(BlobAttrId::OBJPATH, "test").unwrap();
+    message
+        .put_signature(&[("hello", &[("name", BlobMsgType::STRING)])])
+        .unwrap();
+    assert_eq!(
+        &message.finish()[8..],
+        &[
+            0x00, 0x00, 0x00, 0x30, // outer blob
+            0x02, 0x00, 0x00, 0x09, b't', b'e', b's', b't', 0x00, 0x00, 0x00, 0x00, // OBJPATH
+            0x06, 0x00, 0x00, 0x20, // SIGNATURE
+            // "hello" table
+            0x82, 0x00, 0x00, 0x1c, 0x00, 0x05, b'h', b'e', b'l', b'l', b'o', 0x00,
+            // "name" int32 holding BLOBMSG_TYPE_STRING
+            0x85, 0x00, 0x00, 0x10, 0x00, 0x04, b'n', b'a', b'm', b'e', 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x03,
+        ]
+    );
+}
+
+#[test]
+fn reply_in_place() {
+    let mut buffer = [0u8; 64];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    message.put_u32(BlobAttrId::OBJID, 0x13333337).unwrap();
+    let len = message.finish().len();
+
+    let mut reply = UbusMsgBuilder::reply_in_place(&mut buffer, UbusCmdType::DATA).unwrap();
+    reply.put_str(BlobAttrId::METHOD, "info").unwrap();
+    let reply = reply.finish().to_vec();
+    assert!(reply.len() > len);
+
+    let mut expected = [0u8; 64];
+    let mut message = UbusMsgBuilder::new(
+        &mut expected,
+        &UbusMsgHeader {
+            cmd_type: UbusCmdType::DATA,
+            ..header()
+        },
+    )
+    .unwrap();
+    message.put_u32(BlobAttrId::OBJID, 0x13333337).unwrap();
+    message.put_str(BlobAttrId::METHOD, "info").unwrap();
+    assert_eq!(reply, message.finish());
+}
+
+#[test]
+fn chained() -> Result<(), UbusError> {
+    let mut buffer = [0u8; 128];
+    let chained = UbusMsgBuilder::new(&mut buffer, &header())?
+        .with_u32(BlobAttrId::OBJID, 0x13333337)?
+        .with_str(BlobAttrId::METHOD, "status")?
+        .with_nested(BlobAttrId::DATA, |data| data.put_bool("up", true))?
+        .with(UbusMsgAttr::NoReply(true))?
+        .finish()
+        .to_vec();
+
+    let mut buffer = [0u8; 128];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header())?;
+    message.put_u32(BlobAttrId::OBJID, 0x13333337)?;
+    message.put_str(BlobAttrId::METHOD, "status")?;
+    message.put_nested(BlobAttrId::DATA, |data| data.put_bool("up", true))?;
+    message.put_bool(BlobAttrId::NO_REPLY, true)?;
+    assert_eq!(chained, message.finish());
+
+    let mut buffer = [0u8; 16];
+    let full = UbusMsgBuilder::new(&mut buffer, &header())?.with_str(BlobAttrId::METHOD, "status");
+    assert!(full.is_err());
+    Ok(())
+}
+
+#[test]
+fn stack_builder() {
+    let mut message = UbusStackMsgBuilder::<128>::new(&header()).unwrap();
+    message
+        .put_u32(BlobAttrId::OBJID, 0x13333337)
+        .unwrap()
+        .put_str(BlobAttrId::METHOD, "status")
+        .unwrap()
+        .build(|message| message.put_nested(BlobAttrId::DATA, |data| data.put_bool("up", true)))
+        .unwrap();
+    let stacked = message.finish().to_vec();
+
+    let mut buffer = [0u8; 128];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    message.put_u32(BlobAttrId::OBJID, 0x13333337).unwrap();
+    message.put_str(BlobAttrId::METHOD, "status").unwrap();
+    message
+        .put_nested(BlobAttrId::DATA, |data| data.put_bool("up", true))
+        .unwrap();
+    assert_eq!(stacked, message.finish());
+
+    let mut small = UbusStackMsgBuilder::<16>::new(&header()).unwrap();
+    assert!(small.put_str(BlobAttrId::METHOD, "status").is_err());
+    assert_eq!(small.finish().len(), 12);
+    assert!(UbusStackMsgBuilder::<8>::new(&header()).is_err());
+}
+
+#[test]
+fn user_and_group() {
+    let mut buffer = [0u8; 64];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    message.put_user("root").unwrap();
+    message.put_group("wheel").unwrap();
+    let message = UbusMsg::from_bytes(message.finish()).unwrap();
+    assert_eq!(message.user(), Some("root"));
+    assert_eq!(message.group(), Some("wheel"));
+    assert_eq!(message.blob.get_str(BlobAttrId::USER), Some("root"));
+
+    let attrs: Vec<UbusMsgAttr> = BlobIter::new(message.blob.data).collect();
+    assert!(matches!(
+        attrs[..],
+        [UbusMsgAttr::User("root"), UbusMsgAttr::Group("wheel")]
+    ));
+
+    let mut buffer = [0u8; 64];
+    let message = UbusMsgBuilder::new(&mut buffer, &header())
+        .unwrap()
+        .finish();
+    let message = UbusMsg::from_bytes(message).unwrap();
+    assert_eq!(message.user(), None);
+    assert_eq!(message.group(), None);
+}
+
+#[test]
+fn target() {
+    let mut buffer = [0u8; 64];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    message.put_u32(BlobAttrId::OBJID, 0x2468).unwrap();
+    message.put_target(0x1357).unwrap();
+    let message = UbusMsg::from_bytes(message.finish()).unwrap();
+    assert_eq!(message.target(), Some(0x1357));
+    assert_eq!(message.blob.get_u32(BlobAttrId::TARGET), Some(0x1357));
+
+    let attrs: Vec<UbusMsgAttr> = BlobIter::new(message.blob.data).collect();
+    assert!(matches!(
+        attrs[..],
+        [UbusMsgAttr::ObjId(0x2468), UbusMsgAttr::Target(0x1357)]
+    ));
+}
+
+#[test]
+fn table_is_empty() {
+    let mut buffer = [0u8; 64];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    let mut data = message.open_table(BlobAttrId::DATA).unwrap();
+    assert!(data.is_empty());
+    let mut nested = data.open_table("nested").unwrap();
+    assert!(nested.is_empty());
+    nested.put_u32("mtu", 1500).unwrap();
+    assert!(!nested.is_empty());
+    nested.close().unwrap();
+    assert!(!data.is_empty());
+    data.close().unwrap();
+}
@@ -0,0 +1,1187 @@
+#![cfg(all(feature = "std", feature = "mock"))]
+use std::os::unix::net::UnixStream;
+use ubus::*;
+
+const HELLO: [u8; 12] = [
+    0x00, 0x00, 0x00, 0x00, 0x13, 0x33, 0x33, 0x37, 0x00, 0x00, 0x00, 0x04,
+];
+
+#[test]
+fn request_increments_sequence() {
+    let io = MockIo::new(HELLO.to_vec());
+    let mut connection = Connection::new(io).unwrap();
+
+    for expected in 1..=2u16 {
+        let header = connection
+            .request(UbusCmdType::INVOKE, 0xabcd, |message| {
+                message.put_u32(BlobAttrId::OBJID, 0xabcd)
+            })
+            .unwrap();
+        assert_eq!(u16::from(header.sequence), expected);
+        assert_eq!(u32::from(header.peer), 0xabcd);
+        assert_eq!(header.cmd_type, UbusCmdType::INVOKE);
+    }
+}
+
+#[test]
+fn request_sends_built_message() {
+    let (client, server) = UnixStream::pair().unwrap();
+    let mut server = &server;
+    IO::put(&mut server, &HELLO).unwrap();
+
+    let mut connection = Connection::new(client).unwrap();
+    connection
+        .request(UbusCmdType::LOOKUP, 0, |message| {
+            message.put_str(BlobAttrId::OBJPATH, "system")
+        })
+        .unwrap();
+
+    let mut buffer = [0u8; 64];
+    let message = UbusMsg::from_io(&mut server, &mut buffer).unwrap();
+    assert_eq!(message.header.cmd_type, UbusCmdType::LOOKUP);
+    assert_eq!(u16::from(message.header.sequence), 1);
+    assert_eq!(message.blob.get_str(BlobAttrId::OBJPATH), Some("system"));
+}
+
+#[test]
+fn request_build_error_is_returned() {
+    let io = MockIo::new(HELLO.to_vec());
+    let mut connection = Connection::new(io).unwrap();
+    let result = connection.request(UbusCmdType::INVOKE, 0, |message| {
+        message.put_bytes(BlobAttrId::DATA, &[0; 2048])
+    });
+    assert!(matches!(result, Err(UbusError::InvalidData(_))));
+}
+
+#[test]
+fn hello_assigns_peer() {
+    let connection = Connection::new(MockIo::new(HELLO.to_vec())).unwrap();
+    assert_eq!(connection.peer(), PeerId(0x13333337));
+    assert_eq!(connection.peer().to_string(), "peer:0x13333337");
+}
+
+#[test]
+fn first_message_must_be_hello() {
+    let mut rx = HELLO.to_vec();
+    rx[1] = UbusCmdType::STATUS.value();
+    let result = Connection::new(MockIo::new(rx));
+    assert!(matches!(result, Err(UbusError::InvalidData(_))));
+
+    let result = Connection::new(MockIo::new(Vec::new()));
+    assert!(matches!(result, Err(UbusError::Disconnected)));
+}
+
+#[test]
+fn add_object() {
+    let (client, server) = UnixStream::pair().unwrap();
+
+    std::thread::spawn(move || {
+        let mut server = &server;
+        IO::put(&mut server, &HELLO).unwrap();
+
+        let mut buffer = [0u8; 256];
+        let request = UbusMsg::from_io(&mut server, &mut buffer).unwrap();
+        assert_eq!(request.header.cmd_type, UbusCmdType::ADD_OBJECT);
+        assert_eq!(request.blob.get_str(BlobAttrId::OBJPATH), Some("test"));
+        assert!(request.blob.get(BlobAttrId::SIGNATURE).is_some());
+        let sequence = u16::from(request.header.sequence);
+
+        // ubusd answers with the new object's id and type, then a status
+        IO::put(
+            &mut server,
+            &server_message(UbusCmdType::DATA, sequence, 0, |reply| {
+                reply.put_u32(BlobAttrId::OBJID, 0x2468)?;
+                reply.put_u32(BlobAttrId::OBJTYPE, 0x1357)
+            }),
+        )
+        .unwrap();
+        IO::put(
+            &mut server,
+            &server_message(UbusCmdType::STATUS, sequence, 0, |reply| {
+                reply.put_i32(BlobAttrId::STATUS, 0)
+            }),
+        )
+        .unwrap();
+    });
+
+    let mut connection = Connection::new(client).unwrap();
+    let object = connection
+        .add_object("test", &[("hello", &[("name", BlobMsgType::STRING)])])
+        .unwrap();
+    assert_eq!(object.path, "test");
+    assert_eq!(object.id, 0x2468);
+    assert_eq!(object.ty, 0x1357);
+}
+
+#[test]
+fn remove_object() {
+    let (client, server) = UnixStream::pair().unwrap();
+
+    std::thread::spawn(move || {
+        let mut server = &server;
+        IO::put(&mut server, &HELLO).unwrap();
+
+        for status in [0, 5] {
+            let mut buffer = [0u8; 64];
+            let request = UbusMsg::from_io(&mut server, &mut buffer).unwrap();
+            assert_eq!(request.header.cmd_type, UbusCmdType::REMOVE_OBJECT);
+            assert_eq!(request.blob.get_u32(BlobAttrId::OBJID), Some(0x2468));
+            let sequence = u16::from(request.header.sequence);
+
+            // A stale reply to another request must not be taken as the ack
+            let stale = sequence + 100;
+            IO::put(
+                &mut server,
+                &server_message(UbusCmdType::STATUS, stale, 0, |reply| {
+                    reply.put_i32(BlobAttrId::STATUS, 2)
+                }),
+            )
+            .unwrap();
+            IO::put(
+                &mut server,
+                &server_message(UbusCmdType::DATA, sequence, 0, |reply| {
+                    reply.put_u32(BlobAttrId::OBJID, 0x2468)
+                }),
+            )
+            .unwrap();
+            IO::put(
+                &mut server,
+                &server_message(UbusCmdType::STATUS, sequence, 0, |reply| {
+                    reply.put_i32(BlobAttrId::STATUS, status)
+                }),
+            )
+            .unwrap();
+        }
+    });
+
+    let mut connection = Connection::new(client).unwrap();
+    connection.remove_object(0x2468).unwrap();
+    assert!(matches!(
+        connection.remove_object(0x2468),
+        Err(UbusError::Status(UbusStatus::NO_DATA))
+    ));
+}
+
+#[test]
+fn subscribe_and_unsubscribe() {
+    let (client, server) = UnixStream::pair().unwrap();
+
+    std::thread::spawn(move || {
+        let mut server = &server;
+        IO::put(&mut server, &HELLO).unwrap();
+
+        for cmd_type in [UbusCmdType::SUBSCRIBE, UbusCmdType::UNSUBSCRIBE] {
+            let mut buffer = [0u8; 64];
+            let request = UbusMsg::from_io(&mut server, &mut buffer).unwrap();
+            assert_eq!(request.header.cmd_type, cmd_type);
+            assert_eq!(u32::from(request.header.peer), 0);
+            assert_eq!(request.blob.get_u32(BlobAttrId::OBJID), Some(0x2468));
+            assert_eq!(request.blob.get_u32(BlobAttrId::TARGET), Some(0x1357));
+            let sequence = u16::from(request.header.sequence);
+            IO::put(
+                &mut server,
+                &server_message(UbusCmdType::STATUS, sequence, 0, |reply| {
+                    reply.put_i32(BlobAttrId::STATUS, 0)
+                }),
+            )
+            .unwrap();
+        }
+    });
+
+    let mut connection = Connection::new(client).unwrap();
+    connection.subscribe(0x2468, 0x1357).unwrap();
+    connection.unsubscribe(0x2468, 0x1357).unwrap();
+}
+
+#[test]
+fn notify() {
+    let (client, server) = UnixStream::pair().unwrap();
+
+    std::thread::spawn(move || {
+        let mut server = &server;
+        IO::put(&mut server, &HELLO).unwrap();
+
+        let mut buffer = [0u8; 128];
+        let request = UbusMsg::from_io(&mut server, &mut buffer).unwrap();
+        assert_eq!(request.header.cmd_type, UbusCmdType::NOTIFY);
+        assert_eq!(u32::from(request.header.peer), 0x2468);
+        let attrs = request
+            .blob
+            .iter()
+            .map(|blob| blob.tag.id().into())
+            .collect::<Vec<BlobAttrId>>();
+        assert_eq!(
+            attrs,
+            [BlobAttrId::OBJID, BlobAttrId::METHOD, BlobAttrId::DATA]
+        );
+        assert_eq!(request.blob.get_str(BlobAttrId::METHOD), Some("link.up"));
+        let data = request.blob.get(BlobAttrId::DATA).unwrap();
+        let msgs: Vec<BlobMsg> = data.iter().map(|blob| blob.try_into().unwrap()).collect();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].name, "ifname");
+        assert!(matches!(msgs[0].data, BlobMsgPayload::String("eth0")));
+
+        // ubusd lists the subscribers it forwarded the event to
+        let sequence = u16::from(request.header.sequence);
+        IO::put(
+            &mut server,
+            &server_message(UbusCmdType::STATUS, sequence, 0x2468, |reply| {
+                reply.put_i32(BlobAttrId::STATUS, 0)?;
+                let mut subscribers = reply.open_array(BlobAttrId::SUBSCRIBERS)?;
+                subscribers.push_u32(0x1111)?;
+                subscribers.push_u32(0x2222)?;
+                subscribers.close()
+            }),
+        )
+        .unwrap();
+    });
+
+    let mut data = BlobMsgBuilder::new_extended(BlobMsgType::STRING.value(), "ifname");
+    data.push_str("eth0").unwrap();
+
+    let mut connection = Connection::new(client).unwrap();
+    let subscribers = connection.notify(0x2468, "link.up", data.data()).unwrap();
+    assert_eq!(subscribers, 2);
+}
+
+#[test]
+fn ping_is_answered() {
+    let (client, server) = UnixStream::pair().unwrap();
+
+    std::thread::spawn(move || {
+        let mut server = &server;
+        IO::put(&mut server, &HELLO).unwrap();
+        IO::put(
+            &mut server,
+            &server_message(UbusCmdType::PING, 42, 0x13333337, |_| Ok(())),
+        )
+        .unwrap();
+        IO::put(
+            &mut server,
+            &server_message(UbusCmdType::STATUS, 1, 0, |reply| {
+                reply.put_i32(BlobAttrId::STATUS, 0)
+            }),
+        )
+        .unwrap();
+
+        let mut pong = [0u8; 12];
+        IO::get(&mut server, &mut pong).unwrap();
+        assert_eq!(
+            pong,
+            [0x00, 0x02, 0x00, 0x2a, 0x13, 0x33, 0x33, 0x37, 0x00, 0x00, 0x00, 0x04]
+        );
+    });
+
+    let mut connection = Connection::new(client).unwrap();
+    let message = connection.next_message().unwrap();
+    assert_eq!(message.header.cmd_type, UbusCmdType::STATUS);
+    assert_eq!(message.blob.get_i32(BlobAttrId::STATUS), Some(0));
+}
+
+#[test]
+fn raw_messages_include_pings() {
+    let mut rx = HELLO.to_vec();
+    rx.extend_from_slice(&[
+        0x00, 0x03, 0x00, 0x2a, 0x13, 0x33, 0x33, 0x37, 0x00, 0x00, 0x00, 0x04,
+    ]);
+    let mut connection = Connection::new(MockIo::new(rx)).unwrap();
+    let message = connection.next_raw_message().unwrap();
+    assert_eq!(message.header.cmd_type, UbusCmdType::PING);
+}
+
+#[test]
+fn replies_are_matched_by_sequence() {
+    let (client, server) = UnixStream::pair().unwrap();
+
+    std::thread::spawn(move || {
+        let mut server = &server;
+        IO::put(&mut server, &HELLO).unwrap();
+
+        let mut sequences = Vec::new();
+        for _ in 0..2 {
+            let mut buffer = [0u8; 128];
+            let request = UbusMsg::from_io(&mut server, &mut buffer).unwrap();
+            sequences.push(u16::from(request.header.sequence));
+        }
+
+        // Answer the second request first, interleaving the two replies
+        for &(index, value) in &[(1, 20), (0, 10), (1, 21)] {
+            IO::put(
+                &mut server,
+                &server_message(UbusCmdType::DATA, sequences[index], 0x2468, |reply| {
+                    reply.put_u32(BlobAttrId::OBJID, value)
+                }),
+            )
+            .unwrap();
+        }
+        for (index, status) in [(1, 0), (0, 4)] {
+            IO::put(
+                &mut server,
+                &server_message(UbusCmdType::STATUS, sequences[index], 0x2468, |reply| {
+                    reply.put_i32(BlobAttrId::STATUS, status)
+                }),
+            )
+            .unwrap();
+        }
+    });
+
+    let mut connection = Connection::new(client).unwrap();
+    let first = connection.invoke_request(0x2468, "first", &[]).unwrap();
+    let second = connection.invoke_request(0x2468, "second", &[]).unwrap();
+
+    let mut replies = |request: &UbusMsgHeader| {
+        let mut values = Vec::new();
+        loop {
+            let message = connection.next_reply(request).unwrap();
+            assert_eq!(message.header.sequence, request.sequence);
+            if message.header.cmd_type == UbusCmdType::STATUS {
+                return (values, message.status().unwrap());
+            }
+            values.push(message.blob.get_u32(BlobAttrId::OBJID).unwrap());
+        }
+    };
+    assert_eq!(replies(&first), (vec![10], UbusStatus::NOT_FOUND));
+    assert_eq!(replies(&second), (vec![20, 21], UbusStatus::OK));
+}
+
+#[test]
+fn reply_larger_than_64k() {
+    let header = UbusMsgHeader::new(UbusCmdType::DATA, 1, 0x2468);
+    let mut buffer = vec![0u8; 128 * 1024];
+    let mut big = UbusMsgBuilder::new(&mut buffer, &header).unwrap();
+    big.put_bytes(BlobAttrId::DATA, &[0xaa; 100 * 1024])
+        .unwrap();
+    let rx = [
+        HELLO.to_vec(),
+        big.finish().to_vec(),
+        server_message(UbusCmdType::STATUS, 1, 0x2468, |reply| {
+            reply.put_i32(BlobAttrId::STATUS, 0)
+        }),
+    ];
+    let mut connection = Connection::new(MockIo::new(rx.concat())).unwrap();
+    let request = connection
+        .request(UbusCmdType::INVOKE, 0x2468, |_| Ok(()))
+        .unwrap();
+
+    let data = connection.next_reply(&request).unwrap();
+    assert_eq!(data.header.cmd_type, UbusCmdType::DATA);
+    assert_eq!(
+        data.blob.get(BlobAttrId::DATA).unwrap().data.len(),
+        100 * 1024
+    );
+    let status = connection.next_reply(&request).unwrap();
+    assert_eq!(status.status().unwrap(), UbusStatus::OK);
+}
+
+#[test]
+fn invokes_wait_while_replies_are_read() {
+    let rx = [
+        HELLO.to_vec(),
+        // A call to one of our objects that happens to share the request's sequence number
+        server_message(UbusCmdType::INVOKE, 1, 0x1357, |message| {
+            message.put_u32(BlobAttrId::OBJID, 0x1111)?;
+            message.put_str(BlobAttrId::METHOD, "hello")
+        }),
+        // Right sequence number, wrong peer: not a reply to this request
+        server_message(UbusCmdType::STATUS, 1, 0x9999, |reply| {
+            reply.put_i32(BlobAttrId::STATUS, 2)
+        }),
+        server_message(UbusCmdType::STATUS, 1, 0x2468, |reply| {
+            reply.put_i32(BlobAttrId::STATUS, 0)
+        }),
+    ];
+    let mut connection = Connection::new(MockIo::new(rx.concat())).unwrap();
+    let request = connection
+        .request(UbusCmdType::INVOKE, 0x2468, |_| Ok(()))
+        .unwrap();
+
+    let status = connection.next_reply(&request).unwrap();
+    assert_eq!(status.header.peer_id(), PeerId(0x2468));
+    assert_eq!(status.status().unwrap(), UbusStatus::OK);
+
+    let invoke = connection.next_message().unwrap();
+    assert_eq!(invoke.header.cmd_type, UbusCmdType::INVOKE);
+    assert_eq!(invoke.blob.get_str(BlobAttrId::METHOD), Some("hello"));
+    assert!(matches!(
+        connection.next_message(),
+        Err(UbusError::Disconnected)
+    ));
+}
+
+/// Build a message as ubusd would send it. Replies carry the sequence number and peer of
+/// their request.
+fn server_message(
+    cmd_type: UbusCmdType,
+    sequence: u16,
+    peer: u32,
+    build: impl FnOnce(&mut UbusMsgBuilder) -> Result<(), UbusError>,
+) -> Vec<u8> {
+    let header = UbusMsgHeader {
+        version: UbusMsgVersion::CURRENT,
+        cmd_type,
+        sequence: sequence.into(),
+        peer: peer.into(),
+    };
+    let mut buffer = [0u8; 256];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header).unwrap();
+    build(&mut message).unwrap();
+    message.finish().to_vec()
+}
+
+#[test]
+fn run_subscriptions() {
+    let mut rx = HELLO.to_vec();
+    rx.extend(server_message(UbusCmdType::INVOKE, 7, 0x1357, |notify| {
+        notify.put_u32(BlobAttrId::OBJID, 0x2468)?;
+        notify.put_str(BlobAttrId::METHOD, "link.up")?;
+        notify.put_nested(BlobAttrId::DATA, |data| data.put_str("ifname", "eth0"))
+    }));
+    rx.extend(server_message(UbusCmdType::PING, 8, 0, |_| Ok(())));
+    rx.extend(server_message(UbusCmdType::INVOKE, 9, 0x1357, |notify| {
+        notify.put_u32(BlobAttrId::OBJID, 0x2468)?;
+        notify.put_str(BlobAttrId::METHOD, "link.down")?;
+        notify.put_bool(BlobAttrId::NO_REPLY, true)
+    }));
+    let mut io = MockIo::new(rx);
+    let mut connection = Connection::new(&mut io).unwrap();
+
+    let mut events = Vec::new();
+    let result = connection.run_subscriptions(|event, data| {
+        events.push((event.to_string(), data.get_msg("ifname").is_some()))
+    });
+    // The loop only ends once the mock runs dry, which looks like ubusd going away
+    assert!(matches!(result, Err(UbusError::Disconnected)));
+    assert_eq!(
+        events,
+        [
+            ("link.up".to_string(), true),
+            ("link.down".to_string(), false)
+        ]
+    );
+
+    drop(connection);
+    // A STATUS for the first event, a pong, and nothing for the NO_REPLY event
+    let tx = io.written();
+    let status = UbusMsg::from_bytes(tx).unwrap();
+    assert_eq!(status.header.cmd_type, UbusCmdType::STATUS);
+    assert_eq!(u16::from(status.header.sequence), 7);
+    assert_eq!(status.blob.get_i32(BlobAttrId::STATUS), Some(0));
+    assert_eq!(status.blob.get_u32(BlobAttrId::OBJID), Some(0x2468));
+    let status_len = UbusMsgHeader::SIZE + status.blob.tag.size();
+    let pong = UbusMsg::from_bytes(&tx[status_len..]).unwrap();
+    assert_eq!(pong.header.cmd_type, UbusCmdType::DATA);
+    assert_eq!(u16::from(pong.header.sequence), 8);
+    assert_eq!(tx.len(), status_len + 12);
+}
+
+#[test]
+fn reconnect() {
+    // The first transport registers the object and subscribes; after ubusd "restarts" the
+    // object gets a new id and the subscription target is gone
+    let first = [
+        HELLO.to_vec(),
+        server_message(UbusCmdType::DATA, 1, 0, |reply| {
+            reply.put_u32(BlobAttrId::OBJID, 0x1111)?;
+            reply.put_u32(BlobAttrId::OBJTYPE, 0x2222)
+        }),
+        server_message(UbusCmdType::STATUS, 1, 0, |reply| {
+            reply.put_i32(BlobAttrId::STATUS, 0)
+        }),
+        server_message(UbusCmdType::STATUS, 2, 0, |reply| {
+            reply.put_i32(BlobAttrId::STATUS, 0)
+        }),
+    ];
+    let second = [
+        HELLO.to_vec(),
+        server_message(UbusCmdType::DATA, 3, 0, |reply| {
+            reply.put_u32(BlobAttrId::OBJID, 0x3333)?;
+            reply.put_u32(BlobAttrId::OBJTYPE, 0x2222)
+        }),
+        server_message(UbusCmdType::STATUS, 3, 0, |reply| {
+            reply.put_i32(BlobAttrId::STATUS, 0)
+        }),
+        server_message(UbusCmdType::STATUS, 4, 0, |reply| {
+            reply.put_i32(BlobAttrId::STATUS, UbusStatus::NOT_FOUND.value() as i32)
+        }),
+    ];
+    let transports = std::sync::Mutex::new(vec![second.concat(), first.concat()]);
+    let mut connection = Connection::with_connector(move || {
+        let rx = transports.lock().unwrap().pop().unwrap();
+        Ok(MockIo::new(rx))
+    })
+    .unwrap();
+
+    let object = connection
+        .add_object("test", &[("hello", &[("name", BlobMsgType::STRING)])])
+        .unwrap();
+    assert_eq!(object.id, 0x1111);
+    connection.subscribe(object.id, 0x5555).unwrap();
+
+    let lost = connection.reconnect().unwrap();
+    assert_eq!(lost, [(0x3333, 0x5555)]);
+    let objects: Vec<_> = connection.objects().collect();
+    assert_eq!(objects.len(), 1);
+    assert_eq!(objects[0].path, "test");
+    assert_eq!(objects[0].id, 0x3333);
+}
+
+#[test]
+fn reconnect_keeps_objects_on_failure() {
+    let registered = |sequence, id| {
+        [
+            server_message(UbusCmdType::DATA, sequence, 0, |reply| {
+                reply.put_u32(BlobAttrId::OBJID, id)?;
+                reply.put_u32(BlobAttrId::OBJTYPE, 0x2222)
+            }),
+            server_message(UbusCmdType::STATUS, sequence, 0, |reply| {
+                reply.put_i32(BlobAttrId::STATUS, 0)
+            }),
+        ]
+        .concat()
+    };
+    let first = [HELLO.to_vec(), registered(1, 0x1111), registered(2, 0x2222)];
+    // The first object is registered again but ubusd refuses the second
+    let second = [
+        HELLO.to_vec(),
+        registered(3, 0x3333),
+        server_message(UbusCmdType::STATUS, 4, 0, |reply| {
+            reply.put_i32(
+                BlobAttrId::STATUS,
+                UbusStatus::PERMISSION_DENIED.value() as i32,
+            )
+        }),
+    ];
+    let third = [HELLO.to_vec(), registered(5, 0x5555), registered(6, 0x6666)];
+    let transports = std::sync::Mutex::new(vec![third.concat(), second.concat(), first.concat()]);
+    let mut connection = Connection::with_connector(move || {
+        let rx = transports.lock().unwrap().pop().unwrap();
+        Ok(MockIo::new(rx))
+    })
+    .unwrap();
+    connection.add_object("a", &[]).unwrap();
+    connection.add_object("b", &[]).unwrap();
+
+    assert!(matches!(
+        connection.reconnect(),
+        Err(UbusError::Status(UbusStatus::PERMISSION_DENIED))
+    ));
+    let objects: Vec<_> = connection.objects().map(|o| (o.path, o.id)).collect();
+    assert_eq!(objects, [("a", 0x3333), ("b", 0x2222)]);
+
+    // Nothing was lost, so the next attempt registers both
+    assert!(connection.reconnect().unwrap().is_empty());
+    let objects: Vec<_> = connection.objects().map(|o| (o.path, o.id)).collect();
+    assert_eq!(objects, [("a", 0x5555), ("b", 0x6666)]);
+}
+
+#[test]
+fn reconnect_keeps_subscriptions_on_failure() {
+    let status = |sequence, status: UbusStatus| {
+        server_message(UbusCmdType::STATUS, sequence, 0, |reply| {
+            reply.put_i32(BlobAttrId::STATUS, status.value() as i32)
+        })
+    };
+    let first = [
+        HELLO.to_vec(),
+        status(1, UbusStatus::OK),
+        status(2, UbusStatus::OK),
+    ];
+    // ubusd goes away again after renewing the first subscription, before answering 4
+    let second = [HELLO.to_vec(), status(3, UbusStatus::OK)];
+    let third = [
+        HELLO.to_vec(),
+        status(5, UbusStatus::OK),
+        status(6, UbusStatus::NOT_FOUND),
+    ];
+    let transports = std::sync::Mutex::new(vec![third.concat(), second.concat(), first.concat()]);
+    let mut connection = Connection::with_connector(move || {
+        let rx = transports.lock().unwrap().pop().unwrap();
+        Ok(MockIo::new(rx))
+    })
+    .unwrap();
+    connection.subscribe(0x1111, 0x5555).unwrap();
+    connection.subscribe(0x1111, 0x6666).unwrap();
+
+    assert!(matches!(
+        connection.reconnect(),
+        Err(UbusError::Disconnected)
+    ));
+    // Both are renewed again, so the second one's target being gone shows up now
+    assert_eq!(connection.reconnect().unwrap(), [(0x1111, 0x6666)]);
+}
+
+#[test]
+fn reconnect_needs_connector() {
+    let mut connection = Connection::new(MockIo::new(HELLO.to_vec())).unwrap();
+    assert!(matches!(
+        connection.reconnect(),
+        Err(UbusError::InvalidData(_))
+    ));
+}
+
+#[test]
+fn invoke_no_reply() {
+    let (client, server) = UnixStream::pair().unwrap();
+    let mut server = &server;
+    IO::put(&mut server, &HELLO).unwrap();
+
+    // Nothing is ever sent back, so this would block if it waited
+    let mut connection = Connection::new(client).unwrap();
+    connection.invoke_no_reply(0x2468, "reload", &[]).unwrap();
+
+    let mut buffer = [0u8; 64];
+    let request = UbusMsg::from_io(&mut server, &mut buffer).unwrap();
+    assert_eq!(request.header.cmd_type, UbusCmdType::INVOKE);
+    assert_eq!(request.blob.get_u32(BlobAttrId::OBJID), Some(0x2468));
+    assert_eq!(request.blob.get_str(BlobAttrId::METHOD), Some("reload"));
+    assert_eq!(request.blob.get_bool(BlobAttrId::NO_REPLY), Some(true));
+}
+
+#[test]
+fn active_tracks_subscribers() {
+    let notify = |active| {
+        server_message(UbusCmdType::NOTIFY, 0, 0, |message| {
+            message.put_u32(BlobAttrId::OBJID, 0x1111)?;
+            message.put_bool(BlobAttrId::ACTIVE, active)
+        })
+    };
+    let rx = [
+        HELLO.to_vec(),
+        server_message(UbusCmdType::DATA, 1, 0, |reply| {
+            reply.put_u32(BlobAttrId::OBJID, 0x1111)?;
+            reply.put_u32(BlobAttrId::OBJTYPE, 0x2222)
+        }),
+        server_message(UbusCmdType::STATUS, 1, 0, |reply| {
+            reply.put_i32(BlobAttrId::STATUS, 0)
+        }),
+        notify(true),
+        notify(false),
+    ];
+    let mut connection = Connection::new(MockIo::new(rx.concat())).unwrap();
+    let object = connection.add_object("test", &[]).unwrap();
+    assert!(!connection.has_subscribers(object.id));
+
+    let message = connection.next_message().unwrap();
+    assert_eq!(message.active(), Some(true));
+    assert!(connection.has_subscribers(object.id));
+    assert!(!connection.has_subscribers(0x9999));
+
+    connection.next_message().unwrap();
+    assert!(!connection.has_subscribers(object.id));
+}
+
+#[test]
+fn notify_without_subscribers() {
+    // ubusd leaves SUBSCRIBERS out when nobody is listening
+    let rx = [
+        HELLO.to_vec(),
+        server_message(UbusCmdType::STATUS, 1, 0x2468, |reply| {
+            reply.put_i32(BlobAttrId::STATUS, 0)
+        }),
+    ];
+    let mut connection = Connection::new(MockIo::new(rx.concat())).unwrap();
+    assert_eq!(connection.notify(0x2468, "link.up", &[]).unwrap(), 0);
+}
+
+#[test]
+fn run_subscriptions_from() {
+    let mut rx = HELLO.to_vec();
+    for (sequence, target) in [(1, Some(0x1111)), (2, Some(0x2222)), (3, None)] {
+        rx.extend(server_message(
+            UbusCmdType::INVOKE,
+            sequence,
+            0x1357,
+            |notify| {
+                notify.put_u32(BlobAttrId::OBJID, 0x2468)?;
+                notify.put_str(BlobAttrId::METHOD, "link.up")?;
+                if let Some(target) = target {
+                    notify.put_target(target)?;
+                }
+                notify.put_bool(BlobAttrId::NO_REPLY, true)
+            },
+        ));
+    }
+    let mut connection = Connection::new(MockIo::new(rx)).unwrap();
+
+    let mut sources = Vec::new();
+    let result = connection.run_subscriptions_from(|source, event, _data| {
+        assert_eq!(event, "link.up");
+        sources.push(source);
+    });
+    assert!(matches!(result, Err(UbusError::Disconnected)));
+    assert_eq!(sources, [0x1111, 0x2222, 0x2468]);
+}
+
+#[test]
+fn sequence_wraps_around() {
+    // ubusd answers each request below with a STATUS. 0 is never used and 1 goes to
+    // `pending`, so after the wrap the sequence numbers carry on from 2
+    let mut rx = HELLO.to_vec();
+    for sequence in (2..=u16::MAX).chain(2..).take(70000) {
+        rx.extend(server_message(
+            UbusCmdType::STATUS,
+            sequence,
+            0xabcd,
+            |reply| reply.put_i32(BlobAttrId::STATUS, 0),
+        ));
+    }
+    let mut connection = Connection::new(MockIo::new(rx)).unwrap();
+    // Never read, so its sequence number stays taken across the wrap
+    let pending = connection
+        .request(UbusCmdType::INVOKE, 0xabcd, |_| Ok(()))
+        .unwrap();
+    let pending = u16::from(pending.sequence);
+
+    let mut previous = pending;
+    for _ in 0..70000 {
+        let header = connection
+            .request(UbusCmdType::INVOKE, 0xabcd, |_| Ok(()))
+            .unwrap();
+        let sequence = u16::from(header.sequence);
+        assert_ne!(sequence, 0);
+        assert_ne!(sequence, pending);
+        assert!(sequence == previous.wrapping_add(1) || previous == u16::MAX);
+        previous = sequence;
+
+        let reply = connection.next_reply(&header).unwrap();
+        assert_eq!(reply.header.cmd_type, UbusCmdType::STATUS);
+        assert_eq!(u16::from(reply.header.sequence), sequence);
+    }
+}
+
+struct Greeter {
+    calls: usize,
+}
+
+impl Object for Greeter {
+    fn methods(&self) -> &[MethodDef<'_>] {
+        &[MethodDef {
+            name: "hello",
+            args: &[("name", BlobMsgType::STRING)],
+        }]
+    }
+
+    fn invoke(&mut self, method: &str, args: &Blob, reply: &mut TableBuilder) -> UbusStatus {
+        assert_eq!(method, "hello");
+        self.calls += 1;
+        let Some(BlobMsgPayload::String(name)) = args.get_msg("name") else {
+            return UbusStatus::INVALID_ARGUMENT;
+        };
+        match reply.put_str("message", &format!("hello {name}")) {
+            Ok(()) => UbusStatus::OK,
+            Err(_) => UbusStatus::UNKNOWN_ERROR,
+        }
+    }
+}
+
+/// Split everything written to a [`MockIo`] into messages
+fn sent(tx: &[u8]) -> Vec<UbusOwnedMsg> {
+    let mut decoder = UbusMsgDecoder::new();
+    let mut messages = Vec::new();
+    let mut next = decoder.push(tx).unwrap();
+    while let Some(message) = next {
+        messages.push(message);
+        next = decoder.next_msg().unwrap();
+    }
+    messages
+}
+
+#[test]
+fn serve() {
+    let invoke = |sequence, obj_id, method: &str, name: Option<&str>| {
+        server_message(UbusCmdType::INVOKE, sequence, 0x1357, |message| {
+            message.put_u32(BlobAttrId::OBJID, obj_id)?;
+            message.put_str(BlobAttrId::METHOD, method)?;
+            match name {
+                Some(name) => {
+                    message.put_nested(BlobAttrId::DATA, |data| data.put_str("name", name))
+                }
+                None => Ok(()),
+            }
+        })
+    };
+    let rx = [
+        HELLO.to_vec(),
+        server_message(UbusCmdType::DATA, 1, 0, |reply| {
+            reply.put_u32(BlobAttrId::OBJID, 0x1111)?;
+            reply.put_u32(BlobAttrId::OBJTYPE, 0x2222)
+        }),
+        server_message(UbusCmdType::STATUS, 1, 0, |reply| {
+            reply.put_i32(BlobAttrId::STATUS, 0)
+        }),
+        invoke(5, 0x1111, "hello", Some("world")),
+        invoke(6, 0x1111, "hello", None),
+        invoke(7, 0x1111, "goodbye", None),
+        invoke(8, 0x9999, "hello", None),
+        // No METHOD, so there is nothing to dispatch
+        server_message(UbusCmdType::INVOKE, 9, 0x1357, |message| {
+            message.put_u32(BlobAttrId::OBJID, 0x1111)
+        }),
+    ];
+    let mut io = MockIo::new(rx.concat());
+    let mut connection = Connection::new(&mut io).unwrap();
+    let mut greeter = Greeter { calls: 0 };
+    let object = connection.register_object("greeter", &greeter).unwrap();
+    assert_eq!(object.id, 0x1111);
+
+    let result = connection.serve(&mut [(object.id, &mut greeter)]);
+    assert!(matches!(result, Err(UbusError::Disconnected)));
+    assert_eq!(greeter.calls, 2);
+
+    drop(connection);
+    let sent = sent(io.written());
+    let add = sent[0].as_msg();
+    assert_eq!(add.header.cmd_type, UbusCmdType::ADD_OBJECT);
+    assert!(add.blob.get(BlobAttrId::SIGNATURE).is_some());
+
+    // A DATA reply then a STATUS, both to the caller's peer id and sequence number
+    let data = sent[1].as_msg();
+    assert_eq!(data.header.cmd_type, UbusCmdType::DATA);
+    assert_eq!(u16::from(data.header.sequence), 5);
+    assert_eq!(data.header.peer_id(), PeerId(0x1357));
+    assert_eq!(data.blob.get_u32(BlobAttrId::OBJID), Some(0x1111));
+    let greeting = data.blob.get(BlobAttrId::DATA).unwrap();
+    assert!(matches!(
+        greeting.get_msg("message"),
+        Some(BlobMsgPayload::String("hello world"))
+    ));
+
+    let statuses: Vec<_> = sent[2..]
+        .iter()
+        .map(|message| {
+            let message = message.as_msg();
+            (
+                u16::from(message.header.sequence),
+                message.status().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        statuses,
+        [
+            (5, UbusStatus::OK),
+            (6, UbusStatus::INVALID_ARGUMENT),
+            (7, UbusStatus::METHOD_NOT_FOUND),
+            (8, UbusStatus::NOT_FOUND),
+            (9, UbusStatus::INVALID_ARGUMENT),
+        ]
+    );
+    // ubusd only accepts replies that name the invoked object
+    let objects: Vec<_> = sent[2..]
+        .iter()
+        .map(|message| message.as_msg().blob.get_u32(BlobAttrId::OBJID))
+        .collect();
+    assert_eq!(
+        objects,
+        [
+            Some(0x1111),
+            Some(0x1111),
+            Some(0x1111),
+            Some(0x9999),
+            Some(0x1111)
+        ]
+    );
+}
+
+/// Never expects to be called with bad arguments
+struct Strict;
+
+impl Object for Strict {
+    fn methods(&self) -> &[MethodDef<'_>] {
+        &[MethodDef {
+            name: "hello",
+            args: &[("name", BlobMsgType::STRING), ("any", BlobMsgType::UNSPEC)],
+        }]
+    }
+
+    fn invoke(&mut self, _method: &str, args: &Blob, _reply: &mut TableBuilder) -> UbusStatus {
+        assert!(!matches!(
+            args.get_msg("name"),
+            Some(BlobMsgPayload::Int32(_))
+        ));
+        UbusStatus::OK
+    }
+
+    fn validate_args(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn serve_validates_args() {
+    let invoke = |sequence, build: fn(&mut TableBuilder) -> Result<(), UbusError>| {
+        server_message(UbusCmdType::INVOKE, sequence, 0x1357, |message| {
+            message.put_u32(BlobAttrId::OBJID, 0x1111)?;
+            message.put_str(BlobAttrId::METHOD, "hello")?;
+            message.put_nested(BlobAttrId::DATA, build)
+        })
+    };
+    let rx = [
+        HELLO.to_vec(),
+        invoke(1, |args| args.put_str("name", "world")),
+        invoke(2, |args| args.put_i32("name", 5)),
+        invoke(3, |args| {
+            args.put_i32("any", 5)?;
+            args.put_bool("extra", true)
+        }),
+    ];
+    let mut io = MockIo::new(rx.concat());
+    let mut connection = Connection::new(&mut io).unwrap();
+    let result = connection.serve(&mut [(0x1111, &mut Strict)]);
+    assert!(matches!(result, Err(UbusError::Disconnected)));
+
+    drop(connection);
+    let statuses: Vec<_> = sent(io.written())
+        .iter()
+        .map(|message| message.as_msg().status().unwrap())
+        .collect();
+    assert_eq!(
+        statuses,
+        [UbusStatus::OK, UbusStatus::INVALID_ARGUMENT, UbusStatus::OK]
+    );
+}
+
+#[test]
+fn reply_error_bytes() {
+    let mut io = MockIo::default();
+    reply_error(
+        &mut io,
+        9,
+        PeerId(0x1357),
+        0x2468,
+        UbusStatus::PERMISSION_DENIED,
+    )
+    .unwrap();
+    let tx = io.take_written();
+
+    let reply = UbusMsg::from_bytes(&tx).unwrap();
+    assert_eq!(reply.header.cmd_type, UbusCmdType::STATUS);
+    assert_eq!(u16::from(reply.header.sequence), 9);
+    assert_eq!(reply.header.peer_id(), PeerId(0x1357));
+    assert_eq!(reply.status().unwrap(), UbusStatus::PERMISSION_DENIED);
+    // STATUS then OBJID, which ubusd needs to accept the reply, and no DATA
+    assert_eq!(reply.blob.get_u32(BlobAttrId::OBJID), Some(0x2468));
+    assert_eq!(reply.blob.iter().count(), 2);
+    assert_eq!(
+        tx,
+        [
+            0x00, 0x01, 0x00, 0x09, 0x00, 0x00, 0x13, 0x57, 0x00, 0x00, 0x00, 0x14, 0x01, 0x00,
+            0x00, 0x08, 0x00, 0x00, 0x00, 0x06, 0x03, 0x00, 0x00, 0x08, 0x00, 0x00, 0x24, 0x68,
+        ]
+    );
+}
+
+/// A LOOKUP reply describing one object
+fn lookup_reply(
+    sequence: u16,
+    path: &str,
+    id: u32,
+    methods: &[(&str, &[(&str, BlobMsgType)])],
+) -> Vec<u8> {
+    server_message(UbusCmdType::DATA, sequence, 0, |reply| {
+        reply.put_str(BlobAttrId::OBJPATH, path)?;
+        reply.put_u32(BlobAttrId::OBJID, id)?;
+        reply.put_u32(BlobAttrId::OBJTYPE, id + 1)?;
+        reply.put_signature(methods)
+    })
+}
+
+#[test]
+fn lookup_all() {
+    let mut rx = HELLO.to_vec();
+    rx.extend(lookup_reply(
+        1,
+        "file",
+        0x10,
+        &[("read", &[("path", BlobMsgType::STRING)])],
+    ));
+    rx.extend(lookup_reply(
+        1,
+        "system",
+        0x20,
+        &[("board", &[]), ("info", &[])],
+    ));
+    rx.extend(server_message(UbusCmdType::STATUS, 1, 0, |status| {
+        status.put_i32(BlobAttrId::STATUS, 0)
+    }));
+    let mut connection = Connection::new(MockIo::new(rx)).unwrap();
+
+    let objects = connection.lookup_all().unwrap();
+    assert_eq!(objects.len(), 2);
+    assert_eq!(objects[0].path, "file");
+    assert_eq!((objects[0].id, objects[0].ty), (0x10, 0x11));
+    assert_eq!(objects[0].methods["read"]["path"], BlobMsgType::STRING);
+    assert_eq!(objects[1].path, "system");
+    assert_eq!(objects[1].methods.len(), 2);
+    assert!(objects[1].methods["board"].is_empty());
+}
+
+#[test]
+fn lookup_all_status() {
+    let mut rx = HELLO.to_vec();
+    rx.extend(server_message(UbusCmdType::STATUS, 1, 0, |status| {
+        status.put_i32(
+            BlobAttrId::STATUS,
+            UbusStatus::PERMISSION_DENIED.value() as i32,
+        )
+    }));
+    let mut connection = Connection::new(MockIo::new(rx)).unwrap();
+    assert!(matches!(
+        connection.lookup_all(),
+        Err(UbusError::Status(UbusStatus::PERMISSION_DENIED))
+    ));
+}
+
+#[test]
+fn multi_part_lookup() {
+    let mut rx = HELLO.to_vec();
+    rx.extend(lookup_reply(1, "dnsmasq", 0x10, &[]));
+    rx.extend(server_message(UbusCmdType::PING, 9, 0, |_| Ok(())));
+    rx.extend(lookup_reply(1, "file", 0x20, &[]));
+    // A stray reply to some other sequence number is skipped
+    rx.extend(lookup_reply(5, "stale", 0x30, &[]));
+    rx.extend(lookup_reply(1, "system", 0x40, &[]));
+    rx.extend(server_message(UbusCmdType::STATUS, 1, 0, |status| {
+        status.put_i32(BlobAttrId::STATUS, 0)
+    }));
+    let mut io = MockIo::new(rx);
+    let mut connection = Connection::new(&mut io).unwrap();
+
+    let mut paths = Vec::new();
+    connection
+        .lookup("", |obj| paths.push(obj.path.to_string()))
+        .unwrap();
+    assert_eq!(paths, ["dnsmasq", "file", "system"]);
+
+    drop(connection);
+    let sent = sent(io.written());
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[0].header().cmd_type, UbusCmdType::LOOKUP);
+    assert_eq!(sent[1].header().cmd_type, UbusCmdType::DATA);
+    assert_eq!(u16::from(sent[1].header().sequence), 9);
+}
+
+#[test]
+fn invoke_stream() {
+    let chunk = |sequence, index: u32| {
+        server_message(UbusCmdType::DATA, sequence, 0xabcd, |reply| {
+            reply.put_u32(BlobAttrId::OBJID, 0xabcd)?;
+            reply.put_nested(BlobAttrId::DATA, |data| data.put_u32("chunk", index))
+        })
+    };
+    let mut rx = HELLO.to_vec();
+    rx.extend(chunk(1, 0));
+    rx.extend(server_message(UbusCmdType::PING, 3, 0, |_| Ok(())));
+    rx.extend(chunk(1, 1));
+    rx.extend(chunk(7, 99));
+    rx.extend(chunk(1, 2));
+    rx.extend(server_message(UbusCmdType::STATUS, 1, 0xabcd, |status| {
+        status.put_i32(BlobAttrId::STATUS, UbusStatus::NOT_FOUND.value() as i32)
+    }));
+    let mut connection = Connection::new(MockIo::new(rx)).unwrap();
+
+    let mut chunks = Vec::new();
+    let status = connection
+        .invoke_stream(0xabcd, "list", &[], |data| {
+            let Some(BlobMsgPayload::Int32(index)) = data.get_msg("chunk") else {
+                panic!("no chunk index in {data:?}");
+            };
+            chunks.push(index);
+        })
+        .unwrap();
+    assert_eq!(status, UbusStatus::NOT_FOUND);
+    assert_eq!(chunks, [0, 1, 2]);
+}
+
+#[test]
+fn request_layout_matches_libubus() {
+    let mut io = MockIo::new(HELLO.to_vec());
+    let mut connection = Connection::new(&mut io).unwrap();
+
+    // ubus_invoke(ctx, 0x2d3bb2e4, "info", NULL, ...): OBJID, METHOD, then an empty DATA
+    connection.invoke_request(0x2d3bb2e4, "info", &[]).unwrap();
+    // ubus_lookup(ctx, "system", ...): just OBJPATH. Nothing answers, so the lookup fails
+    // once the request is out.
+    assert!(connection.lookup("system", |_| {}).is_err());
+
+    drop(connection);
+    let (invoke, lookup) = io.written().split_at(36);
+    assert_eq!(
+        invoke,
+        [
+            0x00, 0x05, 0x00, 0x01, 0x2d, 0x3b, 0xb2, 0xe4, 0x00, 0x00, 0x00, 0x1c, 0x03, 0x00,
+            0x00, 0x08, 0x2d, 0x3b, 0xb2, 0xe4, 0x04, 0x00, 0x00, 0x09, b'i', b'n', b'f', b'o',
+            0x00, 0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x04,
+        ]
+    );
+    assert_eq!(
+        lookup,
+        [
+            0x00, 0x04, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x02, 0x00,
+            0x00, 0x0b, b's', b'y', b's', b't', b'e', b'm', 0x00, 0x00,
+        ]
+    );
+}
+
+#[test]
+fn notify_frame_matches_libubus() {
+    let mut io = MockIo::new(HELLO.to_vec());
+    let mut connection = Connection::new(&mut io).unwrap();
+
+    // ubus_notify(ctx, obj, "link.up", &b, ...) for object 0x2468 and an empty blob_buf.
+    // The type byte is UBUS_MSG_NOTIFY, 10. Nothing answers, so notify fails once it's sent.
+    assert!(connection.notify(0x2468, "link.up", &[]).is_err());
+    drop(connection);
+    assert_eq!(
+        io.written(),
+        [
+            0x00, 0x0a, 0x00, 0x01, 0x00, 0x00, 0x24, 0x68, 0x00, 0x00, 0x00, 0x1c, 0x03, 0x00,
+            0x00, 0x08, 0x00, 0x00, 0x24, 0x68, 0x04, 0x00, 0x00, 0x0c, b'l', b'i', b'n', b'k',
+            b'.', b'u', b'p', 0x00, 0x07, 0x00, 0x00, 0x04,
+        ]
+    );
+}
+
+#[test]
+fn active_from_wire_frame() {
+    // ubus_notify_subscription() telling the owner of 0x1111 it gained a subscriber:
+    // type UBUS_MSG_NOTIFY (10) with OBJID and a one-byte ACTIVE
+    let frame = [
+        0x00, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x14, 0x03, 0x00, 0x00,
+        0x08, 0x00, 0x00, 0x11, 0x11, 0x09, 0x00, 0x00, 0x05, 0x01, 0x00, 0x00, 0x00,
+    ];
+    let rx = [
+        HELLO.to_vec(),
+        server_message(UbusCmdType::DATA, 1, 0, |reply| {
+            reply.put_u32(BlobAttrId::OBJID, 0x1111)?;
+            reply.put_u32(BlobAttrId::OBJTYPE, 0x2222)
+        }),
+        server_message(UbusCmdType::STATUS, 1, 0, |reply| {
+            reply.put_i32(BlobAttrId::STATUS, 0)
+        }),
+        frame.to_vec(),
+    ];
+    let mut connection = Connection::new(MockIo::new(rx.concat())).unwrap();
+    let object = connection.add_object("test", &[]).unwrap();
+    assert!(!connection.has_subscribers(object.id));
+
+    let message = connection.next_message().unwrap();
+    assert_eq!(message.header.cmd_type.value(), 10);
+    assert!(connection.has_subscribers(object.id));
+}
+
+#[test]
+fn lookup_cb_incomplete_reply() {
+    // A SIGNATURE with no OBJID before it, as a truncated or hostile reply might send
+    let mut rx = HELLO.to_vec();
+    rx.extend(server_message(UbusCmdType::DATA, 1, 0, |reply| {
+        reply.put_str(BlobAttrId::OBJPATH, "file")?;
+        reply.put_signature(&[("read", &[])])
+    }));
+    rx.extend(server_message(UbusCmdType::STATUS, 1, 0, |status| {
+        status.put_i32(BlobAttrId::STATUS, 0)
+    }));
+    let mut connection = Connection::new(MockIo::new(rx)).unwrap();
+    let result = connection.lookup_cb("", |_| panic!("no object"), |_| panic!("no method"));
+    assert!(matches!(result, Err(UbusError::InvalidData(_))));
+}
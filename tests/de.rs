@@ -0,0 +1,137 @@
+#![cfg(feature = "serde")]
+use serde::Deserialize;
+use ubus::*;
+
+/// Build a DATA message; `build` fills in its blobmsg table
+fn data(buffer: &mut [u8], build: impl FnOnce(&mut TableBuilder)) -> &[u8] {
+    let header = UbusMsgHeader {
+        version: UbusMsgVersion::CURRENT,
+        cmd_type: UbusCmdType::DATA,
+        sequence: 1u16.into(),
+        peer: 0u32.into(),
+    };
+    let mut message = UbusMsgBuilder::new(buffer, &header).unwrap();
+    let mut table = message.open_table(BlobAttrId::DATA).unwrap();
+    build(&mut table);
+    table.close().unwrap();
+    message.finish()
+}
+
+fn parse<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, UbusError> {
+    let message = UbusMsg::from_bytes(bytes).unwrap();
+    from_blob(&message.blob.get(BlobAttrId::DATA).unwrap())
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Route {
+    target: String,
+    mask: u8,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct IfaceStatus<'a> {
+    up: bool,
+    l3_device: String,
+    proto: &'a str,
+    uptime: u32,
+    dns: Vec<String>,
+    route: Route,
+    metric: Option<u32>,
+    gateway: Option<String>,
+    #[serde(default)]
+    mtu: u32,
+}
+
+#[test]
+fn reads_struct() {
+    let mut buffer = [0u8; 512];
+    let bytes = data(&mut buffer, |reply| {
+        reply.put_bool("up", true).unwrap();
+        reply.put_str("l3_device", "eth0").unwrap();
+        reply.put_str("proto", "dhcp").unwrap();
+        reply.put_u32("uptime", 1234).unwrap();
+        let mut dns = reply.open_array("dns").unwrap();
+        dns.push_str("1.1.1.1").unwrap();
+        dns.push_str("8.8.8.8").unwrap();
+        dns.close().unwrap();
+        let mut route = reply.open_table("route").unwrap();
+        route.put_str("target", "0.0.0.0").unwrap();
+        route.put_serialized("mask", &0u8).unwrap();
+        route.close().unwrap();
+        reply.put_u32("metric", 10).unwrap();
+        reply
+            .put_str("ignored", "extra entries are skipped")
+            .unwrap();
+    });
+    let status: IfaceStatus = parse(bytes).unwrap();
+    assert_eq!(
+        status,
+        IfaceStatus {
+            up: true,
+            l3_device: "eth0".to_string(),
+            proto: "dhcp",
+            uptime: 1234,
+            dns: vec!["1.1.1.1".to_string(), "8.8.8.8".to_string()],
+            route: Route {
+                target: "0.0.0.0".to_string(),
+                mask: 0,
+            },
+            metric: Some(10),
+            gateway: None,
+            mtu: 0,
+        }
+    );
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+struct Uptime {
+    uptime: u32,
+}
+
+#[test]
+fn type_mismatch() {
+    let mut buffer = [0u8; 64];
+    let bytes = data(&mut buffer, |reply| {
+        reply.put_str("uptime", "1234").unwrap()
+    });
+    assert!(matches!(
+        parse::<Uptime>(bytes),
+        Err(UbusError::Deserialize(_))
+    ));
+}
+
+#[test]
+fn missing_field() {
+    let mut buffer = [0u8; 64];
+    let bytes = data(&mut buffer, |reply| reply.put_u32("load", 1).unwrap());
+    assert!(matches!(
+        parse::<Uptime>(bytes),
+        Err(UbusError::Deserialize(_))
+    ));
+}
+
+#[derive(Deserialize)]
+struct Unsigned {
+    value: u32,
+}
+
+#[derive(Deserialize)]
+struct Signed {
+    value: i64,
+}
+
+#[derive(Deserialize)]
+struct Narrow {
+    #[allow(dead_code)]
+    value: u8,
+}
+
+#[test]
+fn integer_widths() {
+    let mut buffer = [0u8; 64];
+    let bytes = data(&mut buffer, |reply| reply.put_i32("value", -1).unwrap());
+    assert_eq!(parse::<Unsigned>(bytes).unwrap().value, u32::MAX);
+    assert_eq!(parse::<Signed>(bytes).unwrap().value, -1);
+    assert!(parse::<Narrow>(bytes).is_err());
+}
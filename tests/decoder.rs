@@ -0,0 +1,97 @@
+#![cfg(feature = "alloc")]
+use ubus::*;
+
+fn message(sequence: u16, method: &str) -> Vec<u8> {
+    let header = UbusMsgHeader::new(UbusCmdType::INVOKE, sequence, 0x2468);
+    UbusMsgBuilder::build_vec(&header, |message| {
+        message.put_u32(BlobAttrId::OBJID, 0x2468)?;
+        message.put_str(BlobAttrId::METHOD, method)
+    })
+    .unwrap()
+}
+
+#[test]
+fn byte_by_byte() {
+    let bytes = message(1, "info");
+    let mut decoder = UbusMsgDecoder::new();
+    for byte in &bytes[..bytes.len() - 1] {
+        assert!(decoder.push(&[*byte]).unwrap().is_none());
+    }
+    let message = decoder.push(&bytes[bytes.len() - 1..]).unwrap().unwrap();
+    assert_eq!(message.as_bytes(), &bytes[..]);
+    assert_eq!(decoder.buffered(), 0);
+}
+
+#[test]
+fn several_per_push() {
+    let (first, second) = (message(1, "info"), message(2, "status"));
+    let mut stream = [first.clone(), second.clone()].concat();
+    // Half of a third message trails the first two
+    let third = message(3, "reload");
+    stream.extend_from_slice(&third[..10]);
+
+    let mut decoder = UbusMsgDecoder::new();
+    let message = decoder.push(&stream).unwrap().unwrap();
+    assert_eq!(message.as_bytes(), &first[..]);
+    let message = decoder.next_msg().unwrap().unwrap();
+    assert_eq!(
+        message.as_msg().blob.get_str(BlobAttrId::METHOD),
+        Some("status")
+    );
+    assert!(decoder.next_msg().unwrap().is_none());
+    assert_eq!(decoder.buffered(), 10);
+
+    let message = decoder.push(&third[10..]).unwrap().unwrap();
+    assert_eq!(u16::from(message.header().sequence), 3);
+}
+
+#[test]
+fn bad_preamble() {
+    let mut bytes = message(1, "info");
+    bytes[0] = 1;
+    let mut decoder = UbusMsgDecoder::new();
+    assert!(decoder.push(&bytes[..4]).unwrap().is_none());
+    assert!(matches!(
+        decoder.push(&bytes[4..]),
+        Err(UbusError::UnsupportedVersion(_))
+    ));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn write_to_and_back() {
+    let mut log = Vec::new();
+    let bytes = [message(1, "info"), message(2, "status")];
+    for bytes in &bytes {
+        UbusMsg::from_bytes(bytes)
+            .unwrap()
+            .write_to(&mut log)
+            .unwrap();
+    }
+    assert_eq!(log, bytes.concat());
+
+    let mut decoder = UbusMsgDecoder::new();
+    let first = decoder.push(&log).unwrap().unwrap();
+    let second = decoder.next_msg().unwrap().unwrap();
+    assert_eq!(first.as_bytes(), &bytes[0][..]);
+    assert_eq!(second.as_bytes(), &bytes[1][..]);
+    assert!(decoder.next_msg().unwrap().is_none());
+}
+
+#[test]
+fn peek_header() {
+    let bytes = message(4, "info");
+    let mut decoder = UbusMsgDecoder::new();
+    assert!(decoder.peek_header().unwrap().is_none());
+    assert!(decoder.push(&bytes[..8]).unwrap().is_none());
+
+    // The type is known before the tag or payload arrive, and peeking consumes nothing
+    let header = decoder.peek_header().unwrap().unwrap();
+    assert_eq!(header.cmd_type, UbusCmdType::INVOKE);
+    assert_eq!(u16::from(header.sequence), 4);
+    assert_eq!(decoder.buffered(), 8);
+
+    let message = decoder.push(&bytes[8..]).unwrap().unwrap();
+    assert_eq!(message.as_bytes(), &bytes[..]);
+    assert!(decoder.peek_header().unwrap().is_none());
+}
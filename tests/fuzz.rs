@@ -0,0 +1,91 @@
+#![cfg(feature = "json")]
+//! Malformed input must produce errors, never panics. Runs the same checks as the
+//! cargo-fuzz target in `fuzz/` over mutations of a valid message.
+use ubus::*;
+
+/// Parse `bytes` and walk everything that can be decoded from them
+fn exercise(bytes: &[u8]) {
+    let Ok(message) = UbusMsg::from_bytes(bytes) else {
+        return;
+    };
+    let _ = format!("{:?} {:?}", message, message.blob);
+    let _ = format!("{:?}", message.hex_dump());
+    let _ = message.status();
+    let _ = UbusMonitorMsg::from_msg(&message);
+    for blob in message.blob.iter() {
+        let _ = UbusMsgAttr::from(blob).to_string();
+        let _ = parse_signature(&blob).count();
+        let _ = blobmsg_to_json(&blob);
+        for (_, value) in blob.msgs() {
+            let _ = value.to_string();
+        }
+    }
+}
+
+fn message() -> Vec<u8> {
+    let header = UbusMsgHeader {
+        version: UbusMsgVersion::CURRENT,
+        cmd_type: UbusCmdType::DATA,
+        sequence: 1u16.into(),
+        peer: 0x13333337u32.into(),
+    };
+    let mut buffer = [0u8; 256];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header).unwrap();
+    message.put_u32(BlobAttrId::OBJID, 0x2468).unwrap();
+    message.put_str(BlobAttrId::METHOD, "status").unwrap();
+    message
+        .put_signature(&[("read", &[("path", BlobMsgType::STRING)])])
+        .unwrap();
+    message
+        .put_nested(BlobAttrId::DATA, |data| {
+            data.put_str("name", "eth0")?;
+            data.put_u64("rx", 1 << 40)?;
+            let mut dns = data.open_array("dns")?;
+            dns.push_str("1.1.1.1")?;
+            dns.push_nested(|entry| entry.put_bool("up", true))?;
+            dns.close()
+        })
+        .unwrap();
+    message.finish().to_vec()
+}
+
+#[test]
+fn truncated() {
+    let bytes = message();
+    for len in 0..bytes.len() {
+        exercise(&bytes[..len]);
+    }
+}
+
+#[test]
+fn mutated() {
+    let bytes = message();
+    for index in 0..bytes.len() {
+        for value in [0x00, 0x01, 0x7f, 0x80, 0xff, bytes[index] ^ 0x04] {
+            let mut bytes = bytes.clone();
+            bytes[index] = value;
+            exercise(&bytes);
+        }
+    }
+}
+
+#[test]
+fn random() {
+    let mut state = 0x2545f491u32;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as u8
+    };
+    for _ in 0..2000 {
+        let len = next() as usize;
+        let mut bytes: Vec<u8> = (0..len).map(|_| next()).collect();
+        // Mostly well-formed preambles, so parsing gets past the header
+        if len >= 12 {
+            bytes[0] = 0;
+            bytes[8..12].copy_from_slice(&(len as u32 - 8).to_be_bytes());
+        }
+        exercise(&bytes);
+    }
+}
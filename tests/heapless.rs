@@ -0,0 +1,36 @@
+#![cfg(feature = "heapless")]
+use ubus::*;
+
+fn header() -> UbusMsgHeader {
+    UbusMsgHeader {
+        version: UbusMsgVersion::CURRENT,
+        cmd_type: UbusCmdType::INVOKE,
+        sequence: 1u16.into(),
+        peer: 0x13333337u32.into(),
+    }
+}
+
+fn put_attrs(message: &mut UbusMsgBuilder) -> Result<(), UbusError> {
+    message.put_u32(BlobAttrId::OBJID, 0x13333337)?;
+    message.put_str(BlobAttrId::METHOD, "info")?;
+    let mut data = message.open_table(BlobAttrId::DATA)?;
+    data.put_bool("up", true)?;
+    data.close()
+}
+
+#[test]
+fn matches_slice_builder() {
+    let mut buffer = [0u8; 64];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    put_attrs(&mut message).unwrap();
+    let expected = message.finish();
+
+    let bytes = UbusMsgBuilder::build_heapless::<64>(&header(), put_attrs).unwrap();
+    assert_eq!(&bytes[..], expected);
+}
+
+#[test]
+fn full() {
+    assert!(UbusMsgBuilder::build_heapless::<24>(&header(), put_attrs).is_err());
+    assert!(UbusMsgBuilder::build_heapless::<4>(&header(), |_| Ok(())).is_err());
+}
@@ -1,3 +1,4 @@
+#![cfg(feature = "std")]
 use std::io::{Read, Write};
 use std::os::unix::net::UnixStream;
 use ubus::*;
@@ -27,6 +28,52 @@ fn test() {
         .unwrap();
 }
 
+#[test]
+fn invoke_reply() {
+    let (client, mut server) = UnixStream::pair().unwrap();
+
+    std::thread::spawn(move || {
+        server.write_all(TEST_HELLO).unwrap();
+        let mut command = [0u8; TEST_TX.len()];
+        server.read_exact(&mut command).unwrap();
+        for i in TEST_RX {
+            server.write_all(i).unwrap();
+        }
+    });
+
+    let mut connection = Connection::new(client).unwrap();
+    let reply = connection.invoke_reply(0x13333337, "info", &[]).unwrap();
+
+    let names: Vec<_> = BlobIter::<Blob>::new(&reply)
+        .map(|blob| TryInto::<BlobMsg>::try_into(blob).unwrap().name)
+        .collect();
+    assert_eq!(names, ["localtime", "uptime", "load", "memory", "swap"]);
+    assert_eq!(reply.len(), 0x128 - 4);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn invoke_owned() {
+    let (client, mut server) = UnixStream::pair().unwrap();
+
+    std::thread::spawn(move || {
+        server.write_all(TEST_HELLO).unwrap();
+        let mut command = [0u8; TEST_TX.len()];
+        server.read_exact(&mut command).unwrap();
+        for i in TEST_RX {
+            server.write_all(i).unwrap();
+        }
+    });
+
+    let mut connection = Connection::new(client).unwrap();
+    let replies = connection.invoke_owned(0x13333337, "info", &[]).unwrap();
+
+    assert_eq!(replies.len(), 1);
+    let message = replies[0].as_msg();
+    assert_eq!(message.header.cmd_type, UbusCmdType::DATA);
+    assert!(message.blob.get(BlobAttrId::DATA).is_some());
+}
+
 const TEST_HELLO: &[u8] = &[
     0x00, 0x00, 0x00, 0x00, 0x2e, 0xb8, 0x63, 0xdb, 0x00, 0x00, 0x00, 0x04,
 ];
@@ -64,8 +111,9 @@ const TEST_RX: &[&[u8]] = &[
         0x00, 0x00, 0x14, 0x00, 0x04, 0x66, 0x72, 0x65, 0x65, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         0x00, 0x00, 0x00, 0x00,
     ],
+    // ubusd copies the request's peer into the STATUS, as into the DATA
     &[
-        0x00, 0x01, 0x00, 0x01, 0x13, 0x33, 0x33, 0x77, 0x00, 0x00, 0x00, 0x14,
+        0x00, 0x01, 0x00, 0x01, 0x13, 0x33, 0x33, 0x37, 0x00, 0x00, 0x00, 0x14,
     ],
     &[
         0x01, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x08, 0x85, 0x22, 0x59,
@@ -0,0 +1,127 @@
+#![cfg(all(feature = "std", feature = "mock"))]
+use std::io;
+use ubus::*;
+
+const STATUS: [u8; 20] = [
+    0x00, 0x01, 0x00, 0x01, 0x13, 0x33, 0x33, 0x37, 0x00, 0x00, 0x00, 0x0c, 0x01, 0x00, 0x00, 0x08,
+    0x00, 0x00, 0x00, 0x00,
+];
+
+#[test]
+fn one_byte_at_a_time() {
+    let mut io = MockIo::new(&STATUS[..]).with_chunk(1);
+    let mut buffer = [0xffu8; 64];
+    let message = UbusMsg::from_io(&mut io, &mut buffer).unwrap();
+    assert_eq!(message.header.cmd_type, UbusCmdType::STATUS);
+    assert_eq!(message.blob.get_u32(BlobAttrId::STATUS), Some(0));
+    assert_eq!(io.remaining(), 0);
+}
+
+#[test]
+fn eof_mid_message() {
+    // The transport returning zero bytes is ubusd going away, whether between messages or
+    // part way through one
+    for len in [0, 5, 12, 19] {
+        let mut buffer = [0u8; 64];
+        let result = UbusMsg::from_io(&mut MockIo::new(&STATUS[..len]).with_chunk(1), &mut buffer);
+        assert!(
+            matches!(&result, Err(UbusError::Disconnected)),
+            "{len}: {result:?}"
+        );
+    }
+
+    // A malformed message is not a disconnect
+    let mut bytes = STATUS;
+    bytes[11] = 0x02;
+    let mut buffer = [0u8; 64];
+    let result = UbusMsg::from_io(&mut MockIo::new(&bytes[..]).with_chunk(1), &mut buffer);
+    assert!(matches!(result, Err(UbusError::InvalidBlobTag(_))));
+}
+
+#[test]
+fn interrupted_reads_are_retried() {
+    let mut calls = 0;
+    let mut data = [0u8; 2];
+    read_exact(&mut data, |buf| {
+        calls += 1;
+        if calls == 1 {
+            return Err(UbusError::IO(io::ErrorKind::Interrupted.into()));
+        }
+        buf[0] = calls;
+        Ok(1)
+    })
+    .unwrap();
+    assert_eq!(data, [2, 3]);
+}
+
+#[test]
+fn short_writes_are_completed() {
+    let mut written = Vec::new();
+    let mut calls = 0;
+    write_all(&STATUS, |data| {
+        calls += 1;
+        if calls == 2 {
+            return Err(UbusError::IO(io::ErrorKind::Interrupted.into()));
+        }
+        let n = data.len().min(3);
+        written.extend_from_slice(&data[..n]);
+        Ok(n)
+    })
+    .unwrap();
+    assert_eq!(written, STATUS);
+
+    let result = write_all(&STATUS, |_| Ok(0));
+    assert!(matches!(result, Err(UbusError::IO(e)) if e.kind() == io::ErrorKind::WriteZero));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn buffered_writer() {
+    let mut writer = BufferedWriter::new(MockIo::new([0; 4]), 64);
+    for _ in 0..3 {
+        writer.put(&STATUS).unwrap();
+    }
+    assert!(writer.get_ref().written().is_empty());
+    assert_eq!(writer.buffered(), 60);
+
+    // The fourth message doesn't fit, so the first three go out together
+    writer.put(&STATUS).unwrap();
+    assert_eq!(writer.get_ref().written().len(), 60);
+    assert_eq!(writer.buffered(), 20);
+
+    // Anything larger than the buffer is written straight through, after what was buffered
+    writer.put(&[0; 100]).unwrap();
+    assert_eq!(writer.get_ref().written().len(), 180);
+    assert_eq!(writer.buffered(), 0);
+
+    // Reading flushes first, so requests are sent before waiting for replies
+    writer.put(&STATUS).unwrap();
+    writer.get(&mut [0; 4]).unwrap();
+    assert_eq!(writer.get_ref().written().len(), 200);
+
+    writer.put(&STATUS).unwrap();
+    let io = writer.into_inner().unwrap();
+    assert_eq!(io.written().len(), 220);
+    assert_eq!(&io.written()[..20], STATUS);
+    assert_eq!(&io.written()[200..], STATUS);
+}
+
+/// Reads a message from a file, so both `?`s below convert into `UbusError`
+fn status_from_file(path: &std::path::Path) -> Result<Option<u32>, UbusError> {
+    let bytes = std::fs::read(path)?;
+    let message = UbusMsg::from_bytes(&bytes)?;
+    Ok(message.blob.get_u32(BlobAttrId::STATUS))
+}
+
+#[test]
+fn from_io_error() {
+    let missing = std::env::temp_dir().join("ubus-rs-no-such-message");
+    let error = status_from_file(&missing).unwrap_err();
+    let UbusError::IO(ref inner) = error else {
+        panic!("expected an io error, got {error:?}");
+    };
+    assert_eq!(inner.kind(), io::ErrorKind::NotFound);
+
+    let error: UbusError = io::Error::from(io::ErrorKind::BrokenPipe).into();
+    assert!(matches!(error, UbusError::IO(e) if e.kind() == io::ErrorKind::BrokenPipe));
+}
@@ -1,3 +1,4 @@
+#![cfg(feature = "std")]
 use std::io::{Read, Write};
 use std::os::unix::net::UnixStream;
 use std::println;
@@ -36,6 +37,39 @@ fn test() {
         .unwrap();
 }
 
+#[test]
+fn lookup_parses_objects() {
+    let (client, mut server) = UnixStream::pair().unwrap();
+
+    std::thread::spawn(move || {
+        server.write_all(TEST_HELLO).unwrap();
+        let mut command = [0u8; 12];
+        server.read_exact(&mut command).unwrap();
+        for i in TEST_RX {
+            server.write_all(i).unwrap();
+        }
+    });
+
+    let mut connection = Connection::new(client).unwrap();
+
+    let mut objects = Vec::new();
+    connection
+        .lookup("", |obj| {
+            let read = obj.methods.get("read").map(|m| m.policy.len());
+            objects.push((obj.path.to_string(), obj.id, obj.methods.len(), read));
+            if obj.path == "file" {
+                let read = &obj.methods["read"].policy;
+                assert_eq!(read["path"], BlobMsgType::STRING);
+                assert_eq!(read["base64"], BlobMsgType::BOOL);
+            }
+        })
+        .unwrap();
+
+    assert_eq!(objects[0], ("dnsmasq".to_string(), 115032848, 1, None));
+    assert_eq!(objects[1], ("file".to_string(), 450594135, 7, Some(3)));
+    assert_eq!(objects[2].0, "hostapd");
+}
+
 // Data dumped from `ubus list`
 const TEST_HELLO: &[u8] = &[
     0x00, 0x00, 0x00, 0x00, 0x2e, 0xb8, 0x63, 0xdb, 0x00, 0x00, 0x00, 0x04,
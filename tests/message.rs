@@ -0,0 +1,451 @@
+#![cfg(all(feature = "std", feature = "mock"))]
+use ubus::*;
+
+#[test]
+fn unaligned_payload_keeps_stream_in_sync() {
+    let bytes = [
+        // DATA message whose blob ends with an unpadded 1-byte attribute
+        0x00, 0x02, 0x00, 0x01, 0x13, 0x33, 0x33, 0x37, 0x00, 0x00, 0x00, 0x09, 0x0a, 0x00, 0x00,
+        0x05, 0x01, // STATUS message straight after it
+        0x00, 0x01, 0x00, 0x01, 0x13, 0x33, 0x33, 0x37, 0x00, 0x00, 0x00, 0x0c, 0x01, 0x00, 0x00,
+        0x08, 0x00, 0x00, 0x00, 0x00,
+    ];
+    let mut io = MockIo::new(&bytes[..]);
+    let mut buffer = [0u8; 64];
+
+    let message = UbusMsg::from_io(&mut io, &mut buffer).unwrap();
+    assert_eq!(message.header.cmd_type, UbusCmdType::DATA);
+    assert_eq!(message.blob.data, &[0x0a, 0x00, 0x00, 0x05, 0x01]);
+    assert_eq!(message.blob.get_bool(BlobAttrId::NO_REPLY), Some(true));
+
+    let message = UbusMsg::from_io(&mut io, &mut buffer).unwrap();
+    assert_eq!(message.header.cmd_type, UbusCmdType::STATUS);
+    assert_eq!(message.blob.get_u32(BlobAttrId::STATUS), Some(0));
+    assert_eq!(io.remaining(), 0);
+}
+
+#[test]
+fn header_bytes() {
+    assert_eq!(UbusMsgHeader::SIZE, 8);
+    let bytes = [0x00, 0x05, 0x00, 0x01, 0x13, 0x33, 0x33, 0x37];
+    let header = UbusMsgHeader::from_bytes(bytes);
+    assert_eq!(header.version, UbusMsgVersion::CURRENT);
+    assert_eq!(header.cmd_type, UbusCmdType::INVOKE);
+    assert_eq!(u16::from(header.sequence), 1);
+    assert_eq!(u32::from(header.peer), 0x13333337);
+    assert_eq!(header.to_bytes(), bytes);
+}
+
+#[test]
+fn header_endianness() {
+    // Every multi-byte field is big-endian, in the positions ubusd reads them from
+    let header = UbusMsgHeader::new(UbusCmdType::STATUS, 0x0102, 0x0a0b0c0d);
+    assert_eq!(
+        header.to_bytes(),
+        [0x00, 0x01, 0x01, 0x02, 0x0a, 0x0b, 0x0c, 0x0d]
+    );
+    let header = UbusMsgHeader::from_bytes([0x00, 0x02, 0xfe, 0xff, 0x80, 0x00, 0x00, 0x01]);
+    assert_eq!(u16::from(header.sequence), 0xfeff);
+    assert_eq!(u32::from(header.peer), 0x80000001);
+
+    // Tags pack the extended bit, the id and the length into one big-endian u32
+    let tag = BlobTag::new(0x07, 0x010203, true).unwrap();
+    assert_eq!(tag.to_bytes(), [0x87, 0x01, 0x02, 0x03]);
+    let tag = BlobTag::from_bytes([0x03, 0x00, 0x00, 0x08]);
+    assert_eq!((tag.id(), tag.size(), tag.is_extended()), (3, 8, false));
+}
+
+#[test]
+fn two_phase_read() {
+    let header = UbusMsgHeader::new(UbusCmdType::DATA, 1, 0);
+    let unwanted = UbusMsgBuilder::build_vec(&header, |message| {
+        message.put_bytes(BlobAttrId::DATA, &[0x55; 1000])
+    })
+    .unwrap();
+    let header = UbusMsgHeader::new(UbusCmdType::STATUS, 2, 0);
+    let wanted =
+        UbusMsgBuilder::build_vec(&header, |message| message.put_i32(BlobAttrId::STATUS, 0))
+            .unwrap();
+    let stream = [unwanted, wanted].concat();
+    let mut io = MockIo::new(&stream[..]);
+
+    // Skip the large DATA without a buffer for it
+    let (header, tag) = UbusMsgHeader::from_io(&mut io).unwrap();
+    assert_eq!(header.cmd_type, UbusCmdType::DATA);
+    assert_eq!(tag.inner_len(), 1004);
+    UbusMsg::skip_blob(&mut io, tag).unwrap();
+
+    let (header, tag) = UbusMsgHeader::from_io(&mut io).unwrap();
+    assert_eq!(header.cmd_type, UbusCmdType::STATUS);
+    let mut buffer = [0u8; 16];
+    let blob = UbusMsg::read_blob(&mut io, tag, &mut buffer).unwrap();
+    assert_eq!(blob.get_i32(BlobAttrId::STATUS), Some(0));
+    assert_eq!(io.remaining(), 0);
+}
+
+#[test]
+fn unsupported_version() {
+    let bytes = [
+        0x01, 0x02, 0x00, 0x01, 0x13, 0x33, 0x33, 0x37, 0x00, 0x00, 0x00, 0x04,
+    ];
+    let mut buffer = [0u8; 64];
+    let result = UbusMsg::from_io(&mut MockIo::new(&bytes[..]), &mut buffer);
+    assert!(matches!(result, Err(UbusError::UnsupportedVersion(v)) if v.value() == 1));
+}
+
+#[test]
+fn invalid_blob_tag() {
+    // Outer tag claims 2 bytes, less than the tag itself
+    let bytes = [
+        0x00, 0x02, 0x00, 0x01, 0x13, 0x33, 0x33, 0x37, 0x00, 0x00, 0x00, 0x02,
+    ];
+    let mut buffer = [0u8; 64];
+    let result = UbusMsg::from_io(&mut MockIo::new(&bytes[..]), &mut buffer);
+    assert!(matches!(result, Err(UbusError::InvalidBlobTag(tag)) if tag.size() == 2));
+}
+
+#[test]
+fn oversized_or_undersized_buffers() {
+    // Payload larger than the receive buffer
+    let bytes = [
+        0x00, 0x02, 0x00, 0x01, 0x13, 0x33, 0x33, 0x37, 0x00, 0x00, 0x01, 0x00,
+    ];
+    let mut buffer = [0u8; 64];
+    let result = UbusMsg::from_io(&mut MockIo::new(&bytes[..]), &mut buffer);
+    assert!(matches!(result, Err(UbusError::InvalidData(_))));
+
+    // Receive buffer can't even hold the header and tag
+    let mut buffer = [0u8; 8];
+    let result = UbusMsg::from_io(&mut MockIo::new(&bytes[..]), &mut buffer);
+    assert!(matches!(result, Err(UbusError::InvalidData(_))));
+}
+
+#[test]
+fn max_msg_size() {
+    // Outer tag claims 2 MiB: rejected before the payload is read
+    let bytes = [
+        0x00, 0x02, 0x00, 0x01, 0x13, 0x33, 0x33, 0x37, 0x00, 0x20, 0x00, 0x00,
+    ];
+    let mut buffer = vec![0u8; 4 * MAX_MSG_SIZE];
+    let mut io = MockIo::new(&bytes[..]);
+    let result = UbusMsg::from_io(&mut io, &mut buffer);
+    assert!(matches!(result, Err(UbusError::MessageTooLarge(0x200000))));
+
+    // The builder stops at the limit too, however large its buffer
+    let mut buffer = vec![0u8; 2 * MAX_MSG_SIZE];
+    let header = UbusMsgHeader::new(UbusCmdType::DATA, 1, 0);
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header).unwrap();
+    let data = vec![0u8; MAX_MSG_SIZE];
+    assert!(matches!(
+        message.put_bytes(BlobAttrId::DATA, &data),
+        Err(UbusError::MessageTooLarge(_))
+    ));
+    let data = &data[..MAX_MSG_SIZE - 2 * BlobTag::SIZE];
+    message.put_bytes(BlobAttrId::DATA, data).unwrap();
+    assert_eq!(message.finish().len(), UbusMsgHeader::SIZE + MAX_MSG_SIZE);
+}
+
+#[test]
+fn monitor() {
+    let header = UbusMsgHeader {
+        version: UbusMsgVersion::CURRENT,
+        cmd_type: UbusCmdType::MONITOR,
+        sequence: 0u16.into(),
+        peer: 0u32.into(),
+    };
+    let attr = |id: UbusMonitorAttrId| BlobAttrId::from(id.value());
+
+    // The monitored message: an INVOKE of "info" on 0x13333337
+    let inner = [
+        0x03, 0x00, 0x00, 0x08, 0x13, 0x33, 0x33, 0x37, 0x04, 0x00, 0x00, 0x09, b'i', b'n', b'f',
+        b'o', 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    let mut buffer = [0u8; 128];
+    let mut frame = UbusMsgBuilder::new(&mut buffer, &header).unwrap();
+    frame
+        .put_u32(attr(UbusMonitorAttrId::CLIENT), 0x2468)
+        .unwrap();
+    frame
+        .put_u32(attr(UbusMonitorAttrId::PEER), 0x13333337)
+        .unwrap();
+    frame
+        .put_bool(attr(UbusMonitorAttrId::SEND), false)
+        .unwrap();
+    frame.put_u32(attr(UbusMonitorAttrId::TYPE), 5).unwrap();
+    frame.put_u32(attr(UbusMonitorAttrId::SEQ), 7).unwrap();
+    frame
+        .put_bytes(attr(UbusMonitorAttrId::DATA), &inner)
+        .unwrap();
+    let frame = frame.finish();
+
+    let mut buffer = [0u8; 128];
+    let message = UbusMsg::from_io(&mut MockIo::new(frame), &mut buffer).unwrap();
+    let monitored = UbusMonitorMsg::from_msg(&message).unwrap();
+    assert_eq!(monitored.client, 0x2468);
+    assert!(!monitored.send);
+
+    let message = monitored.message;
+    assert_eq!(message.header.cmd_type, UbusCmdType::INVOKE);
+    assert_eq!(u16::from(message.header.sequence), 7);
+    assert_eq!(u32::from(message.header.peer), 0x13333337);
+    assert_eq!(message.blob.data, &inner);
+    assert_eq!(message.blob.get_u32(BlobAttrId::OBJID), Some(0x13333337));
+    assert_eq!(message.blob.get_str(BlobAttrId::METHOD), Some("info"));
+}
+
+#[test]
+fn monitor_frame_from_ubusd() {
+    // ubusd_monitor_message() reporting that client 0x2468 sent an INVOKE of "info" on
+    // 0x13333337 with sequence 7: type UBUS_MSG_MONITOR (11), and the blob_buf was
+    // initialised with that id too. Attributes come in ubusd's order: CLIENT, PEER, SEQ,
+    // TYPE, SEND, DATA.
+    let frame = [
+        0x00, 0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0b, 0x00, 0x00, 0x44, 0x01, 0x00, 0x00,
+        0x08, 0x00, 0x00, 0x24, 0x68, 0x02, 0x00, 0x00, 0x08, 0x13, 0x33, 0x33, 0x37, 0x04, 0x00,
+        0x00, 0x08, 0x00, 0x00, 0x00, 0x07, 0x05, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x05, 0x03,
+        0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x18, 0x03, 0x00, 0x00, 0x08,
+        0x13, 0x33, 0x33, 0x37, 0x04, 0x00, 0x00, 0x09, b'i', b'n', b'f', b'o', 0x00, 0x00, 0x00,
+        0x00,
+    ];
+    let mut buffer = [0u8; 128];
+    let message = UbusMsg::from_io(&mut MockIo::new(&frame[..]), &mut buffer).unwrap();
+    assert_eq!(message.header.cmd_type, UbusCmdType::MONITOR);
+    let monitored = UbusMonitorMsg::from_msg(&message).unwrap();
+    assert_eq!(monitored.client, 0x2468);
+    assert!(!monitored.send);
+    let message = monitored.message;
+    assert_eq!(message.header.cmd_type, UbusCmdType::INVOKE);
+    assert_eq!(u16::from(message.header.sequence), 7);
+    assert_eq!(u32::from(message.header.peer), 0x13333337);
+    assert_eq!(message.blob.get_str(BlobAttrId::METHOD), Some("info"));
+}
+
+#[test]
+fn monitor_rejects_other_messages() {
+    let bytes = [
+        0x00, 0x01, 0x00, 0x01, 0x13, 0x33, 0x33, 0x37, 0x00, 0x00, 0x00, 0x04,
+    ];
+    let mut buffer = [0u8; 64];
+    let message = UbusMsg::from_io(&mut MockIo::new(&bytes[..]), &mut buffer).unwrap();
+    assert!(UbusMonitorMsg::from_msg(&message).is_err());
+
+    // A MONITOR frame without its attributes
+    let mut bytes = bytes;
+    bytes[1] = UbusCmdType::MONITOR.value();
+    let message = UbusMsg::from_io(&mut MockIo::new(&bytes[..]), &mut buffer).unwrap();
+    assert!(matches!(
+        UbusMonitorMsg::from_msg(&message),
+        Err(UbusError::InvalidData(_))
+    ));
+}
+
+#[test]
+fn cmd_type_display() {
+    assert_eq!(UbusCmdType::INVOKE.to_string(), "INVOKE");
+    assert_eq!(UbusCmdType::LOOKUP.name(), Some("LOOKUP"));
+    assert_eq!(UbusCmdType::from(0x3f).to_string(), "Unknown(0x3f)");
+    assert_eq!(UbusCmdType::from(0x3f).name(), None);
+    assert_eq!(BlobMsgType::TABLE.to_string(), "TABLE");
+    // UbusStatus keeps its libubus wording
+    assert_eq!(UbusStatus::NOT_FOUND.to_string(), "Not found");
+    assert_eq!(UbusStatus::NOT_FOUND.name(), Some("NOT_FOUND"));
+}
+
+#[test]
+fn into_iterator() {
+    let mut buffer = [0u8; 64];
+    let message = UbusMsgBuilder::new(&mut buffer, &UbusMsgHeader::new(UbusCmdType::INVOKE, 1, 2))
+        .unwrap()
+        .with_u32(BlobAttrId::OBJID, 0x2468)
+        .unwrap()
+        .with_str(BlobAttrId::METHOD, "info")
+        .unwrap()
+        .finish();
+    let message = UbusMsg::from_bytes(message).unwrap();
+
+    let mut attrs = Vec::new();
+    for Blob { tag, data } in &message {
+        attrs.push((tag.id(), data.len()));
+    }
+    assert_eq!(
+        attrs,
+        [
+            (BlobAttrId::OBJID.value(), 4),
+            (BlobAttrId::METHOD.value(), 5)
+        ]
+    );
+    assert_eq!((&message).into_iter().count(), message.blob.iter().count());
+}
+
+#[test]
+fn header_new() {
+    let header = UbusMsgHeader::new(UbusCmdType::INVOKE, 7, 0x2468);
+    assert_eq!(header.version, UbusMsgVersion::CURRENT);
+    assert_eq!(header.cmd_type, UbusCmdType::INVOKE);
+    assert_eq!(u16::from(header.sequence), 7);
+    assert_eq!(u32::from(header.peer), 0x2468);
+    assert_eq!(header.to_bytes(), [0, 5, 0, 7, 0, 0, 0x24, 0x68]);
+}
+
+#[test]
+fn peer_id() {
+    let header = UbusMsgHeader::from_bytes([0, 0, 0, 1, 0x13, 0x33, 0x33, 0x37]);
+    let peer = header.peer_id();
+    assert_eq!(peer, PeerId(0x13333337));
+    assert_eq!(u32::from(peer), 0x13333337);
+    assert_eq!(format!("{}", peer), "peer:0x13333337");
+    assert_eq!(format!("{:?}", PeerId::from(4)), "peer:0x00000004");
+}
+
+#[test]
+fn attr_display() {
+    assert_eq!(BlobAttrId::OBJPATH.to_string(), "OBJPATH");
+    assert_eq!(BlobAttrId::from(0x42).to_string(), "Unknown(0x42)");
+    assert_eq!(
+        UbusMsgAttr::ObjPath("system").to_string(),
+        "OBJPATH: \"system\""
+    );
+    assert_eq!(UbusMsgAttr::ObjId(0x2468).to_string(), "OBJID: 0x00002468");
+    assert_eq!(UbusMsgAttr::Status(4).to_string(), "STATUS: NOT_FOUND");
+    assert_eq!(
+        UbusMsgAttr::Unknown(0x42.into(), &[1, 2]).to_string(),
+        "Unknown(0x42): 2 bytes"
+    );
+    assert_eq!(UbusMsgAttr::NoReply(true).id(), BlobAttrId::NO_REPLY);
+}
+
+#[test]
+fn hex_dump() {
+    let bytes = b"\x00\x02\x00\x01\x00\x00\x24\x68hello ubus\x7f";
+    assert_eq!(
+        format!("{:?}", HexDump(bytes)),
+        "00000000  00 02 00 01 00 00 24 68  68 65 6c 6c 6f 20 75 62  |......$hhello ub|\n\
+         00000010  75 73 7f                                          |us.|"
+    );
+
+    let header = UbusMsgHeader {
+        version: UbusMsgVersion::CURRENT,
+        cmd_type: UbusCmdType::DATA,
+        sequence: 1u16.into(),
+        peer: 0x2468u32.into(),
+    };
+    let mut buffer = [0u8; 64];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header).unwrap();
+    message.put_u32(BlobAttrId::OBJID, 0x01020304).unwrap();
+    let message = UbusMsg::from_bytes(message.finish()).unwrap();
+    assert_eq!(
+        format!("{:?}", message.hex_dump()),
+        "DATA seq=1 peer=00002468 len=12\n\
+         00000000  00 02 00 01 00 00 24 68  00 00 00 0c 03 00 00 08  |......$h........|\n\
+         00000010  01 02 03 04                                       |....|"
+    );
+}
+
+#[test]
+fn signature() {
+    let header = UbusMsgHeader {
+        version: UbusMsgVersion::CURRENT,
+        cmd_type: UbusCmdType::DATA,
+        sequence: 1u16.into(),
+        peer: 0u32.into(),
+    };
+    let mut buffer = [0u8; 256];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header).unwrap();
+    message
+        .put_signature(&[
+            ("info", &[]),
+            (
+                "read",
+                &[("path", BlobMsgType::STRING), ("base64", BlobMsgType::BOOL)],
+            ),
+        ])
+        .unwrap();
+    let message = UbusMsg::from_bytes(message.finish()).unwrap();
+    let signature = message.blob.get(BlobAttrId::SIGNATURE).unwrap();
+
+    let methods: Vec<_> = parse_signature(&signature)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(methods.len(), 2);
+    assert_eq!(methods[0].0, "info");
+    assert!(methods[0].1.is_empty());
+    assert_eq!(methods[1].0, "read");
+    assert_eq!(methods[1].1["path"], BlobMsgType::STRING);
+    assert_eq!(methods[1].1["base64"], BlobMsgType::BOOL);
+
+    let mut buffer = [0u8; 64];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header).unwrap();
+    let mut table = message.open_table(BlobAttrId::SIGNATURE).unwrap();
+    table.put_str("broken", "not a policy").unwrap();
+    table.close().unwrap();
+    let message = UbusMsg::from_bytes(message.finish()).unwrap();
+    let signature = message.blob.get(BlobAttrId::SIGNATURE).unwrap();
+    assert!(parse_signature(&signature).next().unwrap().is_err());
+}
+
+#[test]
+fn data_entries() {
+    let header = UbusMsgHeader {
+        version: UbusMsgVersion::CURRENT,
+        cmd_type: UbusCmdType::DATA,
+        sequence: 1u16.into(),
+        peer: 0u32.into(),
+    };
+    let mut buffer = [0u8; 256];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header).unwrap();
+    message.put_u32(BlobAttrId::OBJID, 0x2468).unwrap();
+    message
+        .put_nested(BlobAttrId::DATA, |data| {
+            for (mac, signal) in [("00:11:22:33:44:55", -40), ("66:77:88:99:aa:bb", -71)] {
+                data.put_nested(mac, |client| client.put_i32("signal", signal))?;
+            }
+            Ok(())
+        })
+        .unwrap();
+    let message = UbusMsg::from_bytes(message.finish()).unwrap();
+
+    // The outer attributes are OBJID and DATA; DATA holds one table per client
+    assert_eq!(message.blob.iter().count(), 2);
+    let clients: Vec<BlobMsg> = message.data_entries().collect();
+    assert_eq!(clients.len(), 2);
+    assert_eq!(clients[0].name, "00:11:22:33:44:55");
+    assert!(matches!(
+        clients[0].get("signal"),
+        Some(BlobMsgPayload::Int32(-40))
+    ));
+    assert_eq!(clients[1].name, "66:77:88:99:aa:bb");
+    assert!(matches!(
+        clients[1].get("signal"),
+        Some(BlobMsgPayload::Int32(-71))
+    ));
+
+    let mut buffer = [0u8; 64];
+    let message = UbusMsgBuilder::new(&mut buffer, &header).unwrap().finish();
+    assert_eq!(
+        UbusMsg::from_bytes(message).unwrap().data_entries().count(),
+        0
+    );
+}
+
+#[test]
+fn payload_too_big_for_buffer_is_skipped() {
+    let header = UbusMsgHeader::new(UbusCmdType::DATA, 1, 0);
+    let big = UbusMsgBuilder::build_vec(&header, |message| {
+        message.put_bytes(BlobAttrId::DATA, &[0xaa; 200])
+    })
+    .unwrap();
+    let next = UbusMsgBuilder::build_vec(&header, |message| {
+        message.put_u32(BlobAttrId::OBJID, 0x2468)
+    })
+    .unwrap();
+    let bytes = [big, next].concat();
+    let mut io = MockIo::new(&bytes[..]);
+    let mut buffer = [0u8; 64];
+    assert!(matches!(
+        UbusMsg::from_io(&mut io, &mut buffer),
+        Err(UbusError::InvalidData(_))
+    ));
+    let message = UbusMsg::from_io(&mut io, &mut buffer).unwrap();
+    assert_eq!(message.blob.get_u32(BlobAttrId::OBJID), Some(0x2468));
+}
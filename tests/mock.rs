@@ -0,0 +1,65 @@
+#![cfg(feature = "mock")]
+use ubus::*;
+
+const HELLO: [u8; 12] = [
+    0x00, 0x00, 0x00, 0x00, 0x13, 0x33, 0x33, 0x37, 0x00, 0x00, 0x00, 0x04,
+];
+
+fn status(sequence: u16) -> Vec<u8> {
+    let header = UbusMsgHeader::new(UbusCmdType::STATUS, sequence, 0);
+    UbusMsgBuilder::build_vec(&header, |reply| reply.put_i32(BlobAttrId::STATUS, 0)).unwrap()
+}
+
+#[test]
+fn short_reads() {
+    let bytes = status(1);
+    let mut io = MockIo::new(bytes.clone()).with_chunk(1);
+    let mut buffer = [0u8; 64];
+    let message = UbusMsg::from_io(&mut io, &mut buffer).unwrap();
+    assert_eq!(message.to_vec(), bytes);
+    assert_eq!(io.remaining(), 0);
+}
+
+#[test]
+fn eof() {
+    let bytes = status(1);
+    let mut io = MockIo::new(&bytes[..bytes.len() - 1]);
+    let mut buffer = [0u8; 64];
+    let result = UbusMsg::from_io(&mut io, &mut buffer);
+    assert!(matches!(result, Err(UbusError::Disconnected)));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn drives_connection() {
+    let mut io = MockIo::new(HELLO);
+    io.push_rx(&status(1));
+    let mut connection = Connection::new(&mut io).unwrap();
+    assert_eq!(connection.peer(), PeerId(0x13333337));
+    connection.remove_object(0x2468).unwrap();
+    // ubusd has nothing more to say
+    assert!(matches!(
+        connection.remove_object(0x2468),
+        Err(UbusError::Disconnected)
+    ));
+    drop(connection);
+
+    let request = UbusMsg::from_bytes(io.written()).unwrap();
+    assert_eq!(request.header.cmd_type, UbusCmdType::REMOVE_OBJECT);
+    assert_eq!(request.blob.get_u32(BlobAttrId::OBJID), Some(0x2468));
+}
+
+#[test]
+fn records_writes() {
+    let mut io = MockIo::default();
+    io.put(&HELLO).unwrap();
+    io.put(&[1, 2]).unwrap();
+    assert_eq!(io.written().len(), 14);
+    assert_eq!(io.take_written()[12..], [1, 2]);
+    assert!(io.written().is_empty());
+
+    io.push_rx(&[7; 3]);
+    let mut data = [0u8; 3];
+    io.get(&mut data).unwrap();
+    assert_eq!(data, [7; 3]);
+}
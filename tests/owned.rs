@@ -0,0 +1,79 @@
+#![cfg(feature = "alloc")]
+use ubus::*;
+
+fn message(buffer: &mut [u8]) -> &[u8] {
+    let header = UbusMsgHeader {
+        version: UbusMsgVersion::CURRENT,
+        cmd_type: UbusCmdType::DATA,
+        sequence: 7u16.into(),
+        peer: 0x13333337u32.into(),
+    };
+    let mut message = UbusMsgBuilder::new(buffer, &header).unwrap();
+    message.put_u32(BlobAttrId::OBJID, 0x2468).unwrap();
+    message.put_str(BlobAttrId::METHOD, "info").unwrap();
+    message.finish()
+}
+
+#[test]
+fn outlives_buffer() {
+    let owned = {
+        let mut buffer = [0u8; 64];
+        let bytes = message(&mut buffer);
+        UbusOwnedMsg::from(&UbusMsg::from_bytes(bytes).unwrap())
+    };
+    assert_eq!(u16::from(owned.header().sequence), 7);
+    let message = owned.as_msg();
+    assert_eq!(message.blob.get_u32(BlobAttrId::OBJID), Some(0x2468));
+    assert_eq!(message.blob.get_str(BlobAttrId::METHOD), Some("info"));
+}
+
+#[test]
+fn from_bytes() {
+    let mut buffer = [0u8; 64];
+    let bytes = message(&mut buffer).to_vec();
+    let mut padded = bytes.clone();
+    padded.extend_from_slice(&[0xff; 8]);
+
+    let owned = UbusOwnedMsg::from_bytes(padded).unwrap();
+    assert_eq!(owned.as_bytes(), &bytes[..]);
+    assert_eq!(UbusOwnedMsg::from(&owned.as_msg()).into_bytes(), bytes);
+
+    assert!(UbusOwnedMsg::from_bytes(bytes[..4].to_vec()).is_err());
+}
+
+#[test]
+fn to_vec_matches_fixed_buffer() {
+    let mut buffer = [0u8; 64];
+    let fixed = message(&mut buffer);
+    let message = UbusMsg::from_bytes(fixed).unwrap();
+    assert_eq!(message.to_vec(), fixed);
+
+    let built = UbusMsgBuilder::build_vec(&message.header, |builder| {
+        builder.put_u32(BlobAttrId::OBJID, 0x2468)?;
+        builder.put_str(BlobAttrId::METHOD, "info")
+    })
+    .unwrap();
+    assert_eq!(built, fixed);
+}
+
+#[test]
+fn build_vec_size() {
+    let header = UbusMsgHeader::new(UbusCmdType::DATA, 1, 0);
+    let small = UbusMsgBuilder::build_vec(&header, |message| {
+        message.put_u32(BlobAttrId::OBJID, 0x2468)
+    })
+    .unwrap();
+    assert_eq!(small.len(), 20);
+    assert!(small.capacity() < 1024);
+
+    // Larger than 64 KiB, up to MAX_MSG_SIZE is allowed
+    let big = UbusMsgBuilder::build_vec(&header, |message| {
+        message.put_bytes(BlobAttrId::DATA, &[0xaa; 100 * 1024])
+    })
+    .unwrap();
+    let message = UbusMsg::from_bytes(&big).unwrap();
+    assert_eq!(
+        message.blob.get(BlobAttrId::DATA).unwrap().data.len(),
+        100 * 1024
+    );
+}
@@ -0,0 +1,137 @@
+#![cfg(all(feature = "std", feature = "mock"))]
+use ubus::*;
+
+/// Build a message with `build`, read it back through [`UbusMsg::from_io`] and hand it to
+/// `check`. The parsed message must reproduce the built bytes exactly, padding included.
+fn round_trip(
+    build: impl FnOnce(&mut UbusMsgBuilder) -> Result<(), UbusError>,
+    check: impl FnOnce(UbusMsg),
+) {
+    let header = UbusMsgHeader::new(UbusCmdType::INVOKE, 0x0102, 0x13333337);
+    let bytes = UbusMsgBuilder::build_vec(&header, build).unwrap();
+    let mut buffer = [0xaau8; 1024];
+    let mut io = MockIo::new(&bytes[..]).with_chunk(3);
+    let message = UbusMsg::from_io(&mut io, &mut buffer).unwrap();
+    assert_eq!(io.remaining(), 0);
+    assert_eq!(message.to_vec(), bytes);
+    assert_eq!(u16::from(message.header.sequence), 0x0102);
+    check(message);
+}
+
+#[test]
+fn scalars() {
+    round_trip(
+        |message| {
+            message.put_str(BlobAttrId::OBJPATH, "network.interface.lan")?;
+            message.put_u32(BlobAttrId::OBJID, 0xdeadbeef)?;
+            message.put_bool(BlobAttrId::NO_REPLY, true)?;
+            message.put_i32(BlobAttrId::STATUS, -2)?;
+            message.put_str(BlobAttrId::METHOD, "")?;
+            message.put_bytes(BlobAttrId::USER, b"abcde")
+        },
+        |message| {
+            let blob = message.blob;
+            assert_eq!(
+                blob.get_str(BlobAttrId::OBJPATH),
+                Some("network.interface.lan")
+            );
+            assert_eq!(blob.get_u32(BlobAttrId::OBJID), Some(0xdeadbeef));
+            assert_eq!(blob.get_bool(BlobAttrId::NO_REPLY), Some(true));
+            assert_eq!(blob.get_i32(BlobAttrId::STATUS), Some(-2));
+            assert_eq!(blob.get_str(BlobAttrId::METHOD), Some(""));
+            assert_eq!(blob.get(BlobAttrId::USER).unwrap().data, b"abcde");
+            assert_eq!(blob.iter().count(), 6);
+        },
+    );
+}
+
+#[test]
+fn nested() {
+    round_trip(
+        |message| {
+            message.put_u32(BlobAttrId::OBJID, 1)?;
+            message.put_nested(BlobAttrId::DATA, |data| {
+                data.put_str("a", "x")?;
+                data.put_u64("abcde", u64::MAX)?;
+                data.put_nested("inner", |inner| inner.put_bool("up", false))?;
+                let mut list = data.open_array("list")?;
+                list.push_i32(-1)?;
+                list.push_str("two")?;
+                list.close()
+            })
+        },
+        |message| {
+            let data = message.blob.get(BlobAttrId::DATA).unwrap();
+            let entries: Vec<_> = data.msgs().collect();
+            assert_eq!(entries.len(), 4);
+            assert!(matches!(entries[0], ("a", BlobMsgPayload::String("x"))));
+            assert!(matches!(entries[1], ("abcde", BlobMsgPayload::Int64(-1))));
+            let ("inner", BlobMsgPayload::Table(inner)) = &entries[2] else {
+                panic!("{:?}", entries[2]);
+            };
+            assert!(matches!(inner["up"], BlobMsgPayload::Int8(0)));
+            let ("list", BlobMsgPayload::Array(list)) = &entries[3] else {
+                panic!("{:?}", entries[3]);
+            };
+            assert!(matches!(list[0].data, BlobMsgPayload::Int32(-1)));
+            assert!(matches!(list[1].data, BlobMsgPayload::String("two")));
+        },
+    );
+}
+
+#[test]
+fn message_attrs() {
+    let attrs = || {
+        [
+            UbusMsgAttr::Status(4),
+            UbusMsgAttr::ObjPath("system"),
+            UbusMsgAttr::ObjId(0x2468),
+            UbusMsgAttr::Method("board"),
+            UbusMsgAttr::ObjType(0x1357),
+            UbusMsgAttr::Data(&[0x01, 0x02, 0x03]),
+            UbusMsgAttr::Target(0x9abc),
+            UbusMsgAttr::Active(true),
+            UbusMsgAttr::NoReply(false),
+            UbusMsgAttr::User("root"),
+            UbusMsgAttr::Group("wheel"),
+        ]
+    };
+    round_trip(
+        |message| attrs().into_iter().try_for_each(|attr| message.put(attr)),
+        |message| {
+            let parsed: Vec<UbusMsgAttr> = BlobIter::new(message.blob.data).collect();
+            let expected = attrs();
+            assert_eq!(parsed.len(), expected.len());
+            for (parsed, expected) in parsed.iter().zip(&expected) {
+                assert_eq!(parsed.id(), expected.id());
+                assert_eq!(parsed.to_string(), expected.to_string());
+            }
+        },
+    );
+}
+
+#[test]
+fn unknown_attrs() {
+    // A monitor or proxy forwards ids newer than this crate as-is
+    let unknown = BlobAttrId::from(0x55);
+    round_trip(
+        |message| {
+            message.put_u32(BlobAttrId::OBJID, 7)?;
+            message.put(UbusMsgAttr::Unknown(unknown, &[0xde, 0xad, 0xbe]))?;
+            message.put_str(BlobAttrId::METHOD, "status")
+        },
+        |message| {
+            let parsed: Vec<UbusMsgAttr> = BlobIter::new(message.blob.data).collect();
+            assert!(
+                matches!(parsed[1], UbusMsgAttr::Unknown(id, [0xde, 0xad, 0xbe]) if id == unknown)
+            );
+            assert_eq!(parsed[1].id().value(), 0x55);
+
+            let rebuilt = UbusMsgBuilder::build_vec(&message.header, |builder| {
+                parsed.into_iter().try_for_each(|attr| builder.put(attr))
+            })
+            .unwrap();
+            assert_eq!(rebuilt, message.to_vec());
+        },
+    );
+}
@@ -0,0 +1,113 @@
+#![cfg(feature = "serde")]
+use serde::Serialize;
+use std::collections::BTreeMap;
+use ubus::*;
+
+fn header() -> UbusMsgHeader {
+    UbusMsgHeader {
+        version: UbusMsgVersion::CURRENT,
+        cmd_type: UbusCmdType::INVOKE,
+        sequence: 1u16.into(),
+        peer: 0x13333337u32.into(),
+    }
+}
+
+#[derive(Serialize)]
+struct Small<'a> {
+    a: &'a str,
+    b: u16,
+}
+
+#[test]
+fn libubox_layout() {
+    // blobmsg_add_string(&b, "a", "x"); blobmsg_add_u16(&b, "b", 0x0102);
+    let mut buffer = [0u8; 64];
+    let bytes = to_blobmsg(&mut buffer, &Small { a: "x", b: 0x0102 }).unwrap();
+    assert_eq!(
+        bytes,
+        &[
+            0x83, 0x00, 0x00, 0x0a, 0x00, 0x01, b'a', 0x00, b'x', 0x00, 0x00, 0x00, 0x86, 0x00,
+            0x00, 0x0a, 0x00, 0x01, b'b', 0x00, 0x01, 0x02, 0x00, 0x00,
+        ]
+    );
+}
+
+#[derive(Serialize)]
+struct Inner {
+    mtu: u32,
+    up: bool,
+}
+
+#[derive(Serialize)]
+struct Outer<'a> {
+    name: &'a str,
+    metric: i64,
+    inner: Inner,
+    dns: Vec<&'a str>,
+    gateway: Option<&'a str>,
+}
+
+#[test]
+fn matches_builder() {
+    let value = Outer {
+        name: "lan",
+        metric: -1,
+        inner: Inner {
+            mtu: 1500,
+            up: true,
+        },
+        dns: vec!["1.1.1.1", "8.8.8.8"],
+        gateway: None,
+    };
+    let mut buffer = [0u8; 256];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    message.put_serialized(BlobAttrId::DATA, &value).unwrap();
+    let serialized = message.finish().to_vec();
+
+    let mut buffer = [0u8; 256];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header()).unwrap();
+    let mut data = message.open_table(BlobAttrId::DATA).unwrap();
+    data.put_str("name", "lan").unwrap();
+    data.put_i64("metric", -1).unwrap();
+    let mut inner = data.open_table("inner").unwrap();
+    inner.put_u32("mtu", 1500).unwrap();
+    inner.put_bool("up", true).unwrap();
+    inner.close().unwrap();
+    let mut dns = data.open_array("dns").unwrap();
+    dns.push_str("1.1.1.1").unwrap();
+    dns.push_str("8.8.8.8").unwrap();
+    dns.close().unwrap();
+    data.close().unwrap();
+    assert_eq!(serialized, message.finish());
+}
+
+#[test]
+fn maps_and_invoke_args() {
+    let mut value = BTreeMap::new();
+    value.insert("path", "/tmp");
+    value.insert("mode", "ro");
+    let mut buffer = [0u8; 64];
+    let args = to_blobmsg(&mut buffer, &value).unwrap();
+
+    let mut expected = [0u8; 64];
+    let mut message = UbusMsgBuilder::new(&mut expected, &header()).unwrap();
+    let mut data = message.open_table(BlobAttrId::DATA).unwrap();
+    data.put_str("mode", "ro").unwrap();
+    data.put_str("path", "/tmp").unwrap();
+    data.close().unwrap();
+    let expected = message.finish();
+    assert_eq!(args, &expected[16..]);
+}
+
+#[test]
+fn top_level_must_be_table() {
+    let mut buffer = [0u8; 64];
+    assert!(matches!(
+        to_blobmsg(&mut buffer, &5u32),
+        Err(UbusError::Serialize(_))
+    ));
+    assert!(matches!(
+        to_blobmsg(&mut buffer, &["a", "b"]),
+        Err(UbusError::Serialize(_))
+    ));
+}
@@ -0,0 +1,73 @@
+use ubus::*;
+
+#[test]
+fn into_result() {
+    assert_eq!(UbusStatus::OK.into_result(), Ok(()));
+    assert_eq!(
+        UbusStatus::NOT_FOUND.into_result(),
+        Err(UbusStatus::NOT_FOUND)
+    );
+    assert_eq!(
+        UbusStatus::from(42).into_result(),
+        Err(UbusStatus::from(42))
+    );
+}
+
+#[test]
+fn wire_values() {
+    assert_eq!(UbusStatus::from(0), UbusStatus::OK);
+    assert_eq!(UbusStatus::from(3), UbusStatus::METHOD_NOT_FOUND);
+    assert_eq!(UbusStatus::from(7), UbusStatus::TIMEOUT);
+    assert_eq!(UbusStatus::CONNECTION_FAILED.value(), 10);
+    assert_eq!(UbusStatus::SYSTEM_ERROR.value(), 13);
+    assert!(!UbusStatus::from(14).known());
+}
+
+#[test]
+fn display() {
+    assert_eq!(UbusStatus::OK.to_string(), "Success");
+    assert_eq!(
+        UbusStatus::PERMISSION_DENIED.to_string(),
+        "Permission denied"
+    );
+    assert_eq!(UbusStatus::from(99).to_string(), "Unknown status 99");
+    assert_eq!(format!("{:?}", UbusStatus::NO_DATA), "NO_DATA");
+}
+
+#[test]
+fn into_error() {
+    let error: UbusError = UbusStatus::INVALID_ARGUMENT.into();
+    assert!(matches!(
+        error,
+        UbusError::Status(UbusStatus::INVALID_ARGUMENT)
+    ));
+}
+
+#[test]
+fn message_status() {
+    let status = |bytes: &[u8]| UbusMsg::from_bytes(bytes).unwrap().status();
+
+    let bytes = [
+        0x00, 0x01, 0x00, 0x01, 0x13, 0x33, 0x33, 0x37, 0x00, 0x00, 0x00, 0x0c, 0x01, 0x00, 0x00,
+        0x08, 0x00, 0x00, 0x00, 0x04,
+    ];
+    assert_eq!(status(&bytes).unwrap(), UbusStatus::NOT_FOUND);
+
+    // Missing STATUS attribute
+    let empty = [
+        0x00, 0x01, 0x00, 0x01, 0x13, 0x33, 0x33, 0x37, 0x00, 0x00, 0x00, 0x04,
+    ];
+    assert!(matches!(status(&empty), Err(UbusError::InvalidData(_))));
+
+    // STATUS attribute that isn't 4 bytes
+    let short = [
+        0x00, 0x01, 0x00, 0x01, 0x13, 0x33, 0x33, 0x37, 0x00, 0x00, 0x00, 0x0a, 0x01, 0x00, 0x00,
+        0x06, 0x00, 0x04,
+    ];
+    assert!(matches!(status(&short), Err(UbusError::InvalidData(_))));
+
+    // Not a STATUS message at all
+    let mut data = bytes;
+    data[1] = UbusCmdType::DATA.value();
+    assert!(matches!(status(&data), Err(UbusError::InvalidData(_))));
+}
@@ -0,0 +1,87 @@
+#![cfg(feature = "std")]
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+use ubus::*;
+
+#[test]
+fn message_over_socket_pair() {
+    let (mut tx, rx) = UnixStream::pair().unwrap();
+
+    let header = UbusMsgHeader {
+        version: UbusMsgVersion::CURRENT,
+        cmd_type: UbusCmdType::INVOKE,
+        sequence: 7u16.into(),
+        peer: 0x13333337u32.into(),
+    };
+    let mut buffer = [0u8; 64];
+    let mut message = UbusMsgBuilder::new(&mut buffer, &header).unwrap();
+    message.put_u32(BlobAttrId::OBJID, 0x13333337).unwrap();
+    message.put_str(BlobAttrId::METHOD, "info").unwrap();
+    IO::put(&mut tx, message.finish()).unwrap();
+
+    // Read through a shared reference
+    let mut buffer = [0u8; 64];
+    let message = UbusMsg::from_io(&mut &rx, &mut buffer).unwrap();
+    assert_eq!(message.header.cmd_type, UbusCmdType::INVOKE);
+    assert_eq!(u16::from(message.header.sequence), 7);
+    assert_eq!(message.blob.get_u32(BlobAttrId::OBJID), Some(0x13333337));
+    assert_eq!(message.blob.get_str(BlobAttrId::METHOD), Some("info"));
+}
+
+#[test]
+fn eof_is_an_error() {
+    let (tx, rx) = UnixStream::pair().unwrap();
+    drop(tx);
+    let mut buffer = [0u8; 64];
+    assert!(matches!(
+        UbusMsg::from_io(&mut &rx, &mut buffer),
+        Err(UbusError::Disconnected)
+    ));
+}
+
+/// Talks to a real ubusd when `UBUS_SOCKET` is set, e.g. `/var/run/ubus/ubus.sock`
+#[test]
+fn real_ubusd() {
+    let Ok(path) = std::env::var("UBUS_SOCKET") else {
+        return;
+    };
+    let mut connection = Connection::connect(path.as_ref()).unwrap();
+    connection.lookup("", |_| {}).unwrap();
+}
+
+#[test]
+fn read_timeout() {
+    let (tx, rx) = UnixStream::pair().unwrap();
+    rx.set_read_timeout(Some(Duration::from_millis(10)))
+        .unwrap();
+    let mut buffer = [0u8; 64];
+    assert!(matches!(
+        UbusMsg::from_io(&mut &rx, &mut buffer),
+        Err(UbusError::Timeout)
+    ));
+    drop(tx);
+}
+
+#[test]
+fn invoke_timeout() {
+    let (client, mut server) = UnixStream::pair().unwrap();
+    IO::put(
+        &mut server,
+        &[
+            0x00, 0x00, 0x00, 0x00, 0x13, 0x33, 0x33, 0x37, 0x00, 0x00, 0x00, 0x04,
+        ],
+    )
+    .unwrap();
+
+    // ubusd never answers the INVOKE
+    let mut connection = Connection::new(client).unwrap();
+    let before = Some(Duration::from_secs(5));
+    connection.set_read_timeout(before).unwrap();
+    let result = connection.invoke_timeout(0x2468, "info", &[], Duration::from_millis(10), |_| {});
+    assert!(matches!(
+        result,
+        Err(UbusError::Status(UbusStatus::TIMEOUT))
+    ));
+    assert_eq!(connection.read_timeout().unwrap(), before);
+    drop(server);
+}